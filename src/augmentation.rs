@@ -0,0 +1,101 @@
+use crate::pipeline;
+
+/// 测试时增强默认使用的图片变体。
+pub const DEFAULT_AUGMENTATIONS: &[Augmentation] = &[
+    Augmentation::Original,
+    Augmentation::Resize(ResizeFilter::Triangle),
+    Augmentation::Resize(ResizeFilter::CatmullRom),
+    Augmentation::Pad(4),
+    Augmentation::Crop(2),
+    Augmentation::Contrast,
+    Augmentation::PngFix,
+    Augmentation::Invert,
+];
+
+/// 缩放图片使用的算法，同 `image::imageops::FilterType`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for image::imageops::FilterType {
+    fn from(value: ResizeFilter) -> Self {
+        match value {
+            ResizeFilter::Nearest => Self::Nearest,
+            ResizeFilter::Triangle => Self::Triangle,
+            ResizeFilter::CatmullRom => Self::CatmullRom,
+            ResizeFilter::Gaussian => Self::Gaussian,
+            ResizeFilter::Lanczos3 => Self::Lanczos3,
+        }
+    }
+}
+
+/// 测试时增强的图片变体，在颜色过滤之后、缩放到模型所需的大小之前应用，
+/// 所有变体缩放后的大小都与原图相同，所以时间步数量相同，可以使用 `EnsembleStrategy::Fusion`。
+///
+/// json 格式例如 `"original"`、`{"resize": "nearest"}`、`{"pad": 4}`、`{"crop": 2}`、`"contrast"`、`"pngFix"`、`"invert"`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Augmentation {
+    /// 原图。
+    Original,
+
+    /// 使用指定的算法缩放到模型所需的大小，默认使用的是 Lanczos3。
+    Resize(ResizeFilter),
+
+    /// 四周填充指定宽度的白色，然后缩放回模型所需的大小。
+    Pad(u32),
+
+    /// 四周裁剪指定宽度，然后缩放回模型所需的大小，图片太小的时候不裁剪。
+    Crop(u32),
+
+    /// 对比度拉伸，把最暗的灰度映射到 0，最亮的灰度映射到 255。
+    Contrast,
+
+    /// 使用与 png_fix 参数相反的设置。
+    PngFix,
+
+    /// 颜色反转，透明度保持不变。
+    Invert,
+}
+
+impl Augmentation {
+    /// 是否使用与 png_fix 参数相反的设置。
+    pub(crate) fn toggles_png_fix(self) -> bool {
+        self == Augmentation::PngFix
+    }
+
+    /// 生成图片变体，size 是图片缩放后模型所需的大小。
+    pub(crate) fn apply(
+        self,
+        image: &image::DynamicImage,
+        size: (u32, u32),
+    ) -> image::DynamicImage {
+        match self {
+            Augmentation::Original | Augmentation::PngFix => image.clone(),
+            Augmentation::Resize(filter) => image.resize_exact(size.0, size.1, filter.into()),
+            // 填充和裁剪会改变宽高比，缩放回原图缩放后的大小，保持时间步数量不变
+            Augmentation::Pad(n) => pipeline::pad(image, n).resize_exact(
+                size.0,
+                size.1,
+                image::imageops::FilterType::Lanczos3,
+            ),
+            Augmentation::Crop(n) => {
+                if image.width() > n * 2 && image.height() > n * 2 {
+                    image
+                        .crop_imm(n, n, image.width() - n * 2, image.height() - n * 2)
+                        .resize_exact(size.0, size.1, image::imageops::FilterType::Lanczos3)
+                } else {
+                    image.clone()
+                }
+            }
+            Augmentation::Contrast => pipeline::contrast_stretch(image),
+            Augmentation::Invert => pipeline::invert(image.clone()),
+        }
+    }
+}
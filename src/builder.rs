@@ -0,0 +1,400 @@
+use crate::{Calibration, Charset, DetectionModel, Error, OcrModel, Result};
+
+/// 图优化等级。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GraphOptimizationLevel {
+    /// 关闭所有优化。
+    Disable,
+
+    /// 基础优化，例如常量折叠、删除冗余节点。
+    Basic,
+
+    /// 扩展优化，包含更复杂的节点融合。
+    Extended,
+
+    /// 所有优化，包含布局优化，这是 ONNX Runtime 的默认值。
+    All,
+}
+
+impl std::str::FromStr for GraphOptimizationLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "disable" | "0" => Self::Disable,
+            "basic" | "1" => Self::Basic,
+            "extended" | "2" => Self::Extended,
+            "all" | "3" => Self::All,
+            _ => {
+                return Err(Error::InvalidOption(format!(
+                    "graph optimization level: {}",
+                    s
+                )))
+            }
+        })
+    }
+}
+
+impl From<GraphOptimizationLevel> for ort::GraphOptimizationLevel {
+    fn from(value: GraphOptimizationLevel) -> Self {
+        match value {
+            GraphOptimizationLevel::Disable => ort::GraphOptimizationLevel::Disable,
+            GraphOptimizationLevel::Basic => ort::GraphOptimizationLevel::Level1,
+            GraphOptimizationLevel::Extended => ort::GraphOptimizationLevel::Level2,
+            GraphOptimizationLevel::All => ort::GraphOptimizationLevel::Level3,
+        }
+    }
+}
+
+/// 执行提供者，按照添加的顺序注册，前面的不支持的算子会回退到后面的执行提供者。
+///
+/// 除了 CPU 之外，都需要 ONNX Runtime 编译了对应的支持（对应同名的 features），否则会返回
+/// `Error::ExecutionProviderUnavailable`。
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExecutionProvider {
+    /// 默认的 CPU 执行提供者。
+    Cpu,
+
+    /// XNNPACK，针对 CPU 优化的算子库，适合没有显卡的容器。
+    Xnnpack {
+        /// 线程数量，为 None 则使用 ONNX Runtime 的默认值。
+        threads: Option<usize>,
+    },
+
+    /// oneDNN，英特尔的 CPU 算子库。
+    OneDnn,
+
+    /// CUDA。
+    Cuda {
+        /// 显卡编号。
+        device_id: i32,
+
+        /// 显存上限，单位是字节。
+        memory_limit: usize,
+    },
+}
+
+impl ExecutionProvider {
+    /// CUDA，显存上限默认为 2 GiB。
+    pub fn cuda(device_id: i32) -> Self {
+        Self::Cuda {
+            device_id,
+            memory_limit: 2 * 1024 * 1024 * 1024,
+        }
+    }
+
+    /// 注册到 session，不可用的执行提供者会返回错误，而不是静默回退到 CPU。
+    fn register(&self, builder: &ort::SessionBuilder, memory_arena: Option<bool>) -> Result<()> {
+        fn register<E>(name: &str, provider: E, builder: &ort::SessionBuilder) -> Result<()>
+        where
+            E: ort::ExecutionProvider,
+        {
+            if !provider.is_available()? {
+                return Err(Error::ExecutionProviderUnavailable(name.to_string()));
+            }
+
+            Ok(provider.register(builder)?)
+        }
+
+        match self {
+            ExecutionProvider::Cpu => {
+                let mut cpu = ort::CPUExecutionProvider::default();
+
+                if memory_arena.unwrap_or(true) {
+                    cpu = cpu.with_arena_allocator();
+                }
+
+                register("CPU", cpu, builder)
+            }
+            ExecutionProvider::Xnnpack { threads } => {
+                let mut xnnpack = ort::XNNPACKExecutionProvider::default();
+
+                if let Some(v) = threads.and_then(std::num::NonZeroUsize::new) {
+                    xnnpack = xnnpack.with_intra_op_num_threads(v);
+                }
+
+                register("XNNPACK", xnnpack, builder)
+            }
+            ExecutionProvider::OneDnn => {
+                let mut onednn = ort::OneDNNExecutionProvider::default();
+
+                if memory_arena.unwrap_or(true) {
+                    onednn = onednn.with_arena_allocator();
+                }
+
+                register("oneDNN", onednn, builder)
+            }
+            ExecutionProvider::Cuda {
+                device_id,
+                memory_limit,
+            } => {
+                let cuda = ort::CUDAExecutionProvider::default()
+                    .with_device_id(*device_id)
+                    .with_arena_extend_strategy(ort::ArenaExtendStrategy::NextPowerOfTwo)
+                    .with_memory_limit(*memory_limit)
+                    .with_conv_algorithm_search(
+                        ort::CUDAExecutionProviderCuDNNConvAlgoSearch::Exhaustive,
+                    )
+                    .with_copy_in_default_stream(true);
+
+                register("CUDA", cuda, builder)
+            }
+        }
+    }
+}
+
+/// 解析命令行参数，例如 `cpu`、`xnnpack`、`xnnpack:4`、`onednn`、`cuda`、`cuda:1`。
+impl std::str::FromStr for ExecutionProvider {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, value) = match s.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+
+        let invalid = || Error::InvalidOption(format!("execution provider: {}", s));
+
+        Ok(match (name.to_ascii_lowercase().as_str(), value) {
+            ("cpu", None) => Self::Cpu,
+            ("xnnpack", threads) => Self::Xnnpack {
+                threads: threads
+                    .map(|v| v.parse().map_err(|_| invalid()))
+                    .transpose()?,
+            },
+            ("onednn", None) => Self::OneDnn,
+            ("cuda", device_id) => Self::cuda(
+                device_id
+                    .map(|v| v.parse().map_err(|_| invalid()))
+                    .transpose()?
+                    .unwrap_or(0),
+            ),
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+/// 创建模型时的 session 配置，没有设置的选项使用 ONNX Runtime 的默认值。
+///
+/// ```ignore
+/// let ocr = DdddocrBuilder::new()
+///     .intra_threads(2)
+///     .execution_provider(ExecutionProvider::Xnnpack { threads: None })
+///     .execution_provider(ExecutionProvider::Cpu)
+///     .ddddocr_classification()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DdddocrBuilder {
+    /// 算子内部的并行线程数。
+    intra_threads: Option<usize>,
+
+    /// 算子之间的并行线程数，设置后会开启并行执行模式。
+    inter_threads: Option<usize>,
+
+    /// 图优化等级。
+    optimization_level: Option<GraphOptimizationLevel>,
+
+    /// 是否开启 CPU 内存池。
+    memory_arena: Option<bool>,
+
+    /// 是否开启内存模式优化，输入尺寸不固定的时候关闭可以减少内存占用。
+    memory_pattern: Option<bool>,
+
+    /// 执行提供者，按顺序注册。
+    execution_providers: Vec<ExecutionProvider>,
+}
+
+impl DdddocrBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 算子内部的并行线程数。
+    pub fn intra_threads(mut self, threads: usize) -> Self {
+        self.intra_threads = Some(threads);
+        self
+    }
+
+    /// 算子之间的并行线程数，设置后会开启并行执行模式。
+    pub fn inter_threads(mut self, threads: usize) -> Self {
+        self.inter_threads = Some(threads);
+        self
+    }
+
+    /// 图优化等级。
+    pub fn optimization_level(mut self, level: GraphOptimizationLevel) -> Self {
+        self.optimization_level = Some(level);
+        self
+    }
+
+    /// 是否开启 CPU 内存池。
+    pub fn memory_arena(mut self, enable: bool) -> Self {
+        self.memory_arena = Some(enable);
+        self
+    }
+
+    /// 是否开启内存模式优化。
+    pub fn memory_pattern(mut self, enable: bool) -> Self {
+        self.memory_pattern = Some(enable);
+        self
+    }
+
+    /// 追加一个执行提供者。
+    pub fn execution_provider(mut self, provider: ExecutionProvider) -> Self {
+        self.execution_providers.push(provider);
+        self
+    }
+
+    /// 替换全部执行提供者。
+    pub fn execution_providers<P>(mut self, providers: P) -> Self
+    where
+        P: IntoIterator<Item = ExecutionProvider>,
+    {
+        self.execution_providers = providers.into_iter().collect();
+        self
+    }
+
+    /// 根据配置创建 session。
+    pub(crate) fn session(&self, model: &[u8]) -> Result<ort::Session> {
+        let mut builder = ort::Session::builder()?;
+
+        if let Some(v) = self.intra_threads {
+            builder = builder.with_intra_threads(v)?;
+        }
+
+        if let Some(v) = self.inter_threads {
+            builder = builder
+                .with_parallel_execution(true)?
+                .with_inter_threads(v)?;
+        }
+
+        if let Some(v) = self.optimization_level {
+            builder = builder.with_optimization_level(v.into())?;
+        }
+
+        if let Some(v) = self.memory_pattern {
+            builder = builder.with_memory_pattern(v)?;
+        }
+
+        for i in &self.execution_providers {
+            i.register(&builder, self.memory_arena)?;
+        }
+
+        // 没有显式添加 CPU 的时候，内存池的设置也要生效
+        if let Some(v) = self.memory_arena {
+            if !self.execution_providers.contains(&ExecutionProvider::Cpu) {
+                ExecutionProvider::Cpu.register(&builder, Some(v))?;
+            }
+        }
+
+        Ok(builder.commit_from_memory(model)?)
+    }
+
+    /// 从内存加载内容识别模型和字符集，不会加载可信度校准，见 `OcrModel::set_calibration`。
+    pub fn ocr<MODEL>(&self, model: MODEL, charset: Charset) -> Result<OcrModel<'static>>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        OcrModel::from_session(
+            self.session(model.as_ref())?,
+            crate::is_diy(model.as_ref()),
+            std::borrow::Cow::Owned(charset),
+        )
+    }
+
+    /// 从内存加载内容识别模型和字符集，不会加载可信度校准，见 `OcrModel::set_calibration`。
+    pub fn ocr_ref<'a, MODEL>(&self, model: MODEL, charset: &'a Charset) -> Result<OcrModel<'a>>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        OcrModel::from_session(
+            self.session(model.as_ref())?,
+            crate::is_diy(model.as_ref()),
+            std::borrow::Cow::Borrowed(charset),
+        )
+    }
+
+    /// 从文件加载内容识别模型和字符集，如果字符集旁边有可信度校准文件，则一起加载，见 `Calibration::path_for_charset`。
+    pub fn ocr_with_path<PATH1, PATH2>(
+        &self,
+        model: PATH1,
+        charset: PATH2,
+    ) -> Result<OcrModel<'static>>
+    where
+        PATH1: AsRef<std::path::Path>,
+        PATH2: AsRef<std::path::Path>,
+    {
+        let mut ocr = self.ocr(
+            std::fs::read(model)?,
+            serde_json::from_str(&std::fs::read_to_string(&charset)?)?,
+        )?;
+
+        let calibration = Calibration::path_for_charset(charset);
+
+        if calibration.is_file() {
+            ocr.set_calibration(Some(Calibration::with_path(calibration)?));
+        }
+
+        Ok(ocr)
+    }
+
+    /// 从内存加载目标检测模型。
+    pub fn detection<MODEL>(&self, model: MODEL) -> Result<DetectionModel>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        DetectionModel::from_session(self.session(model.as_ref())?)
+    }
+
+    /// 从文件加载目标检测模型。
+    pub fn detection_with_path<P>(&self, model: P) -> Result<DetectionModel>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.detection(std::fs::read(model)?)
+    }
+
+    /// 初始化内容识别，不会加载可信度校准，见 `OcrModel::set_calibration`。
+    #[cfg(feature = "inline-model")]
+    pub fn ddddocr_classification(&self) -> Result<OcrModel<'static>> {
+        self.ocr(
+            include_bytes!("../model/common.onnx"),
+            serde_json::from_str(include_str!("../model/common.json"))?,
+        )
+    }
+
+    /// 初始化内容识别。
+    #[cfg(not(feature = "inline-model"))]
+    pub fn ddddocr_classification(&self) -> Result<OcrModel<'static>> {
+        self.ocr_with_path("model/common.onnx", "model/common.json")
+    }
+
+    /// 使用旧模型初始化内容识别，不会加载可信度校准，见 `OcrModel::set_calibration`。
+    #[cfg(feature = "inline-model")]
+    pub fn ddddocr_classification_old(&self) -> Result<OcrModel<'static>> {
+        self.ocr(
+            include_bytes!("../model/common_old.onnx"),
+            serde_json::from_str(include_str!("../model/common_old.json"))?,
+        )
+    }
+
+    /// 使用旧模型初始化内容识别。
+    #[cfg(not(feature = "inline-model"))]
+    pub fn ddddocr_classification_old(&self) -> Result<OcrModel<'static>> {
+        self.ocr_with_path("model/common_old.onnx", "model/common_old.json")
+    }
+
+    /// 初始化目标检测。
+    #[cfg(feature = "inline-model")]
+    pub fn ddddocr_detection(&self) -> Result<DetectionModel> {
+        self.detection(include_bytes!("../model/common_det.onnx"))
+    }
+
+    /// 初始化目标检测。
+    #[cfg(not(feature = "inline-model"))]
+    pub fn ddddocr_detection(&self) -> Result<DetectionModel> {
+        self.detection_with_path("model/common_det.onnx")
+    }
+}
@@ -0,0 +1,237 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result};
+
+/// 拟合时把可信度限制在 (0, 1) 之内，避免 logit 变成无穷大。
+const EPSILON: f64 = 1e-6;
+
+/// 可信度校准的方法。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CalibrationMethod {
+    /// 温度缩放，只有一个参数，样本较少的时候也很稳定。
+    #[default]
+    Temperature,
+
+    /// 保序回归，可以拟合任意单调的关系，需要较多的样本。
+    Isotonic,
+}
+
+impl std::str::FromStr for CalibrationMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "temperature" => Self::Temperature,
+            "isotonic" => Self::Isotonic,
+            _ => return Err(Error::InvalidOption(format!("calibration method: {}", s))),
+        })
+    }
+}
+
+/// 可信度校准，把 `CharacterProbability::get_raw_confidence` 映射为整个结果识别正确的概率，
+/// 校准后 0.9 表示大约 90% 的结果是正确的，不同模型的阈值含义相同。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "method", rename_all = "camelCase")]
+pub enum Calibration {
+    /// 温度缩放，校准后的可信度为 `sigmoid(logit(p) / temperature)`。
+    Temperature { temperature: f64 },
+
+    /// 保序回归，points 是按可信度排序的 `(原始可信度, 校准后的可信度)`，之间线性插值。
+    Isotonic { points: Vec<(f64, f64)> },
+}
+
+impl Calibration {
+    /// 从样本拟合校准，每个样本是原始可信度和结果是否正确。
+    pub fn fit(samples: &[(f64, bool)], method: CalibrationMethod) -> Result<Self> {
+        if samples.is_empty() {
+            return Err(Error::InvalidCalibration("no samples".to_string()));
+        }
+
+        Ok(match method {
+            CalibrationMethod::Temperature => Self::Temperature {
+                temperature: fit_temperature(samples),
+            },
+            CalibrationMethod::Isotonic => Self::Isotonic {
+                points: fit_isotonic(samples),
+            },
+        })
+    }
+
+    /// 校准原始可信度。
+    pub fn apply(&self, confidence: f64) -> f64 {
+        match self {
+            Calibration::Temperature { temperature } => {
+                sigmoid(logit(confidence) / temperature.max(EPSILON))
+            }
+            Calibration::Isotonic { points } => {
+                let (Some(first), Some(last)) = (points.first(), points.last()) else {
+                    return confidence;
+                };
+
+                if confidence <= first.0 {
+                    return first.1;
+                }
+
+                if confidence >= last.0 {
+                    return last.1;
+                }
+
+                let i = points.partition_point(|v| v.0 <= confidence);
+                let (x1, y1) = points[i - 1];
+                let (x2, y2) = points[i];
+
+                if x2 > x1 {
+                    y1 + (y2 - y1) * (confidence - x1) / (x2 - x1)
+                } else {
+                    y1
+                }
+            }
+        }
+    }
+
+    /// 从 json 文件加载校准。
+    pub fn with_path<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// 保存为 json 文件，下次用 `Calibration::with_path` 加载。
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+
+    /// 字符集对应的校准文件的路径，与字符集放在一起，例如 `model/common.json` 对应 `model/common.calibration.json`。
+    pub fn path_for_charset<P>(charset: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        charset.as_ref().with_extension("calibration.json")
+    }
+}
+
+/// 标注数据集中的图片和标签，文件名中第一个 `_` 之前的部分是标签，例如 `a3bc_0001.png` 的标签是 `a3bc`，
+/// 没有 `_` 的时候整个文件名都是标签，只读取 png、jpg、jpeg、gif、bmp、webp 图片。
+pub fn labeled_images<P>(dir: P) -> Result<Vec<(PathBuf, String)>>
+where
+    P: AsRef<Path>,
+{
+    let mut result = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        let is_image = path.extension().and_then(|v| v.to_str()).is_some_and(|v| {
+            matches!(
+                v.to_ascii_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "gif" | "bmp" | "webp"
+            )
+        });
+
+        if !path.is_file() || !is_image {
+            continue;
+        }
+
+        let Some(stem) = path.file_stem().and_then(|v| v.to_str()) else {
+            continue;
+        };
+
+        let label = stem.split('_').next().unwrap_or(stem).to_string();
+
+        result.push((path, label));
+    }
+
+    result.sort();
+
+    Ok(result)
+}
+
+fn logit(p: f64) -> f64 {
+    let p = p.clamp(EPSILON, 1.0 - EPSILON);
+    (p / (1.0 - p)).ln()
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// 使用黄金分割搜索 ln(temperature)，使样本的负对数似然最小。
+fn fit_temperature(samples: &[(f64, bool)]) -> f64 {
+    let nll = |log_t: f64| {
+        let t = log_t.exp();
+
+        samples
+            .iter()
+            .map(|&(p, correct)| {
+                let q = sigmoid(logit(p) / t).clamp(EPSILON, 1.0 - EPSILON);
+                if correct {
+                    -q.ln()
+                } else {
+                    -(1.0 - q).ln()
+                }
+            })
+            .sum::<f64>()
+    };
+
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut a, mut b) = (-5.0f64, 5.0f64);
+
+    for _ in 0..100 {
+        let c = b - ratio * (b - a);
+        let d = a + ratio * (b - a);
+
+        if nll(c) < nll(d) {
+            b = d;
+        } else {
+            a = c;
+        }
+    }
+
+    ((a + b) / 2.0).exp()
+}
+
+/// 相邻违规合并算法（PAV），返回每个区间的平均可信度和正确率。
+fn fit_isotonic(samples: &[(f64, bool)]) -> Vec<(f64, f64)> {
+    let mut samples = samples.to_vec();
+    samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // 可信度相同的样本先合并成一组，(可信度之和, 正确的数量, 样本数量)
+    let mut groups = Vec::<(f64, f64, f64)>::new();
+    let mut last = None;
+
+    for (p, correct) in samples {
+        let y = if correct { 1.0 } else { 0.0 };
+
+        match groups.last_mut() {
+            Some(v) if last == Some(p) => *v = (v.0 + p, v.1 + y, v.2 + 1.0),
+            _ => groups.push((p, y, 1.0)),
+        }
+
+        last = Some(p);
+    }
+
+    let mut blocks = Vec::<(f64, f64, f64)>::new();
+
+    for group in groups {
+        blocks.push(group);
+
+        while blocks.len() >= 2 {
+            let (x2, y2, n2) = blocks[blocks.len() - 1];
+            let (x1, y1, n1) = blocks[blocks.len() - 2];
+
+            if y1 / n1 < y2 / n2 {
+                break;
+            }
+
+            blocks.pop();
+            *blocks.last_mut().unwrap() = (x1 + x2, y1 + y2, n1 + n2);
+        }
+    }
+
+    blocks.into_iter().map(|(x, y, n)| (x / n, y / n)).collect()
+}
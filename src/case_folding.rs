@@ -0,0 +1,108 @@
+use std::collections::{HashMap, HashSet};
+
+/// 不区分大小写识别时，输出结果使用的大小写。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LetterCase {
+    /// 输出小写字母，例如 `abc`。
+    #[default]
+    Lower,
+
+    /// 输出大写字母，例如 `ABC`。
+    Upper,
+}
+
+impl LetterCase {
+    /// 把单个字符转换成这种大小写，多个字符、空白、以及转换后不是单个字符的（例如 `ß`）保持不变。
+    pub fn convert(self, text: &str) -> String {
+        let mut chars = text.chars();
+
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return text.to_string();
+        };
+
+        let converted = match self {
+            LetterCase::Lower => c.to_lowercase().collect::<String>(),
+            LetterCase::Upper => c.to_uppercase().collect::<String>(),
+        };
+
+        if converted.chars().count() == 1 {
+            converted
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// 把字符范围扩展到所有大小写形式，只会加入模型字符集中存在的字符，
+/// 例如字符范围只有 `a`，模型中 `A` 的概率也会合并到 `a` 上。
+pub(crate) fn case_variants(ranges: &[String], charset: &[String]) -> Vec<String> {
+    let keys = ranges
+        .iter()
+        .map(|v| LetterCase::Lower.convert(v))
+        .collect::<HashSet<_>>();
+    let mut seen = ranges.iter().cloned().collect::<HashSet<_>>();
+    let mut result = ranges.to_vec();
+
+    for v in charset {
+        if keys.contains(&LetterCase::Lower.convert(v)) && seen.insert(v.clone()) {
+            result.push(v.clone());
+        }
+    }
+
+    result
+}
+
+/// 把字符集中只有大小写不同的字符分为一组，返回合并后的字符集和每组字符的下标，
+/// 组的顺序是每组第一个字符在字符集中出现的顺序。
+pub(crate) fn case_groups(charset: &[String], case: LetterCase) -> (Vec<String>, Vec<Vec<usize>>) {
+    let mut index = HashMap::<String, usize>::new();
+    let mut names = Vec::new();
+    let mut groups = Vec::<Vec<usize>>::new();
+
+    for (i, v) in charset.iter().enumerate() {
+        let key = LetterCase::Lower.convert(v);
+
+        match index.get(&key) {
+            Some(&n) => groups[n].push(i),
+            None => {
+                index.insert(key, groups.len());
+                names.push(case.convert(v));
+                groups.push(vec![i]);
+            }
+        }
+    }
+
+    (names, groups)
+}
+
+/// 合并每个时间步同一组字符的概率，概率直接相加，对数概率使用 log-sum-exp 相加。
+pub(crate) fn merge_groups(rows: &[Vec<f32>], groups: &[Vec<usize>], log: bool) -> Vec<Vec<f32>> {
+    rows.iter()
+        .map(|row| {
+            groups
+                .iter()
+                .map(|group| {
+                    if !log {
+                        return group.iter().map(|&i| row[i]).sum();
+                    }
+
+                    let max = group
+                        .iter()
+                        .map(|&i| row[i])
+                        .fold(f32::NEG_INFINITY, f32::max);
+
+                    if max.is_finite() {
+                        max + group
+                            .iter()
+                            .map(|&i| (row[i] - max).exp())
+                            .sum::<f32>()
+                            .ln()
+                    } else {
+                        max
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,282 @@
+use crate::{CharsetRange, Error, Result};
+
+/// 常用的 Unicode 区块，用于 `CharsetRange::Block`，只会选中模型字符集中存在的字符。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum UnicodeBlock {
+    /// 基本拉丁字母，U+0000..U+007F，即 ascii。
+    BasicLatin,
+
+    /// 希腊字母，U+0370..U+03FF。
+    Greek,
+
+    /// 西里尔字母，U+0400..U+04FF。
+    Cyrillic,
+
+    /// 中日韩符号和标点，U+3000..U+303F。
+    CjkSymbolsAndPunctuation,
+
+    /// 平假名，U+3040..U+309F。
+    Hiragana,
+
+    /// 片假名，U+30A0..U+30FF。
+    Katakana,
+
+    /// 中日韩统一表意文字扩展 A，U+3400..U+4DBF。
+    CjkUnifiedIdeographsExtensionA,
+
+    /// 中日韩统一表意文字，U+4E00..U+9FFF，即常用汉字。
+    CjkUnifiedIdeographs,
+
+    /// 韩文音节，U+AC00..U+D7AF。
+    HangulSyllables,
+
+    /// 半角及全角字符，U+FF00..U+FFEF。
+    HalfwidthAndFullwidthForms,
+}
+
+impl UnicodeBlock {
+    const ALL: [(UnicodeBlock, &'static str); 10] = [
+        (UnicodeBlock::BasicLatin, "basic_latin"),
+        (UnicodeBlock::Greek, "greek"),
+        (UnicodeBlock::Cyrillic, "cyrillic"),
+        (UnicodeBlock::CjkSymbolsAndPunctuation, "cjk_symbols"),
+        (UnicodeBlock::Hiragana, "hiragana"),
+        (UnicodeBlock::Katakana, "katakana"),
+        (UnicodeBlock::CjkUnifiedIdeographsExtensionA, "cjk_ext_a"),
+        (UnicodeBlock::CjkUnifiedIdeographs, "cjk"),
+        (UnicodeBlock::HangulSyllables, "hangul"),
+        (UnicodeBlock::HalfwidthAndFullwidthForms, "fullwidth"),
+    ];
+
+    /// 区块的码位范围，包含两端。
+    pub fn range(self) -> (char, char) {
+        match self {
+            UnicodeBlock::BasicLatin => ('\u{0000}', '\u{007f}'),
+            UnicodeBlock::Greek => ('\u{0370}', '\u{03ff}'),
+            UnicodeBlock::Cyrillic => ('\u{0400}', '\u{04ff}'),
+            UnicodeBlock::CjkSymbolsAndPunctuation => ('\u{3000}', '\u{303f}'),
+            UnicodeBlock::Hiragana => ('\u{3040}', '\u{309f}'),
+            UnicodeBlock::Katakana => ('\u{30a0}', '\u{30ff}'),
+            UnicodeBlock::CjkUnifiedIdeographsExtensionA => ('\u{3400}', '\u{4dbf}'),
+            UnicodeBlock::CjkUnifiedIdeographs => ('\u{4e00}', '\u{9fff}'),
+            UnicodeBlock::HangulSyllables => ('\u{ac00}', '\u{d7af}'),
+            UnicodeBlock::HalfwidthAndFullwidthForms => ('\u{ff00}', '\u{ffef}'),
+        }
+    }
+
+    /// 表达式中使用的名称，例如 `cjk`。
+    pub fn name(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(v, _)| *v == self)
+            .map(|(_, v)| *v)
+            .unwrap_or_default()
+    }
+
+    /// 根据名称查找区块，不区分大小写。
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(_, v)| v.eq_ignore_ascii_case(name))
+            .map(|(v, _)| *v)
+    }
+}
+
+/// 并集，例如 `CharsetRange::Digit + CharsetRange::Uppercase`。
+impl std::ops::Add for CharsetRange {
+    type Output = CharsetRange;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        match self {
+            CharsetRange::Union(mut v) => {
+                v.push(rhs);
+                CharsetRange::Union(v)
+            }
+            v => CharsetRange::Union(vec![v, rhs]),
+        }
+    }
+}
+
+/// 差集，例如 `CharsetRange::All - CharsetRange::Digit`。
+impl std::ops::Sub for CharsetRange {
+    type Output = CharsetRange;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        CharsetRange::Difference(Box::new(self), Box::new(rhs))
+    }
+}
+
+/// 解析字符范围表达式，0~7 表示内置的字符范围，其他的语法见 `CharsetRange` 的说明。
+impl std::str::FromStr for CharsetRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(v @ 0..=7) = s.trim().parse::<i32>() {
+            return CharsetRange::try_from(v);
+        }
+
+        let mut parser = Parser { source: s, pos: 0 };
+        let range = parser.expression()?;
+
+        parser.skip_whitespace();
+
+        match parser.peek() {
+            None => Ok(range),
+            Some(c) => Err(parser.error(&format!("unexpected '{}'", c))),
+        }
+    }
+}
+
+/// 字符范围表达式的递归下降解析器，运算符都是左结合的。
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.source[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidCharsetExpression(format!("{} at {} in {:?}", message, self.pos, self.source))
+    }
+
+    /// expression := term (('+' | '|' | '-') term)*
+    fn expression(&mut self) -> Result<CharsetRange> {
+        let mut range = self.term()?;
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some('+' | '|') => {
+                    self.bump();
+                    range = range + self.term()?;
+                }
+                Some('-') => {
+                    self.bump();
+                    range = range - self.term()?;
+                }
+                _ => return Ok(range),
+            }
+        }
+    }
+
+    /// term := '(' expression ')' | 字符串 | 码位范围 | 名称
+    fn term(&mut self) -> Result<CharsetRange> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('(') => {
+                self.bump();
+
+                let range = self.expression()?;
+
+                self.skip_whitespace();
+
+                match self.bump() {
+                    Some(')') => Ok(range),
+                    _ => Err(self.error("expected ')'")),
+                }
+            }
+            Some(quote @ ('"' | '\'')) => {
+                self.bump();
+
+                let end = self
+                    .rest()
+                    .find(quote)
+                    .ok_or_else(|| self.error("unterminated string"))?;
+                let text = self.rest()[..end].to_string();
+
+                self.pos += end + quote.len_utf8();
+
+                if text.is_empty() {
+                    return Err(self.error("empty string"));
+                }
+
+                Ok(CharsetRange::Other(text))
+            }
+            Some('U' | 'u') if self.rest()[1..].starts_with('+') => {
+                let start = self.code_point()?;
+
+                if self.rest().starts_with("..") {
+                    self.pos += 2;
+
+                    let end = self.code_point()?;
+
+                    if start > end {
+                        return Err(self.error("the start of the range is greater than the end"));
+                    }
+
+                    Ok(CharsetRange::CodePoints(start, end))
+                } else {
+                    Ok(CharsetRange::CodePoints(start, start))
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let len = self
+                    .rest()
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(self.rest().len());
+                let name = &self.rest()[..len];
+
+                let range = match name.to_ascii_lowercase().as_str() {
+                    "digit" => CharsetRange::Digit,
+                    "lower" | "lowercase" => CharsetRange::Lowercase,
+                    "upper" | "uppercase" => CharsetRange::Uppercase,
+                    "all" => CharsetRange::All,
+                    v => match UnicodeBlock::from_name(v) {
+                        Some(v) => CharsetRange::Block(v),
+                        None => return Err(self.error(&format!("unknown name {:?}", name))),
+                    },
+                };
+
+                self.pos += len;
+
+                Ok(range)
+            }
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    /// 码位，例如 `U+4E00`。
+    fn code_point(&mut self) -> Result<char> {
+        if !self.rest().starts_with(['U', 'u']) || !self.rest()[1..].starts_with('+') {
+            return Err(self.error("expected a code point like U+4E00"));
+        }
+
+        self.pos += 2;
+
+        let len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(self.rest().len());
+        let value = u32::from_str_radix(&self.rest()[..len], 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid code point"))?;
+
+        self.pos += len;
+
+        Ok(value)
+    }
+}
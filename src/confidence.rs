@@ -0,0 +1,56 @@
+/// 识别结果的状态。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecognitionStatus {
+    /// 可信度达到了阈值。
+    Accepted,
+
+    /// 可信度低于阈值，建议刷新验证码，而不是浪费一次提交的机会。
+    Unsure,
+}
+
+/// 带有可信度判断的识别结果。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Recognition {
+    /// 识别结果，状态为 Unsure 的时候也是最好的猜测。
+    pub text: String,
+
+    /// 是否达到了阈值。
+    pub status: RecognitionStatus,
+
+    /// 整体可信度，同 `CharacterProbability::get_confidence`。
+    pub confidence: f64,
+
+    /// 每个字符的可信度，同 `ctc_char_confidence`。
+    pub char_confidence: Vec<f64>,
+}
+
+impl Recognition {
+    /// 根据阈值判断状态，阈值为 None 表示不检查，
+    /// 整体可信度低于 min_confidence，或者任意一个字符的可信度低于 min_char_confidence，都是 Unsure。
+    pub fn new(
+        text: String,
+        confidence: f64,
+        char_confidence: Vec<f64>,
+        min_confidence: Option<f64>,
+        min_char_confidence: Option<f64>,
+    ) -> Self {
+        let unsure = min_confidence.is_some_and(|v| confidence < v)
+            || min_char_confidence.is_some_and(|v| char_confidence.iter().any(|&c| c < v));
+
+        Self {
+            text,
+            status: if unsure {
+                RecognitionStatus::Unsure
+            } else {
+                RecognitionStatus::Accepted
+            },
+            confidence,
+            char_confidence,
+        }
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        self.status == RecognitionStatus::Accepted
+    }
+}
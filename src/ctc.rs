@@ -0,0 +1,760 @@
+use std::collections::HashMap;
+
+use crate::{LanguageModelScorer, Lexicon, LexiconMatch, NgramModel, Pattern, Result};
+
+/// 默认的束宽度，用于没有指定束宽度的接口。
+pub const DEFAULT_BEAM_WIDTH: usize = 20;
+
+/// CTC 解码的候选结果。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Candidate {
+    /// 识别结果。
+    pub text: String,
+
+    /// 整个序列的概率，即所有能折叠成 text 的路径的概率之和。
+    pub probability: f64,
+}
+
+/// 一个前缀的概率，分为以空白结尾和以字符结尾两种，因为它们遇到重复字符时的处理不同。
+#[derive(Debug, Clone, Copy, Default)]
+struct PrefixProbability {
+    blank: f64,
+    non_blank: f64,
+}
+
+impl PrefixProbability {
+    fn total(&self) -> f64 {
+        self.blank + self.non_blank
+    }
+}
+
+/// 字符集中的空白字符，即空字符串。
+/// 官方模型的字符集第一个就是空白，限定字符范围后空白在最后。
+pub(crate) fn blank_index(charset: &[String]) -> Option<usize> {
+    charset.iter().position(|v| v.is_empty())
+}
+
+/// 把每个时间步的概率重新归一化，限定字符范围后概率之和不再是 1，负数当作 0 处理。
+pub(crate) fn normalize(probability: &[Vec<f32>]) -> Vec<Vec<f64>> {
+    probability
+        .iter()
+        .map(|row| {
+            let row = row.iter().map(|&v| v.max(0.0) as f64).collect::<Vec<_>>();
+            let sum = row.iter().sum::<f64>();
+
+            if sum > 0.0 {
+                row.into_iter().map(|v| v / sum).collect()
+            } else {
+                row
+            }
+        })
+        .collect()
+}
+
+/// 贪心解码中连续的相同字符，时间步范围左闭右开。
+pub(crate) struct Segment {
+    pub(crate) index: usize,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+impl Segment {
+    /// 字符的可信度，即它所在的时间步中这个字符的最大概率，normalized 是 `normalize` 的结果。
+    fn confidence(&self, normalized: &[Vec<f64>]) -> f64 {
+        normalized[self.start..self.end]
+            .iter()
+            .map(|row| row[self.index])
+            .fold(0.0, f64::max)
+    }
+}
+
+/// 取每个时间步概率最大的字符，合并连续的相同字符并去掉空白，概率和对数概率都可以使用。
+pub(crate) fn greedy_segments(probability: &[Vec<f32>], blank: Option<usize>) -> Vec<Segment> {
+    let mut result = Vec::<Segment>::new();
+    let mut last_item = None;
+
+    for (t, row) in probability.iter().enumerate() {
+        let c = row
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i);
+
+        match c {
+            Some(c) if Some(c) != blank => {
+                if last_item == Some(c) {
+                    // 连续的相同字符折叠成一个
+                    if let Some(v) = result.last_mut() {
+                        v.end = t + 1;
+                    }
+                } else {
+                    result.push(Segment {
+                        index: c,
+                        start: t,
+                        end: t + 1,
+                    });
+                }
+
+                last_item = Some(c);
+            }
+            _ => last_item = None,
+        }
+    }
+
+    result
+}
+
+/// CTC 贪心解码，取每个时间步概率最大的字符，然后合并连续的相同字符并去掉空白，参数的含义同 `ctc_beam_search`。
+///
+/// 这是 `OcrModel::classification` 和 `CharacterProbability::get_text` 共用的解码器，
+/// 概率和对数概率都可以使用，所以两者对同一张图片的结果总是相同。
+pub fn ctc_greedy_decode(probability: &[Vec<f32>], charset: &[String]) -> String {
+    greedy_segments(probability, blank_index(charset))
+        .iter()
+        .map(|v| charset[v.index].as_str())
+        .collect()
+}
+
+/// 前缀束搜索的约束，每个前缀都带有一个状态，例如格式限制。
+pub(crate) trait PrefixScorer {
+    type State: Clone;
+
+    /// 空前缀的状态。
+    fn start(&self) -> Self::State;
+
+    /// 这个字符是否有可能被追加，用于提前过滤字符。
+    fn allows(&self, _text: &str) -> bool {
+        true
+    }
+
+    /// 前缀追加一个字符，返回新的状态和分数的乘数，返回 None 表示不允许追加。
+    fn extend(&self, state: &Self::State, text: &str) -> Option<(Self::State, f64)>;
+
+    /// 搜索结束时分数的乘数，返回 None 表示这个前缀不是完整的结果。
+    fn finish(&self, state: &Self::State) -> Option<f64>;
+}
+
+/// 没有任何约束。
+impl PrefixScorer for () {
+    type State = ();
+
+    fn start(&self) -> Self::State {}
+
+    fn extend(&self, _state: &Self::State, _text: &str) -> Option<(Self::State, f64)> {
+        Some(((), 1.0))
+    }
+
+    fn finish(&self, _state: &Self::State) -> Option<f64> {
+        Some(1.0)
+    }
+}
+
+/// 束中的一个前缀。
+#[derive(Debug, Clone)]
+struct Beam<S> {
+    probability: PrefixProbability,
+    state: S,
+    factor: f64,
+}
+
+impl<S> Beam<S> {
+    /// 用于排序的分数，即 CTC 概率乘以约束的乘数。
+    fn score(&self) -> f64 {
+        self.probability.total() * self.factor
+    }
+}
+
+/// CTC 前缀束搜索，返回概率最高的 top_n 个候选结果，按概率从高到低排序。
+///
+/// probability 是每个时间步每个字符的概率，charset 是对应的字符，即 `CharacterProbability` 的两个字段，
+/// 所以限定了字符范围的概率也可以直接使用。
+/// beam_width 是每个时间步保留的前缀数量，越大越准确，但是越慢，每个前缀也只会展开概率最高的 beam_width 个字符，以及前缀的最后一个字符。
+pub fn ctc_beam_search(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    beam_width: usize,
+    top_n: usize,
+) -> Vec<Candidate> {
+    prefix_beam_search(probability, charset, beam_width, top_n, &())
+}
+
+/// CTC 前缀束搜索，只返回满足格式的候选结果，例如 `\d{4}` 表示 4 个数字，参数的含义同 `ctc_beam_search`。
+///
+/// 每个前缀只展开满足格式的字符，所以即使模型认为某个位置更像字母，也能找到满足格式的结果。
+/// 如果 beam_width 太小，可能所有的前缀都没有满足格式，这时候返回空列表。
+pub fn ctc_pattern_search(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    pattern: &Pattern,
+    beam_width: usize,
+    top_n: usize,
+) -> Result<Vec<Candidate>> {
+    let automaton = pattern.automaton(charset)?;
+
+    Ok(prefix_beam_search(
+        probability,
+        charset,
+        beam_width,
+        top_n,
+        &automaton,
+    ))
+}
+
+/// CTC 前缀束搜索，每个前缀的分数是 CTC 概率乘以语言模型概率的 weight 次方，参数的含义同 `ctc_beam_search`。
+///
+/// 语言模型可以区分字形相似的字符，例如成语中的 "己" 和 "已"，weight 越大越偏向通顺的结果，0 等同于 `ctc_beam_search`。
+/// 候选结果按分数排序，但是 probability 仍然是 CTC 概率。
+pub fn ctc_language_model_search(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    model: &NgramModel,
+    weight: f64,
+    beam_width: usize,
+    top_n: usize,
+) -> Vec<Candidate> {
+    prefix_beam_search(
+        probability,
+        charset,
+        beam_width,
+        top_n,
+        &LanguageModelScorer { model, weight },
+    )
+}
+
+/// 带有约束的 CTC 前缀束搜索。
+pub(crate) fn prefix_beam_search<S>(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    beam_width: usize,
+    top_n: usize,
+    scorer: &S,
+) -> Vec<Candidate>
+where
+    S: PrefixScorer,
+{
+    let beam_width = beam_width.max(1);
+    let blank = blank_index(charset);
+    let probability = normalize(probability);
+    let allowed = charset
+        .iter()
+        .enumerate()
+        .map(|(i, v)| Some(i) != blank && scorer.allows(v))
+        .collect::<Vec<_>>();
+
+    let mut beams = HashMap::from([(
+        Vec::<usize>::new(),
+        Beam {
+            probability: PrefixProbability {
+                blank: 1.0,
+                non_blank: 0.0,
+            },
+            state: scorer.start(),
+            factor: 1.0,
+        },
+    )]);
+
+    for row in &probability {
+        // 按概率从高到低展开字符，空白单独处理
+        let mut chars = (0..row.len())
+            .filter(|&v| allowed[v] && row[v] > 0.0)
+            .collect::<Vec<_>>();
+        chars.sort_by(|&a, &b| row[b].total_cmp(&row[a]));
+
+        let mut next = HashMap::<Vec<usize>, Beam<S::State>>::new();
+
+        for (prefix, beam) in &beams {
+            let p = beam.probability;
+
+            if let Some(blank) = blank {
+                let pc = row[blank];
+
+                if pc > 0.0 {
+                    next.entry(prefix.clone())
+                        .or_insert_with(|| Beam {
+                            probability: PrefixProbability::default(),
+                            ..beam.clone()
+                        })
+                        .probability
+                        .blank += p.total() * pc;
+                }
+            }
+
+            // 返回是否展开了这个字符
+            let mut extend = |c: usize| {
+                let pc = row[c];
+
+                let extended_probability = if prefix.last() == Some(&c) {
+                    // 重复的字符中间必须隔着空白，否则会被折叠
+                    next.entry(prefix.clone())
+                        .or_insert_with(|| Beam {
+                            probability: PrefixProbability::default(),
+                            ..beam.clone()
+                        })
+                        .probability
+                        .non_blank += p.non_blank * pc;
+
+                    p.blank * pc
+                } else {
+                    p.total() * pc
+                };
+
+                let mut extended = prefix.clone();
+                extended.push(c);
+
+                let entry = match next.entry(extended) {
+                    std::collections::hash_map::Entry::Occupied(v) => v.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(v) => {
+                        match scorer.extend(&beam.state, &charset[c]) {
+                            Some((state, factor)) => v.insert(Beam {
+                                probability: PrefixProbability::default(),
+                                state,
+                                factor: beam.factor * factor,
+                            }),
+                            None => return false,
+                        }
+                    }
+                };
+
+                entry.probability.non_blank += extended_probability;
+                true
+            };
+
+            let last = prefix.last().copied();
+            let mut expanded = 0;
+            let mut repeated = false;
+
+            for &c in &chars {
+                if expanded >= beam_width {
+                    break;
+                }
+
+                repeated |= Some(c) == last;
+
+                if extend(c) {
+                    expanded += 1;
+                }
+            }
+
+            // 前缀的最后一个字符总是展开，否则概率不在前几名的时候会丢掉重复字符的概率
+            if let Some(c) = last.filter(|&v| !repeated && allowed[v] && row[v] > 0.0) {
+                extend(c);
+            }
+        }
+
+        let mut next = next.into_iter().collect::<Vec<_>>();
+        next.sort_by(|(a_prefix, a), (b_prefix, b)| {
+            b.score()
+                .total_cmp(&a.score())
+                .then_with(|| a_prefix.cmp(b_prefix))
+        });
+        next.truncate(beam_width);
+        beams = next.into_iter().collect();
+    }
+
+    // 不同的字符可能对应相同的文本，例如字符集里有重复的字符，所以按文本合并
+    let mut candidates = Vec::<(Candidate, f64)>::new();
+
+    for (prefix, beam) in beams {
+        let Some(factor) = scorer.finish(&beam.state) else {
+            continue;
+        };
+
+        let text = prefix
+            .iter()
+            .map(|&v| charset[v].as_str())
+            .collect::<String>();
+        let score = beam.score() * factor;
+
+        match candidates.iter_mut().find(|(v, _)| v.text == text) {
+            Some((v, s)) => {
+                v.probability += beam.probability.total();
+                *s += score;
+            }
+            None => candidates.push((
+                Candidate {
+                    text,
+                    probability: beam.probability.total(),
+                },
+                score,
+            )),
+        }
+    }
+
+    candidates.sort_by(|(a, a_score), (b, b_score)| {
+        b_score.total_cmp(a_score).then_with(|| a.text.cmp(&b.text))
+    });
+    candidates.truncate(top_n);
+    candidates.into_iter().map(|(v, _)| v).collect()
+}
+
+/// 对数概率相加，即 `ln(e^a + e^b)`。
+fn log_add(a: f64, b: f64) -> f64 {
+    if a == f64::NEG_INFINITY {
+        b
+    } else if b == f64::NEG_INFINITY {
+        a
+    } else {
+        a.max(b) + (-(a - b).abs()).exp().ln_1p()
+    }
+}
+
+/// 字符到字符集下标的映射，只包含单个字符，重复的字符使用第一个下标。
+fn charset_index(charset: &[String]) -> HashMap<char, usize> {
+    let mut index = HashMap::new();
+
+    for (i, v) in charset.iter().enumerate() {
+        let mut chars = v.chars();
+
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            index.entry(c).or_insert(i);
+        }
+    }
+
+    index
+}
+
+/// 每个时间步的对数概率。
+fn log_normalize(probability: &[Vec<f32>]) -> Vec<Vec<f64>> {
+    normalize(probability)
+        .into_iter()
+        .map(|row| row.into_iter().map(f64::ln).collect())
+        .collect()
+}
+
+/// CTC 前向算法，返回所有能折叠成 labels 的路径的对数概率之和。
+fn ctc_forward(log_probability: &[Vec<f64>], blank: Option<usize>, labels: &[usize]) -> f64 {
+    // 在字符之间和两端插入空白，例如 "ab" 变成 "_a_b_"
+    let extended = (0..labels.len() * 2 + 1)
+        .map(|s| {
+            if s % 2 == 0 {
+                blank
+            } else {
+                Some(labels[s / 2])
+            }
+        })
+        .collect::<Vec<_>>();
+    let emit = |row: &[f64], s: usize| extended[s].map_or(f64::NEG_INFINITY, |c| row[c]);
+
+    let Some((first, rest)) = log_probability.split_first() else {
+        return if labels.is_empty() {
+            0.0
+        } else {
+            f64::NEG_INFINITY
+        };
+    };
+
+    let mut alpha = vec![f64::NEG_INFINITY; extended.len()];
+
+    for (s, v) in alpha.iter_mut().enumerate().take(2) {
+        *v = emit(first, s);
+    }
+
+    for row in rest {
+        let mut next = vec![f64::NEG_INFINITY; extended.len()];
+
+        for s in 0..extended.len() {
+            let mut a = alpha[s];
+
+            if s >= 1 {
+                a = log_add(a, alpha[s - 1]);
+            }
+
+            // 不同的字符之间可以跳过空白，重复的字符中间必须隔着空白
+            if s >= 2 && s % 2 == 1 && extended[s] != extended[s - 2] {
+                a = log_add(a, alpha[s - 2]);
+            }
+
+            next[s] = a + emit(row, s);
+        }
+
+        alpha = next;
+    }
+
+    let last = extended.len() - 1;
+
+    if last >= 1 {
+        log_add(alpha[last], alpha[last - 1])
+    } else {
+        alpha[last]
+    }
+}
+
+/// 识别结果是 text 的概率，即所有能折叠成 text 的路径的概率之和，probability 和 charset 的含义同 `ctc_beam_search`。
+///
+/// text 按字符拆分，每个字符都要在字符集中，否则概率为 0。
+pub fn ctc_probability(probability: &[Vec<f32>], charset: &[String], text: &str) -> f64 {
+    let index = charset_index(charset);
+
+    let Some(labels) = text
+        .chars()
+        .map(|c| index.get(&c).copied())
+        .collect::<Option<Vec<_>>>()
+    else {
+        return 0.0;
+    };
+
+    ctc_forward(&log_normalize(probability), blank_index(charset), &labels).exp()
+}
+
+/// 词典解码，返回词典中概率最高的词语，以及与第二名的对数概率之差，probability 和 charset 的含义同 `ctc_beam_search`。
+///
+/// 每个词语的概率都用 CTC 前向算法精确计算，不会像束搜索一样漏掉结果，耗时与词典大小成正比。
+/// 包含字符集以外的字符的词语会被跳过，如果没有任何词语有可能，则返回 None。
+pub fn ctc_lexicon_search(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    lexicon: &Lexicon,
+) -> Option<LexiconMatch> {
+    let index = charset_index(charset);
+    let blank = blank_index(charset);
+    let log_probability = log_normalize(probability);
+
+    let mut best = None::<(&str, f64)>;
+    let mut second = f64::NEG_INFINITY;
+
+    for word in lexicon.words() {
+        let Some(labels) = word
+            .chars()
+            .map(|c| index.get(&c).copied())
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        let score = ctc_forward(&log_probability, blank, &labels);
+
+        if score == f64::NEG_INFINITY {
+            continue;
+        }
+
+        match best {
+            Some((_, v)) if score <= v => second = second.max(score),
+            _ => {
+                if let Some((_, v)) = best {
+                    second = v;
+                }
+
+                best = Some((word, score));
+            }
+        }
+    }
+
+    best.map(|(text, score)| LexiconMatch {
+        text: text.to_string(),
+        probability: score.exp(),
+        margin: (second > f64::NEG_INFINITY).then_some(score - second),
+    })
+}
+
+/// 对齐到时间步和原图位置的单个字符。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AlignedCharacter {
+    /// 字符。
+    pub text: String,
+
+    /// 字符的可信度，即它所在的时间步中这个字符的最大概率。
+    pub confidence: f64,
+
+    /// 字符所在的时间步范围，左闭右开。
+    pub start: usize,
+    pub end: usize,
+
+    /// 字符在原图中的横坐标范围，左右都包含，可以用来裁剪单个字符。
+    pub x1: u32,
+    pub x2: u32,
+}
+
+/// 贪心解码，并返回每个字符的可信度、所在的时间步和原图中的横坐标范围。
+///
+/// probability 和 charset 的含义同 `ctc_beam_search`，width 是原图的宽度。
+/// 图片只在横向按比例缩放，所以每个时间步对应原图中宽度相等的一段。
+pub fn ctc_align(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    width: u32,
+) -> Vec<AlignedCharacter> {
+    aligned_characters(
+        greedy_segments(probability, blank_index(charset)),
+        probability,
+        charset,
+        width,
+    )
+}
+
+/// 把指定的 text 对齐到能折叠成它的概率最大的路径，返回每个字符的可信度、所在的时间步和原图中的横坐标范围，
+/// 适用于束搜索、格式解码、词典解码等不是贪心解码的结果，text 不可能出现时返回 None。
+///
+/// text 按字符拆分，每个字符都要在字符集中，其他参数的含义同 `ctc_align`。
+pub fn ctc_align_text(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    text: &str,
+    width: u32,
+) -> Option<Vec<AlignedCharacter>> {
+    let path = best_path(probability, charset, text)?;
+
+    Some(aligned_characters(
+        path_segments(&path, blank_index(charset)),
+        probability,
+        charset,
+        width,
+    ))
+}
+
+/// 把时间步范围映射到原图的横坐标，并计算每个字符的可信度。
+fn aligned_characters(
+    segments: Vec<Segment>,
+    probability: &[Vec<f32>],
+    charset: &[String],
+    width: u32,
+) -> Vec<AlignedCharacter> {
+    let normalized = normalize(probability);
+    let frames = probability.len() as u64;
+    let width = width as u64;
+
+    segments
+        .into_iter()
+        .map(|v| {
+            // 时间步映射到原图的横坐标
+            let x1 = (v.start as u64 * width / frames) as u32;
+            let x2 = ((v.end as u64 * width).div_ceil(frames) as u32)
+                .saturating_sub(1)
+                .max(x1);
+
+            AlignedCharacter {
+                text: charset[v.index].clone(),
+                confidence: v.confidence(&normalized),
+                start: v.start,
+                end: v.end,
+                x1,
+                x2,
+            }
+        })
+        .collect()
+}
+
+/// 能折叠成 text 的概率最大的路径（Viterbi 对齐），返回每个时间步的字符下标，包括空白，
+/// text 不可能出现时返回 None。
+pub(crate) fn best_path(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    text: &str,
+) -> Option<Vec<usize>> {
+    let index = charset_index(charset);
+    let labels = text
+        .chars()
+        .map(|c| index.get(&c).copied())
+        .collect::<Option<Vec<_>>>()?;
+
+    if probability.is_empty() {
+        return labels.is_empty().then(Vec::new);
+    }
+
+    let log_probability = log_normalize(probability);
+
+    // 与 ctc_forward 相同，在字符之间和两端插入空白
+    let blank = blank_index(charset);
+    let extended = (0..labels.len() * 2 + 1)
+        .map(|s| {
+            if s % 2 == 0 {
+                blank
+            } else {
+                Some(labels[s / 2])
+            }
+        })
+        .collect::<Vec<_>>();
+    let emit = |row: &[f64], s: usize| extended[s].map_or(f64::NEG_INFINITY, |c| row[c]);
+
+    let mut score = vec![f64::NEG_INFINITY; extended.len()];
+    score[0] = emit(&log_probability[0], 0);
+
+    if extended.len() > 1 {
+        score[1] = emit(&log_probability[0], 1);
+    }
+
+    // 每个时间步每个状态的上一个状态
+    let mut back = Vec::with_capacity(log_probability.len() - 1);
+
+    for row in &log_probability[1..] {
+        let mut next = vec![f64::NEG_INFINITY; extended.len()];
+        let mut from = vec![0; extended.len()];
+
+        for s in 0..extended.len() {
+            // 可以停留在当前状态，或者来自前一个状态，如果和前一个字符不同，还可以跳过中间的空白
+            let mut best = (score[s], s);
+
+            if s >= 1 && score[s - 1] > best.0 {
+                best = (score[s - 1], s - 1);
+            }
+
+            if s >= 2 && s % 2 == 1 && extended[s] != extended[s - 2] && score[s - 2] > best.0 {
+                best = (score[s - 2], s - 2);
+            }
+
+            next[s] = best.0 + emit(row, s);
+            from[s] = best.1;
+        }
+
+        score = next;
+        back.push(from);
+    }
+
+    let last = extended.len() - 1;
+    let mut s = if last >= 1 && score[last - 1] > score[last] {
+        last - 1
+    } else {
+        last
+    };
+
+    if score[s] == f64::NEG_INFINITY {
+        return None;
+    }
+
+    let mut path = vec![0; probability.len()];
+
+    for t in (0..probability.len()).rev() {
+        path[t] = extended[s]?;
+
+        if t > 0 {
+            s = back[t - 1][s];
+        }
+    }
+
+    Some(path)
+}
+
+/// 合并路径中连续的相同字符并去掉空白，与 `greedy_segments` 相同。
+fn path_segments(path: &[usize], blank: Option<usize>) -> Vec<Segment> {
+    let mut result = Vec::<Segment>::new();
+    let mut last_item = None;
+
+    for (t, &c) in path.iter().enumerate() {
+        if Some(c) == blank {
+            last_item = None;
+            continue;
+        }
+
+        match result.last_mut() {
+            Some(v) if last_item == Some(c) => v.end = t + 1,
+            _ => result.push(Segment {
+                index: c,
+                start: t,
+                end: t + 1,
+            }),
+        }
+
+        last_item = Some(c);
+    }
+
+    result
+}
+
+/// 贪心解码的每个字符的可信度，与 `ctc_align` 的 confidence 相同，参数的含义同 `ctc_beam_search`。
+pub fn ctc_char_confidence(probability: &[Vec<f32>], charset: &[String]) -> Vec<f64> {
+    let normalized = normalize(probability);
+
+    greedy_segments(probability, blank_index(charset))
+        .iter()
+        .map(|v| v.confidence(&normalized))
+        .collect()
+}
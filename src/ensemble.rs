@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use crate::{
+    CharacterProbability, Classification, ClassificationOptions, Error, ImageInput, OcrModel,
+    Recognition, Result,
+};
+
+/// 组合多个模型结果的方法。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EnsembleStrategy {
+    /// 多数投票，票数相同的时候选择可信度最高的。
+    #[default]
+    Vote,
+
+    /// 选择可信度最高的结果，模型有可信度校准的时候效果更好。
+    Confidence,
+
+    /// 把每个时间步相同字符的概率取平均之后再解码，可信度不使用校准，
+    /// 要求所有模型的时间步数量相同，否则退回到 Confidence。
+    Fusion,
+}
+
+impl std::str::FromStr for EnsembleStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "vote" => Self::Vote,
+            "confidence" => Self::Confidence,
+            "fusion" => Self::Fusion,
+            _ => return Err(Error::InvalidOption(format!("ensemble strategy: {}", s))),
+        })
+    }
+}
+
+/// 多个模型或者多个图片变体组合识别的结果。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EnsembleResult {
+    /// 组合后的识别结果。
+    pub text: String,
+
+    /// 组合后的可信度。
+    pub confidence: f64,
+
+    /// 组合后每个字符的可信度。
+    pub char_confidence: Vec<f64>,
+
+    /// 实际使用的方法，Fusion 无法使用的时候是 Confidence。
+    pub strategy: EnsembleStrategy,
+
+    /// 每个模型或者变体各自的结果，顺序与添加的顺序相同。
+    pub members: Vec<Recognition>,
+}
+
+impl EnsembleResult {
+    /// 根据可信度阈值判断结果是否可信，阈值的含义见 `Recognition::new`。
+    pub fn check_confidence(
+        &self,
+        min_confidence: Option<f64>,
+        min_char_confidence: Option<f64>,
+    ) -> Recognition {
+        Recognition::new(
+            self.text.clone(),
+            self.confidence,
+            self.char_confidence.clone(),
+            min_confidence,
+            min_char_confidence,
+        )
+    }
+}
+
+/// 组合多个内容识别模型，例如 `ddddocr_classification` 和 `ddddocr_classification_old`，仅限于使用官方模型。
+pub struct Ensemble<'a> {
+    models: Vec<&'a OcrModel<'a>>,
+}
+
+impl<'a> Ensemble<'a> {
+    pub fn new<M>(models: M) -> Self
+    where
+        M: IntoIterator<Item = &'a OcrModel<'a>>,
+    {
+        Self {
+            models: models.into_iter().collect(),
+        }
+    }
+
+    /// 添加一个模型。
+    pub fn push(&mut self, model: &'a OcrModel<'a>) {
+        self.models.push(model);
+    }
+
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// 使用所有模型识别，然后按照 strategy 组合结果，每个模型使用各自 `set_ranges` 的字符范围。
+    pub fn classification<I>(&self, image: I, strategy: EnsembleStrategy) -> Result<EnsembleResult>
+    where
+        I: ImageInput,
+    {
+        Ok(self
+            .combine_with_options(image, &ClassificationOptions::default(), strategy)?
+            .0)
+    }
+
+    /// 使用所有模型识别，然后按照 strategy 组合结果，结果的 ensemble 总是存在，
+    /// 选项的含义同 `OcrModel::classification_with_options`，图片只解码和预处理一次，不能设置 augmentations。
+    ///
+    /// ranges 在每个模型的字符集上分别计算，例如 `CharsetRange::All` 是各个模型自己的全部字符，
+    /// 为 None 则每个模型使用各自 `set_ranges` 的字符范围。
+    pub fn classification_with_options<I>(
+        &self,
+        image: I,
+        options: &ClassificationOptions,
+        strategy: EnsembleStrategy,
+    ) -> Result<Classification>
+    where
+        I: ImageInput,
+    {
+        let (result, png_fix) = self.combine_with_options(image, options, strategy)?;
+
+        Ok(Classification {
+            text: result.text.clone(),
+            png_fix,
+            recognition: options.checks_confidence().then(|| {
+                result.check_confidence(options.min_confidence, options.min_char_confidence)
+            }),
+            ensemble: Some(result),
+        })
+    }
+
+    /// 预处理之后使用所有模型识别并组合结果，同时返回是否使用了 png_fix。
+    fn combine_with_options<I>(
+        &self,
+        image: I,
+        options: &ClassificationOptions,
+        strategy: EnsembleStrategy,
+    ) -> Result<(EnsembleResult, bool)>
+    where
+        I: ImageInput,
+    {
+        if self.models.is_empty() {
+            return Err(Error::InvalidOption(
+                "the ensemble has no models".to_string(),
+            ));
+        }
+
+        if !options.augmentations.is_empty() {
+            return Err(Error::InvalidOption(
+                "the ensemble cannot be used with augmentations".to_string(),
+            ));
+        }
+
+        let (image, png_fix) = options.preprocess(image)?;
+
+        let results = self
+            .models
+            .iter()
+            .map(|v| {
+                v.classification_probability_with_options(
+                    &image,
+                    false,
+                    None,
+                    options.ranges.clone(),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((combine(results, strategy)?, png_fix))
+    }
+}
+
+/// 按照 strategy 组合多个识别结果，results 不能为空。
+pub(crate) fn combine(
+    mut results: Vec<CharacterProbability>,
+    strategy: EnsembleStrategy,
+) -> Result<EnsembleResult> {
+    if results.is_empty() {
+        return Err(Error::InvalidOption("nothing to combine".to_string()));
+    }
+
+    let members = results
+        .iter_mut()
+        .map(|v| v.check_confidence(None, None))
+        .collect::<Vec<_>>();
+
+    // 可信度最高的结果
+    let best = |members: &[&Recognition]| -> Recognition {
+        members
+            .iter()
+            .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+            .map(|v| (*v).clone())
+            .unwrap()
+    };
+
+    let (winner, strategy) = match strategy {
+        EnsembleStrategy::Vote => {
+            let mut votes = HashMap::<&str, Vec<&Recognition>>::new();
+
+            for v in &members {
+                votes.entry(v.text.as_str()).or_default().push(v);
+            }
+
+            // 票数最多，票数相同的时候可信度最高
+            let winner = votes
+                .into_values()
+                .map(|v| (v.len(), best(&v)))
+                .max_by(|a, b| {
+                    a.0.cmp(&b.0)
+                        .then(a.1.confidence.total_cmp(&b.1.confidence))
+                })
+                .map(|(_, v)| v)
+                .unwrap();
+
+            (winner, EnsembleStrategy::Vote)
+        }
+        EnsembleStrategy::Fusion => match fuse(&results) {
+            Some(mut v) => (v.check_confidence(None, None), EnsembleStrategy::Fusion),
+            None => (
+                best(&members.iter().collect::<Vec<_>>()),
+                EnsembleStrategy::Confidence,
+            ),
+        },
+        EnsembleStrategy::Confidence => (
+            best(&members.iter().collect::<Vec<_>>()),
+            EnsembleStrategy::Confidence,
+        ),
+    };
+
+    Ok(EnsembleResult {
+        text: winner.text,
+        confidence: winner.confidence,
+        char_confidence: winner.char_confidence,
+        strategy,
+        members,
+    })
+}
+
+/// 按字符对齐所有模型的概率，只保留所有模型都有的字符，每个时间步取平均值，
+/// 时间步数量不同或者没有共同的字符时返回 None。
+pub(crate) fn fuse(results: &[CharacterProbability]) -> Option<CharacterProbability> {
+    let (first, rest) = results.split_first()?;
+
+    if rest
+        .iter()
+        .any(|v| v.probability.len() != first.probability.len())
+    {
+        return None;
+    }
+
+    let indexes = rest
+        .iter()
+        .map(|v| {
+            v.charset
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (c.as_str(), i))
+                .collect::<HashMap<_, _>>()
+        })
+        .collect::<Vec<_>>();
+
+    // 每个共同的字符在第一个模型和其他模型中的下标
+    let columns = first
+        .charset
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| {
+            indexes
+                .iter()
+                .map(|v| v.get(c.as_str()).copied())
+                .collect::<Option<Vec<_>>>()
+                .map(|v| (i, v))
+        })
+        .collect::<Vec<_>>();
+
+    if !columns.iter().any(|(i, _)| !first.charset[*i].is_empty()) {
+        return None;
+    }
+
+    let count = results.len() as f32;
+    let probability = (0..first.probability.len())
+        .map(|t| {
+            columns
+                .iter()
+                .map(|(i, others)| {
+                    let sum = first.probability[t][*i]
+                        + rest
+                            .iter()
+                            .zip(others)
+                            .map(|(v, &j)| v.probability[t][j])
+                            .sum::<f32>();
+
+                    sum / count
+                })
+                .collect()
+        })
+        .collect();
+
+    Some(CharacterProbability {
+        text: None,
+        charset: columns
+            .iter()
+            .map(|(i, _)| first.charset[*i].clone())
+            .collect(),
+        probability,
+        log_probability: None,
+        confidence: None,
+        unknown: Vec::new(),
+        calibration: None,
+    })
+}
@@ -0,0 +1,115 @@
+/// 模型种类。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModelKind {
+    /// 内容识别模型。
+    Ocr,
+
+    /// 目标检测模型。
+    Detection,
+}
+
+impl std::fmt::Display for ModelKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelKind::Ocr => write!(f, "ocr"),
+            ModelKind::Detection => write!(f, "detection"),
+        }
+    }
+}
+
+/// 库的错误类型，可以直接匹配错误原因，而不用解析错误信息。
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// 图片解码失败。
+    #[error("failed to decode the image: {0}")]
+    ImageDecode(#[from] image::ImageError),
+
+    /// 图片尺寸太小，例如背景图片比目标图片还小。
+    #[error(
+        "the image is too small: {width}x{height}, expected at least {min_width}x{min_height}"
+    )]
+    ImageTooSmall {
+        width: u32,
+        height: u32,
+        min_width: u32,
+        min_height: u32,
+    },
+
+    /// 原始像素的长度和宽度、高度、像素排列方式不匹配。
+    #[error("the pixel buffer has {actual} bytes, expected {expected}")]
+    InvalidPixelBuffer { expected: usize, actual: usize },
+
+    /// 两张图片的尺寸不相等。
+    #[error("the image sizes are not equal: {0:?} and {1:?}")]
+    ImageSizeMismatch((u32, u32), (u32, u32)),
+
+    /// 模型种类不对，例如使用目标检测模型进行内容识别。
+    #[error("only the {expected} model can be used")]
+    WrongModelKind { expected: ModelKind },
+
+    /// 该功能仅限于使用官方模型。
+    #[error("can only use the official model")]
+    UnsupportedCustomModel,
+
+    /// 无效的内置字符集编号，有效范围是 0~7。
+    #[error("invalid charset range: {0}")]
+    InvalidCharsetRange(i32),
+
+    /// 无效的字符范围表达式，例如未知的名称或者括号不匹配。
+    #[error("invalid charset expression: {0}")]
+    InvalidCharsetExpression(String),
+
+    /// 未知的颜色名称。
+    #[error("unknown color: {0}")]
+    UnknownColor(String),
+
+    /// 无效的格式，例如正则表达式的语法错误。
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+
+    /// 无效的语言模型，例如 ARPA 文件的格式错误。
+    #[error("invalid language model: {0}")]
+    InvalidLanguageModel(String),
+
+    /// 无效的可信度校准，例如没有样本。
+    #[error("invalid calibration: {0}")]
+    InvalidCalibration(String),
+
+    /// 无效的选项，例如命令行参数解析失败。
+    #[error("invalid option: {0}")]
+    InvalidOption(String),
+
+    /// 推理失败。
+    #[error("inference failed: {0}")]
+    Inference(#[from] ort::Error),
+
+    /// 执行提供者不可用，例如 ONNX Runtime 没有编译 CUDA 支持。
+    #[error("please compile ONNX Runtime with {0}!")]
+    ExecutionProviderUnavailable(String),
+
+    /// 张量形状错误。
+    #[error("invalid tensor shape: {0}")]
+    Shape(#[from] ndarray::ShapeError),
+
+    /// 加载运行库失败。
+    #[error("{0}")]
+    RuntimeLibrary(String),
+
+    /// 读写文件失败。
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// 解析 json 失败，例如字符集配置。
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// 用于 `TryInto` 约束，不可能失败的转换也能统一成 `Error`。
+impl From<std::convert::Infallible> for Error {
+    fn from(value: std::convert::Infallible) -> Self {
+        match value {}
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
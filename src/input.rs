@@ -0,0 +1,167 @@
+use std::borrow::Cow;
+
+use crate::{Error, Result};
+
+/// 原始像素的排列方式，每个通道占一个字节，像素之间紧密排列，没有行填充。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PixelLayout {
+    /// 灰度。
+    Luma8,
+
+    /// 灰度 + 透明度。
+    LumaA8,
+
+    /// 红绿蓝。
+    Rgb8,
+
+    /// 红绿蓝 + 透明度，例如浏览器的截图。
+    Rgba8,
+
+    /// 蓝绿红，例如 opencv 的图片。
+    Bgr8,
+
+    /// 蓝绿红 + 透明度，例如 windows 的截图。
+    Bgra8,
+}
+
+impl PixelLayout {
+    /// 每个像素占用的字节数。
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelLayout::Luma8 => 1,
+            PixelLayout::LumaA8 => 2,
+            PixelLayout::Rgb8 | PixelLayout::Bgr8 => 3,
+            PixelLayout::Rgba8 | PixelLayout::Bgra8 => 4,
+        }
+    }
+}
+
+/// 可以作为图片输入的类型，所有识别、检测和滑块的接口都接受它。
+///
+/// 已经实现的类型：
+/// - 编码后的图片，例如 png、jpg 文件的内容：`[u8]`、`Vec<u8>`、`[u8; N]`
+/// - 图片文件的路径：`Path`、`PathBuf`
+/// - 已经解码的图片：`DynamicImage`、`RgbImage`、`RgbaImage`、`GrayImage`、`GrayAlphaImage`
+/// - 原始像素：`(width, height, PixelLayout, &[u8])`
+/// - 以上类型的引用
+pub trait ImageInput {
+    /// 转换到 `DynamicImage`，已经解码的图片不会重复解码。
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>>;
+}
+
+impl<T> ImageInput for &T
+where
+    T: ImageInput + ?Sized,
+{
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        (**self).to_image()
+    }
+}
+
+impl ImageInput for [u8] {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        Ok(Cow::Owned(image::load_from_memory(self)?))
+    }
+}
+
+impl ImageInput for Vec<u8> {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        self.as_slice().to_image()
+    }
+}
+
+impl<const N: usize> ImageInput for [u8; N] {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        self.as_slice().to_image()
+    }
+}
+
+impl ImageInput for std::path::Path {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        Ok(Cow::Owned(image::load_from_memory(&std::fs::read(self)?)?))
+    }
+}
+
+impl ImageInput for std::path::PathBuf {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        self.as_path().to_image()
+    }
+}
+
+impl ImageInput for image::DynamicImage {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl ImageInput for image::RgbImage {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        Ok(Cow::Owned(image::DynamicImage::ImageRgb8(self.clone())))
+    }
+}
+
+impl ImageInput for image::RgbaImage {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        Ok(Cow::Owned(image::DynamicImage::ImageRgba8(self.clone())))
+    }
+}
+
+impl ImageInput for image::GrayImage {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        Ok(Cow::Owned(image::DynamicImage::ImageLuma8(self.clone())))
+    }
+}
+
+impl ImageInput for image::GrayAlphaImage {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        Ok(Cow::Owned(image::DynamicImage::ImageLumaA8(self.clone())))
+    }
+}
+
+/// 原始像素，依次为宽度、高度、像素排列方式和像素数据。
+impl ImageInput for (u32, u32, PixelLayout, &[u8]) {
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        let (width, height, layout, data) = *self;
+        let expected = width as usize * height as usize * layout.bytes_per_pixel();
+
+        if data.len() != expected {
+            return Err(Error::InvalidPixelBuffer {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        // 蓝绿红转换到红绿蓝
+        let swap = |channel: usize| {
+            data.chunks_exact(channel)
+                .flat_map(|v| {
+                    let mut pixel = v.to_vec();
+                    pixel.swap(0, 2);
+                    pixel
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        // 长度已经检查过了，这里不会失败
+        let image = match layout {
+            PixelLayout::Luma8 => image::GrayImage::from_raw(width, height, data.to_vec())
+                .map(image::DynamicImage::ImageLuma8),
+            PixelLayout::LumaA8 => image::GrayAlphaImage::from_raw(width, height, data.to_vec())
+                .map(image::DynamicImage::ImageLumaA8),
+            PixelLayout::Rgb8 => image::RgbImage::from_raw(width, height, data.to_vec())
+                .map(image::DynamicImage::ImageRgb8),
+            PixelLayout::Rgba8 => image::RgbaImage::from_raw(width, height, data.to_vec())
+                .map(image::DynamicImage::ImageRgba8),
+            PixelLayout::Bgr8 => image::RgbImage::from_raw(width, height, swap(3))
+                .map(image::DynamicImage::ImageRgb8),
+            PixelLayout::Bgra8 => image::RgbaImage::from_raw(width, height, swap(4))
+                .map(image::DynamicImage::ImageRgba8),
+        };
+
+        image.map(Cow::Owned).ok_or(Error::InvalidPixelBuffer {
+            expected,
+            actual: data.len(),
+        })
+    }
+}
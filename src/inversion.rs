@@ -0,0 +1,121 @@
+use std::borrow::Cow;
+
+use crate::{pipeline, Error, PreprocessStage, Result};
+
+/// 中间的平均亮度至少比背景亮这么多才认为是浅色文字，避免亮度接近的图片被误判。
+const MIN_LUMINANCE_DIFFERENCE: f64 = 16.0;
+
+/// 官方模型只认识浅色背景上的深色文字，这个设置控制是否把浅色文字的图片反转颜色，并拉伸对比度。
+///
+/// 注意 `Auto` 和 `On` 总是会拉伸对比度，`Auto` 判断不需要反转的时候也会，
+/// 如果只想反转颜色，可以使用 `BuiltinStage::Invert`，只想拉伸对比度可以使用 `BuiltinStage::Contrast`。
+///
+/// 只处理颜色通道，透明度保持不变，json 格式为 `"off"`、`"auto"`、`"on"`。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Inversion {
+    /// 不处理。
+    #[default]
+    Off,
+
+    /// 根据边缘与中间的亮度判断是否是浅色文字，需要的时候反转颜色，然后总是拉伸对比度，见 `is_light_text`。
+    Auto,
+
+    /// 总是反转颜色并拉伸对比度，适用于已知是浅色文字的图片。
+    On,
+}
+
+impl std::str::FromStr for Inversion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "off" => Self::Off,
+            "auto" => Self::Auto,
+            "on" => Self::On,
+            _ => return Err(Error::InvalidOption(format!("inversion: {}", s))),
+        })
+    }
+}
+
+impl Inversion {
+    /// 是否需要反转颜色。
+    pub fn should_invert(self, image: &image::DynamicImage) -> bool {
+        match self {
+            Inversion::Off => false,
+            Inversion::Auto => is_light_text(image),
+            Inversion::On => true,
+        }
+    }
+
+    /// 按照设置处理图片，`Off` 时返回原图。
+    pub fn apply(self, image: &image::DynamicImage) -> Cow<'_, image::DynamicImage> {
+        if self == Inversion::Off {
+            return Cow::Borrowed(image);
+        }
+
+        let image = if self.should_invert(image) {
+            pipeline::contrast_stretch(&pipeline::invert(image.clone()))
+        } else {
+            pipeline::contrast_stretch(image)
+        };
+
+        Cow::Owned(image)
+    }
+}
+
+impl PreprocessStage for Inversion {
+    fn apply(&self, image: image::DynamicImage) -> Result<image::DynamicImage> {
+        Ok(match Inversion::apply(*self, &image) {
+            Cow::Borrowed(_) => image,
+            Cow::Owned(v) => v,
+        })
+    }
+}
+
+/// 判断是否是深色背景上的浅色文字。
+///
+/// 边缘（四周各 1/8 的宽度或高度）的亮度中位数当作背景的亮度，
+/// 中间部分包含文字，如果中间的平均亮度比背景亮至少 16（0 到 255），则认为文字比背景亮。
+pub fn is_light_text(image: &image::DynamicImage) -> bool {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    let band = (width.min(height) / 8).max(1);
+
+    if width <= band * 2 || height <= band * 2 {
+        return false;
+    }
+
+    let mut border = [0u32; 256];
+    let mut center = [0u32; 256];
+
+    for (x, y, pixel) in luma.enumerate_pixels() {
+        let is_border = x < band || y < band || x >= width - band || y >= height - band;
+
+        if is_border {
+            border[pixel[0] as usize] += 1;
+        } else {
+            center[pixel[0] as usize] += 1;
+        }
+    }
+
+    let border_count = border.iter().sum::<u32>();
+    let mut seen = 0;
+    let border_median = border
+        .iter()
+        .position(|&v| {
+            seen += v;
+            seen * 2 >= border_count
+        })
+        .unwrap_or(0) as f64;
+
+    let center_count = center.iter().sum::<u32>() as f64;
+    let center_mean = center
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| i as f64 * v as f64)
+        .sum::<f64>()
+        / center_count;
+
+    center_mean - border_median >= MIN_LUMINANCE_DIFFERENCE
+}
@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::LN_10;
+
+use crate::{Error, PrefixScorer, Result};
+
+/// 句子的开始和结束，以及未知字符，与 ARPA 格式的约定相同。
+const SENTENCE_START: &str = "<s>";
+const SENTENCE_END: &str = "</s>";
+const UNKNOWN: &str = "<unk>";
+
+/// ARPA 格式中表示概率为 0 的对数概率。
+const ARPA_ZERO: f64 = -99.0;
+
+/// 训练时每个 n-gram 的计数减去的折扣，减掉的概率留给低阶的 n-gram。
+const DISCOUNT: f64 = 0.5;
+
+/// 一个 n-gram 的对数概率和回退权重，都是自然对数。
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    probability: f64,
+    backoff: f64,
+}
+
+/// 字符级的 n-gram 语言模型，用于 `ctc_language_model_search`，让通顺的结果胜过字形相似的无意义结果。
+///
+/// 每个字符都是一个词，可以从 ARPA 格式的文件加载，例如 kenlm 训练的模型（训练前把语料的每个字符用空格隔开），
+/// 也可以用 `NgramModel::train` 从语料直接训练。
+#[derive(Debug, Clone, Default)]
+pub struct NgramModel {
+    order: usize,
+    entries: HashMap<Vec<String>, Entry>,
+    unknown: f64,
+}
+
+impl std::str::FromStr for NgramModel {
+    type Err = Error;
+
+    /// 解析 ARPA 格式的语言模型。
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |line: &str| Error::InvalidLanguageModel(format!("invalid line: {}", line));
+
+        let mut model = Self::default();
+        let mut section = None;
+
+        for line in s.lines().map(str::trim).filter(|v| !v.is_empty()) {
+            if line == "\\data\\" || line == "\\end\\" {
+                section = None;
+            } else if let Some(n) = line
+                .strip_prefix('\\')
+                .and_then(|v| v.strip_suffix("-grams:"))
+            {
+                let n = n.parse::<usize>().map_err(|_| invalid(line))?;
+
+                if n == 0 {
+                    return Err(invalid(line));
+                }
+
+                model.order = model.order.max(n);
+                section = Some(n);
+            } else if let Some(n) = section {
+                let fields = line.split_whitespace().collect::<Vec<_>>();
+
+                if fields.len() != n + 1 && fields.len() != n + 2 {
+                    return Err(invalid(line));
+                }
+
+                let parse = |v: &str| v.parse::<f64>().map(|v| v * LN_10);
+                let probability = parse(fields[0]).map_err(|_| invalid(line))?;
+                let backoff = match fields.get(n + 1) {
+                    Some(v) => parse(v).map_err(|_| invalid(line))?,
+                    None => 0.0,
+                };
+
+                model.entries.insert(
+                    fields[1..=n].iter().map(|v| v.to_string()).collect(),
+                    Entry {
+                        probability,
+                        backoff,
+                    },
+                );
+            } else if !line.starts_with("ngram ") {
+                return Err(invalid(line));
+            }
+        }
+
+        if model.order == 0 {
+            return Err(Error::InvalidLanguageModel("no n-grams found".to_string()));
+        }
+
+        model.unknown = model.unknown_probability();
+
+        Ok(model)
+    }
+}
+
+impl NgramModel {
+    /// 从 ARPA 格式的文件加载语言模型。
+    pub fn with_path<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// 从语料训练 order 阶的语言模型，每个元素是一句话，例如一个成语，空白字符会被忽略。
+    ///
+    /// 使用绝对折扣和回退平滑，没有在语料中出现过的字符按 `<unk>` 计算。
+    pub fn train<C, S>(corpus: C, order: usize) -> Self
+    where
+        C: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let order = order.max(1);
+        let mut counts = vec![HashMap::<Vec<String>, usize>::new(); order];
+
+        for line in corpus {
+            let tokens = std::iter::once(SENTENCE_START.to_string())
+                .chain(
+                    line.as_ref()
+                        .chars()
+                        .filter(|c| !c.is_whitespace())
+                        .map(String::from),
+                )
+                .chain(std::iter::once(SENTENCE_END.to_string()))
+                .collect::<Vec<_>>();
+
+            // 句子开始只作为上文，不会被预测
+            for end in 2..=tokens.len() {
+                for n in 1..=order.min(end) {
+                    *counts[n - 1]
+                        .entry(tokens[end - n..end].to_vec())
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        let mut model = Self {
+            order,
+            ..Default::default()
+        };
+
+        // 一元模型使用加一平滑，剩下的概率留给未知字符
+        let total = counts[0].values().sum::<usize>() as f64;
+        let denominator = total + counts[0].len() as f64 + 1.0;
+
+        for (k, &v) in &counts[0] {
+            model.insert(k.clone(), ((v as f64 + 1.0) / denominator).ln());
+        }
+
+        model.insert(vec![UNKNOWN.to_string()], (1.0 / denominator).ln());
+        model.insert(vec![SENTENCE_START.to_string()], ARPA_ZERO * LN_10);
+
+        for n in 2..=order {
+            let mut followers = HashMap::<&[String], Vec<(&str, usize)>>::new();
+
+            for (k, &v) in &counts[n - 1] {
+                followers
+                    .entry(&k[..n - 1])
+                    .or_default()
+                    .push((&k[n - 1], v));
+            }
+
+            let mut probabilities = Vec::new();
+
+            for (context, words) in followers {
+                let total = words.iter().map(|(_, v)| v).sum::<usize>() as f64;
+                let mut seen = 0.0;
+                let mut lower = 0.0;
+
+                for &(word, count) in &words {
+                    let p = (count as f64 - DISCOUNT) / total;
+                    seen += p;
+                    lower += model.log_probability(&context[1..], word).exp();
+
+                    let mut key = context.to_vec();
+                    key.push(word.to_string());
+                    probabilities.push((key, p.ln()));
+                }
+
+                // 回退权重让每个上文之后的概率之和为 1
+                if lower < 1.0 {
+                    if let Some(v) = model.entries.get_mut(context) {
+                        v.backoff = ((1.0 - seen) / (1.0 - lower)).ln();
+                    }
+                }
+            }
+
+            for (k, v) in probabilities {
+                model.insert(k, v);
+            }
+        }
+
+        model.unknown = model.unknown_probability();
+        model
+    }
+
+    fn insert(&mut self, key: Vec<String>, probability: f64) {
+        self.entries.insert(
+            key,
+            Entry {
+                probability,
+                backoff: 0.0,
+            },
+        );
+    }
+
+    /// 未知字符的对数概率，没有 `<unk>` 的时候使用最小的一元概率。
+    fn unknown_probability(&self) -> f64 {
+        match self.entries.get([UNKNOWN.to_string()].as_slice()) {
+            Some(v) => v.probability,
+            None => self
+                .entries
+                .iter()
+                .filter(|(k, v)| k.len() == 1 && v.probability > ARPA_ZERO * LN_10)
+                .map(|(_, v)| v.probability)
+                .min_by(|a, b| a.total_cmp(b))
+                .unwrap_or(ARPA_ZERO * LN_10),
+        }
+    }
+
+    /// 模型的阶数，即最长的 n-gram 的长度。
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// 在 history 之后出现 word 的对数概率（自然对数），只使用 history 中最后的 order - 1 个字符。
+    /// 句子的开始和结束分别是 `<s>` 和 `</s>`。
+    pub fn log_probability<S>(&self, history: &[S], word: &str) -> f64
+    where
+        S: AsRef<str>,
+    {
+        let history = &history[history.len().saturating_sub(self.order.saturating_sub(1))..];
+        let mut backoff = 0.0;
+
+        for i in 0..=history.len() {
+            let mut key = history[i..]
+                .iter()
+                .map(|v| v.as_ref().to_string())
+                .collect::<Vec<_>>();
+            key.push(word.to_string());
+
+            if let Some(v) = self.entries.get(&key) {
+                return backoff + v.probability;
+            }
+
+            key.pop();
+
+            if let Some(v) = self.entries.get(&key) {
+                backoff += v.backoff;
+            }
+        }
+
+        backoff + self.unknown
+    }
+
+    /// 整句话的对数概率（自然对数），包括句子的结束。
+    pub fn score(&self, text: &str) -> f64 {
+        let mut history = vec![SENTENCE_START.to_string()];
+        let mut score = 0.0;
+
+        for c in text.chars().map(String::from) {
+            score += self.log_probability(&history, &c);
+            history.push(c);
+        }
+
+        score + self.log_probability(&history, SENTENCE_END)
+    }
+
+    /// 转换到 ARPA 格式，可以保存训练的模型，下次用 `NgramModel::with_path` 加载。
+    pub fn to_arpa(&self) -> String {
+        let mut groups = vec![BTreeMap::new(); self.order];
+
+        for (k, v) in &self.entries {
+            groups[k.len() - 1].insert(k.join(" "), *v);
+        }
+
+        let mut arpa = String::from("\n\\data\\\n");
+
+        for (i, group) in groups.iter().enumerate() {
+            arpa += &format!("ngram {}={}\n", i + 1, group.len());
+        }
+
+        for (i, group) in groups.iter().enumerate() {
+            arpa += &format!("\n\\{}-grams:\n", i + 1);
+
+            for (k, v) in group {
+                let probability = (v.probability / LN_10).max(ARPA_ZERO);
+
+                if i + 1 < self.order {
+                    arpa += &format!("{:.6}\t{}\t{:.6}\n", probability, k, v.backoff / LN_10);
+                } else {
+                    arpa += &format!("{:.6}\t{}\n", probability, k);
+                }
+            }
+        }
+
+        arpa + "\n\\end\\\n"
+    }
+}
+
+/// 语言模型作为前缀束搜索的约束，状态是最后的 order - 1 个字符。
+pub(crate) struct LanguageModelScorer<'a> {
+    pub(crate) model: &'a NgramModel,
+    pub(crate) weight: f64,
+}
+
+impl LanguageModelScorer<'_> {
+    fn factor(&self, history: &[String], word: &str) -> f64 {
+        (self.weight * self.model.log_probability(history, word)).exp()
+    }
+}
+
+impl PrefixScorer for LanguageModelScorer<'_> {
+    type State = Vec<String>;
+
+    fn start(&self) -> Self::State {
+        vec![SENTENCE_START.to_string()]
+    }
+
+    fn extend(&self, state: &Self::State, text: &str) -> Option<(Self::State, f64)> {
+        let factor = self.factor(state, text);
+        let mut state = state.clone();
+        state.push(text.to_string());
+
+        if state.len() >= self.model.order {
+            state.remove(0);
+        }
+
+        Some((state, factor))
+    }
+
+    fn finish(&self, state: &Self::State) -> Option<f64> {
+        Some(self.factor(state, SENTENCE_END))
+    }
+}
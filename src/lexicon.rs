@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use crate::Result;
+
+/// 词典，识别结果只能是其中的一个词语，例如四字成语或者固定的英文单词，用于 `ctc_lexicon_search`。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lexicon {
+    words: Vec<String>,
+}
+
+impl Lexicon {
+    /// 从词语列表创建词典，会去掉首尾的空白、空的词语和重复的词语。
+    pub fn new<W, S>(words: W) -> Self
+    where
+        W: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut seen = HashSet::new();
+
+        Self {
+            words: words
+                .into_iter()
+                .map(|v| v.as_ref().trim().to_string())
+                .filter(|v| !v.is_empty() && seen.insert(v.clone()))
+                .collect(),
+        }
+    }
+
+    /// 从文件读取词典，文件是 utf-8 编码的文本，每行一个词语。
+    pub fn with_path<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Ok(Self::new(std::fs::read_to_string(path)?.lines()))
+    }
+
+    /// 词典中的所有词语。
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl From<Vec<String>> for Lexicon {
+    fn from(value: Vec<String>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Vec<&str>> for Lexicon {
+    fn from(value: Vec<&str>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&[&str]> for Lexicon {
+    fn from(value: &[&str]) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&[String]> for Lexicon {
+    fn from(value: &[String]) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<S> FromIterator<S> for Lexicon
+where
+    S: AsRef<str>,
+{
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        Self::new(iter)
+    }
+}
+
+/// 词典解码的结果。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LexiconMatch {
+    /// 词典中概率最高的词语。
+    pub text: String,
+
+    /// 这个词语的 CTC 概率，即所有能折叠成 text 的路径的概率之和。
+    pub probability: f64,
+
+    /// 与第二名的对数概率之差，即 `ln(p1 / p2)`，越大越可靠，
+    /// 如果词典中只有一个词语有可能，则为 None。
+    pub margin: Option<f64>,
+}
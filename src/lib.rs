@@ -1,95 +1,93 @@
-/// 初始化内容识别。
-#[cfg(feature = "inline-model")]
-pub fn ddddocr_classification() -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new(
-        include_bytes!("../model/common.onnx"),
-        serde_json::from_str(include_str!("../model/common.json")).unwrap(),
-    )
-}
-
-/// 初始化内容识别。
-#[cfg(not(feature = "inline-model"))]
-pub fn ddddocr_classification() -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new(
-        std::fs::read("model/common.onnx")?,
-        serde_json::from_str(&std::fs::read_to_string("model/common.json")?)?,
-    )
+mod augmentation;
+mod builder;
+mod calibration;
+mod case_folding;
+mod charset_range;
+mod confidence;
+mod ctc;
+mod ensemble;
+mod error;
+mod input;
+mod inversion;
+mod language_model;
+mod lexicon;
+mod noise;
+mod normalizer;
+mod options;
+mod pattern;
+mod pipeline;
+mod preprocess;
+
+pub use augmentation::*;
+pub use builder::*;
+pub use calibration::*;
+pub use case_folding::*;
+pub use charset_range::*;
+pub use confidence::*;
+pub use ctc::*;
+pub use ensemble::*;
+pub use error::*;
+pub use input::*;
+pub use inversion::*;
+pub use language_model::*;
+pub use lexicon::*;
+pub use noise::*;
+pub use normalizer::*;
+pub use options::*;
+pub use pattern::*;
+pub use pipeline::*;
+pub use preprocess::*;
+
+/// 初始化内容识别，开启 inline-model 时不会加载可信度校准，见 `OcrModel::set_calibration`。
+pub fn ddddocr_classification() -> Result<OcrModel<'static>> {
+    DdddocrBuilder::new().ddddocr_classification()
 }
 
 /// 初始化内容识别。
 #[cfg(feature = "cuda")]
-pub fn ddddocr_classification_cuda(device_id: i32) -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new_cuda(
-        include_bytes!("../model/common.onnx"),
-        serde_json::from_str(include_str!("../model/common.json")).unwrap(),
-        device_id,
-    )
+pub fn ddddocr_classification_cuda(device_id: i32) -> Result<OcrModel<'static>> {
+    DdddocrBuilder::new()
+        .execution_provider(ExecutionProvider::cuda(device_id))
+        .ddddocr_classification()
 }
 
-/// 使用旧模型初始化内容识别。
-#[cfg(feature = "inline-model")]
-pub fn ddddocr_classification_old() -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new(
-        include_bytes!("../model/common_old.onnx"),
-        serde_json::from_str(include_str!("../model/common_old.json")).unwrap(),
-    )
-}
-
-/// 使用旧模型初始化内容识别。
-#[cfg(not(feature = "inline-model"))]
-pub fn ddddocr_classification_old() -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new(
-        std::fs::read("model/common_old.onnx")?,
-        serde_json::from_str(&std::fs::read_to_string("model/common_old.json")?)?,
-    )
+/// 使用旧模型初始化内容识别，开启 inline-model 时不会加载可信度校准，见 `OcrModel::set_calibration`。
+pub fn ddddocr_classification_old() -> Result<OcrModel<'static>> {
+    DdddocrBuilder::new().ddddocr_classification_old()
 }
 
 /// 使用旧模型初始化内容识别。
 #[cfg(feature = "cuda")]
-pub fn ddddocr_classification_old_cuda(device_id: i32) -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new_cuda(
-        include_bytes!("../model/common_old.onnx"),
-        serde_json::from_str(include_str!("../model/common_old.json")).unwrap(),
-        device_id,
-    )
+pub fn ddddocr_classification_old_cuda(device_id: i32) -> Result<OcrModel<'static>> {
+    DdddocrBuilder::new()
+        .execution_provider(ExecutionProvider::cuda(device_id))
+        .ddddocr_classification_old()
 }
 
 /// 初始化目标检测。
-#[cfg(feature = "inline-model")]
-pub fn ddddocr_detection() -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new_model(include_bytes!("../model/common_det.onnx"))
-}
-
-/// 初始化目标检测。
-#[cfg(not(feature = "inline-model"))]
-pub fn ddddocr_detection() -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new_model(std::fs::read("model/common_det.onnx")?)
+pub fn ddddocr_detection() -> Result<DetectionModel> {
+    DdddocrBuilder::new().ddddocr_detection()
 }
 
 /// 初始化目标检测。
 #[cfg(feature = "cuda")]
-pub fn ddddocr_detection_cuda(device_id: i32) -> anyhow::Result<Ddddocr<'static>> {
-    Ddddocr::new_model_cuda(include_bytes!("../model/common_det.onnx"), device_id)
+pub fn ddddocr_detection_cuda(device_id: i32) -> Result<DetectionModel> {
+    DdddocrBuilder::new()
+        .execution_provider(ExecutionProvider::cuda(device_id))
+        .ddddocr_detection()
 }
 
 /// 滑块匹配。
-pub fn slide_match<I1, I2>(target_image: I1, background_image: I2) -> anyhow::Result<SlideBBox>
+pub fn slide_match<I1, I2>(target_image: I1, background_image: I2) -> Result<SlideBBox>
 where
-    I1: AsRef<[u8]>,
-    I2: AsRef<[u8]>,
+    I1: ImageInput,
+    I2: ImageInput,
 {
-    let target_image = image::load_from_memory(target_image.as_ref())?;
-    let background_image = image::load_from_memory(background_image.as_ref())?;
-
-    anyhow::ensure!(
-        background_image.width() >= target_image.width(),
-        "背景图片的宽度必须大于等于目标图片的宽度"
-    );
+    let target_image = target_image.to_image()?;
+    let background_image = background_image.to_image()?;
 
-    anyhow::ensure!(
-        background_image.height() >= target_image.height(),
-        "背景图片的高度必须大于等于目标图片的高度"
-    );
+    // 背景图片的宽度和高度必须大于等于目标图片
+    ensure_not_smaller(&background_image, &target_image)?;
 
     let target_image = target_image.to_rgba8();
 
@@ -165,42 +163,17 @@ where
     })
 }
 
-/// 滑块匹配。
-pub fn slide_match_with_path<P1, P2>(
-    target_image: P1,
-    background_image: P2,
-) -> anyhow::Result<SlideBBox>
-where
-    P1: AsRef<std::path::Path>,
-    P2: AsRef<std::path::Path>,
-{
-    slide_match(
-        std::fs::read(target_image)?,
-        std::fs::read(background_image)?,
-    )
-}
-
 /// 如果小图无过多背景部分，可以使用简单滑块匹配。
-pub fn simple_slide_match<I1, I2>(
-    target_image: I1,
-    background_image: I2,
-) -> anyhow::Result<SlideBBox>
+pub fn simple_slide_match<I1, I2>(target_image: I1, background_image: I2) -> Result<SlideBBox>
 where
-    I1: AsRef<[u8]>,
-    I2: AsRef<[u8]>,
+    I1: ImageInput,
+    I2: ImageInput,
 {
-    let target_image = image::load_from_memory(target_image.as_ref())?;
-    let background_image = image::load_from_memory(background_image.as_ref())?;
-
-    anyhow::ensure!(
-        background_image.width() >= target_image.width(),
-        "背景图片的宽度必须大于等于目标图标的宽度"
-    );
+    let target_image = target_image.to_image()?;
+    let background_image = background_image.to_image()?;
 
-    anyhow::ensure!(
-        background_image.height() >= target_image.height(),
-        "背景图片的高度必须大于等于目标图标的高度"
-    );
+    // 背景图片的宽度和高度必须大于等于目标图标
+    ensure_not_smaller(&background_image, &target_image)?;
 
     // 使用 canny 进行边缘检测。然后对背景图片进行同样的处理
     // 接着，使用 match_template 函数进行模板匹配，得到匹配结果矩阵
@@ -225,50 +198,35 @@ where
     })
 }
 
-/// 如果小图无过多背景部分，可以使用简单滑块匹配。
-pub fn simple_slide_match_with_path<P1, P2>(
-    target_image: P1,
-    background_image: P2,
-) -> anyhow::Result<SlideBBox>
-where
-    P1: AsRef<std::path::Path>,
-    P2: AsRef<std::path::Path>,
-{
-    simple_slide_match(
-        std::fs::read(target_image)?,
-        std::fs::read(background_image)?,
-    )
-}
-
 /// 坑位匹配。
-pub fn slide_comparison<I1, I2>(
-    target_image: I1,
-    background_image: I2,
-) -> anyhow::Result<(u32, u32)>
+pub fn slide_comparison<I1, I2>(target_image: I1, background_image: I2) -> Result<(u32, u32)>
 where
-    I1: AsRef<[u8]>,
-    I2: AsRef<[u8]>,
+    I1: ImageInput,
+    I2: ImageInput,
 {
-    let target_image = image::load_from_memory(target_image.as_ref())?;
-    let background_image = image::load_from_memory(background_image.as_ref())?;
-
-    anyhow::ensure!(
-        target_image.width() == background_image.width()
-            && target_image.height() == background_image.height(),
-        "图片尺寸不相等"
-    );
-
-    let image = image::RgbImage::from_vec(
-        target_image.width(),
-        target_image.height(),
-        target_image
-            .as_bytes()
-            .iter()
-            .zip(background_image.as_bytes().iter())
-            .map(|(a, b)| if a.abs_diff(*b) > 80 { 255 } else { 0 })
-            .collect(),
-    )
-    .unwrap();
+    let target_image = target_image.to_image()?;
+    let background_image = background_image.to_image()?;
+
+    let target_size = (target_image.width(), target_image.height());
+    let background_size = (background_image.width(), background_image.height());
+
+    if target_size != background_size {
+        return Err(Error::ImageSizeMismatch(target_size, background_size));
+    }
+
+    // 统一转换到 rgb，否则两张图片的通道数可能不同
+    let target_image = target_image.to_rgb8();
+    let background_image = background_image.to_rgb8();
+
+    let image = image::RgbImage::from_fn(target_image.width(), target_image.height(), |x, y| {
+        image::Rgb(std::array::from_fn(|i| {
+            if target_image[(x, y)][i].abs_diff(background_image[(x, y)][i]) > 80 {
+                255
+            } else {
+                0
+            }
+        }))
+    });
 
     let mut start_x = 0;
     let mut start_y = 0;
@@ -284,7 +242,7 @@ where
             }
 
             if count >= 5 && start_y == 0 {
-                start_y = j - 5;
+                start_y = j.saturating_sub(5);
             }
         }
 
@@ -297,21 +255,6 @@ where
     Ok((start_x, start_y))
 }
 
-/// 坑位匹配。
-pub fn slide_comparison_with_path<P1, P2>(
-    target_image: P1,
-    background_image: P2,
-) -> anyhow::Result<(u32, u32)>
-where
-    P1: AsRef<std::path::Path>,
-    P2: AsRef<std::path::Path>,
-{
-    slide_comparison(
-        std::fs::read(target_image)?,
-        std::fs::read(background_image)?,
-    )
-}
-
 /// 判断是否为自定义模型。
 pub fn is_diy<MODEL>(model: MODEL) -> bool
 where
@@ -324,6 +267,39 @@ where
         && sha256 != "b8f2ad9cbc1f2e3922a6cb9459e30824e7e2467f3fb4fd61420640e34ea0bf68"
 }
 
+/// 背景图片的宽度和高度必须大于等于目标图片。
+fn ensure_not_smaller(
+    background_image: &image::DynamicImage,
+    target_image: &image::DynamicImage,
+) -> Result<()> {
+    if background_image.width() < target_image.width()
+        || background_image.height() < target_image.height()
+    {
+        return Err(Error::ImageTooSmall {
+            width: background_image.width(),
+            height: background_image.height(),
+            min_width: target_image.width(),
+            min_height: target_image.height(),
+        });
+    }
+
+    Ok(())
+}
+
+/// 图片至少要有一个像素，否则缩放的时候会除以零。
+fn ensure_not_empty(width: u32, height: u32) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Err(Error::ImageTooSmall {
+            width,
+            height,
+            min_width: 1,
+            min_height: 1,
+        });
+    }
+
+    Ok(())
+}
+
 /// 将图片的透明部分用白色填充。
 fn png_rgba_black_preprocess(image: &image::DynamicImage) -> image::DynamicImage {
     let (width, height) = image::GenericImageView::dimensions(image);
@@ -346,6 +322,38 @@ fn png_rgba_black_preprocess(image: &image::DynamicImage) -> image::DynamicImage
     new_image
 }
 
+/// 解码图片，如果指定了 filter，则只保留指定的颜色。
+fn load_image<'a, I>(
+    image: &'a I,
+    filter: Option<&ColorFilter>,
+) -> Result<std::borrow::Cow<'a, image::DynamicImage>>
+where
+    I: ImageInput + ?Sized,
+{
+    match filter {
+        Some(v) => Ok(std::borrow::Cow::Owned(v.filter(image)?)),
+        None => image.to_image(),
+    }
+}
+
+/// 根据图片缩放后的真实宽度，计算模型输出中有效的时间步数，填充部分不参与解码。
+fn valid_frames(frames: usize, width: usize, max_width: usize) -> usize {
+    if max_width == 0 {
+        return 0;
+    }
+
+    (frames * width).div_ceil(max_width).min(frames)
+}
+
+/// 对一个时间步的输出做 log-softmax，得到每个字符的对数概率。
+/// 先减去最大值再求指数，所以很大的输出也不会溢出。
+fn log_softmax(row: ndarray::ArrayView1<f32>) -> Vec<f32> {
+    let max = row.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+    let log_sum = row.fold(0.0, |a, &b| a + (b - max).exp()).ln();
+
+    row.iter().map(|v| (v - max) - log_sum).collect()
+}
+
 /// 内容识别需要用到的配置。
 ///
 /// `../model/common_charset.json`
@@ -403,49 +411,235 @@ pub struct CharacterProbability {
     pub text: Option<String>,
     pub charset: Vec<String>,
     pub probability: Vec<Vec<f32>>,
+
+    /// 每个时间步每个字符的对数概率（自然对数），
+    /// 只有 `classification_log_probability` 系列的接口会返回，概率很小的时候比 probability 更精确。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_probability: Option<Vec<Vec<f32>>>,
+
     pub confidence: Option<f64>,
+
+    /// 限定的字符范围中不在模型字符集里的字符，它们不会出现在 charset 和 probability 中。
+    #[serde(default)]
+    pub unknown: Vec<String>,
+
+    /// 模型的可信度校准，get_confidence 会使用它，见 `OcrModel::set_calibration`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
 }
 
 /// 有时候只想获取 probability，而不获取 text 和 confidence。
 impl CharacterProbability {
+    /// 只有字符集和概率的结果，其他字段为空，用于构造测试数据。
+    #[cfg(test)]
+    pub(crate) fn from_probability<S>(charset: &[S], probability: Vec<Vec<f32>>) -> Self
+    where
+        S: AsRef<str>,
+    {
+        Self {
+            text: None,
+            charset: charset.iter().map(|v| v.as_ref().to_string()).collect(),
+            probability,
+            log_probability: None,
+            confidence: None,
+            unknown: Vec::new(),
+            calibration: None,
+        }
+    }
+
+    /// 贪心解码的结果，与 `OcrModel::classification` 使用同一个解码器，详见 `ctc_greedy_decode`。
     pub fn get_text(&mut self) -> &str {
-        self.text.get_or_insert_with(|| {
-            let mut s = String::new();
+        self.text
+            .get_or_insert_with(|| ctc_greedy_decode(&self.probability, &self.charset))
+    }
 
-            for i in &self.probability {
-                let (n, _) = i
-                    .iter()
-                    .enumerate()
-                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
-                    .unwrap();
+    /// 可信度，如果模型有可信度校准，则返回校准后的值，即整个结果识别正确的概率。
+    pub fn get_confidence(&mut self) -> f64 {
+        if let Some(v) = self.confidence {
+            return v;
+        }
+
+        let confidence = self.get_raw_confidence();
+        let confidence = match &self.calibration {
+            Some(v) => v.apply(confidence),
+            None => confidence,
+        };
+
+        *self.confidence.insert(confidence)
+    }
+
+    /// 未校准的可信度，即每个时间步最大概率的平均值。
+    pub fn get_raw_confidence(&self) -> f64 {
+        let mut max_sum = 0.0;
+        let mut count = 0usize;
 
-                s += &self.charset[n];
+        for i in &self.probability {
+            if let Some(v) = i.iter().fold(None, |acc: Option<f64>, &i| {
+                acc.map_or(Some(i as f64), |max| Some(max.max(i as f64)))
+            }) {
+                max_sum += v;
+                count += 1;
             }
+        }
 
-            s
-        })
+        if count == 0 {
+            0.0
+        } else {
+            max_sum / count as f64
+        }
     }
 
-    pub fn get_confidence(&mut self) -> f64 {
-        *self.confidence.get_or_insert_with(|| {
-            let mut max_sum = 0.0;
-            let mut count = 0usize;
-
-            for i in &self.probability {
-                if let Some(v) = i.iter().fold(None, |acc: Option<f64>, &i| {
-                    acc.map_or(Some(i as f64), |max| Some(max.max(i as f64)))
-                }) {
-                    max_sum += v;
-                    count += 1;
+    /// 贪心解码，并根据可信度阈值判断结果是否可信，阈值的含义见 `Recognition::new`。
+    pub fn check_confidence(
+        &mut self,
+        min_confidence: Option<f64>,
+        min_char_confidence: Option<f64>,
+    ) -> Recognition {
+        Recognition::new(
+            self.get_text().to_string(),
+            self.get_confidence(),
+            ctc_char_confidence(&self.probability, &self.charset),
+            min_confidence,
+            min_char_confidence,
+        )
+    }
+
+    /// 根据可信度阈值判断指定的 text 是否可信，text 通常是束搜索、格式解码、词典解码或者语言模型的结果。
+    ///
+    /// 可信度是能折叠成 text 的概率最大的路径上每个时间步的概率的平均值，有可信度校准时返回校准后的值，
+    /// 每个字符的可信度见 `ctc_align_text`，text 是贪心解码的结果时与 `check_confidence` 相同，
+    /// text 不可能出现时可信度都是 0。
+    pub fn check_text_confidence(
+        &self,
+        text: &str,
+        min_confidence: Option<f64>,
+        min_char_confidence: Option<f64>,
+    ) -> Recognition {
+        let (confidence, char_confidence) =
+            match ctc::best_path(&self.probability, &self.charset, text) {
+                Some(path) => {
+                    let confidence = if path.is_empty() {
+                        0.0
+                    } else {
+                        path.iter()
+                            .zip(&self.probability)
+                            .map(|(&c, row)| row[c] as f64)
+                            .sum::<f64>()
+                            / path.len() as f64
+                    };
+                    let confidence = match &self.calibration {
+                        Some(v) => v.apply(confidence),
+                        None => confidence,
+                    };
+
+                    (
+                        confidence,
+                        self.align_text(text, 0)
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|v| v.confidence)
+                            .collect(),
+                    )
                 }
+                None => (0.0, vec![0.0; text.chars().count()]),
+            };
+
+        Recognition::new(
+            text.to_string(),
+            confidence,
+            char_confidence,
+            min_confidence,
+            min_char_confidence,
+        )
+    }
+
+    /// 把每个时间步的概率在限定的字符和空白上重新归一化，使它们之和为 1，
+    /// 限定字符范围后 get_confidence 表示的是字符之间的相对可信度，而不是在全字符表中的可信度。
+    pub fn renormalize(&mut self) {
+        for row in &mut self.probability {
+            let sum = row.iter().sum::<f32>();
+
+            if sum > 0.0 {
+                row.iter_mut().for_each(|v| *v /= sum);
             }
+        }
 
-            if count == 0 {
-                0.0
-            } else {
-                max_sum / count as f64
+        if let Some(log_probability) = &mut self.log_probability {
+            for row in log_probability {
+                let max = row.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+                if max.is_finite() {
+                    let log_sum = row.iter().map(|v| (v - max).exp()).sum::<f32>().ln();
+                    row.iter_mut().for_each(|v| *v = (*v - max) - log_sum);
+                }
             }
-        })
+        }
+
+        self.confidence = None;
+    }
+
+    /// 不区分大小写，把每个时间步只有大小写不同的字符的概率相加，合并成 case 指定的大小写，
+    /// 例如 `O` 和 `o` 合并成 `o`，之后的解码都使用合并后的概率。
+    pub fn fold_case(&mut self, case: LetterCase) {
+        let (charset, groups) = case_folding::case_groups(&self.charset, case);
+
+        self.probability = case_folding::merge_groups(&self.probability, &groups, false);
+
+        if let Some(log_probability) = &mut self.log_probability {
+            *log_probability = case_folding::merge_groups(log_probability, &groups, true);
+        }
+
+        self.charset = charset;
+        self.text = None;
+        self.confidence = None;
+    }
+
+    /// CTC 前缀束搜索，返回概率最高的 top_n 个候选结果，详见 `ctc_beam_search`。
+    pub fn beam_search(&self, beam_width: usize, top_n: usize) -> Vec<Candidate> {
+        ctc_beam_search(&self.probability, &self.charset, beam_width, top_n)
+    }
+
+    /// 只返回满足格式的候选结果，详见 `ctc_pattern_search`。
+    pub fn pattern_search(
+        &self,
+        pattern: &Pattern,
+        beam_width: usize,
+        top_n: usize,
+    ) -> Result<Vec<Candidate>> {
+        ctc_pattern_search(&self.probability, &self.charset, pattern, beam_width, top_n)
+    }
+
+    /// 使用语言模型重新打分的束搜索，详见 `ctc_language_model_search`。
+    pub fn language_model_search(
+        &self,
+        model: &NgramModel,
+        weight: f64,
+        beam_width: usize,
+        top_n: usize,
+    ) -> Vec<Candidate> {
+        ctc_language_model_search(
+            &self.probability,
+            &self.charset,
+            model,
+            weight,
+            beam_width,
+            top_n,
+        )
+    }
+
+    /// 返回词典中概率最高的词语，详见 `ctc_lexicon_search`。
+    pub fn lexicon_search(&self, lexicon: &Lexicon) -> Option<LexiconMatch> {
+        ctc_lexicon_search(&self.probability, &self.charset, lexicon)
+    }
+
+    /// 返回每个字符的可信度和位置，width 是原图的宽度，详见 `ctc_align`。
+    pub fn align(&self, width: u32) -> Vec<AlignedCharacter> {
+        ctc_align(&self.probability, &self.charset, width)
+    }
+
+    /// 返回指定的 text 中每个字符的可信度和位置，width 是原图的宽度，详见 `ctc_align_text`。
+    pub fn align_text(&self, text: &str, width: u32) -> Option<Vec<AlignedCharacter>> {
+        ctc_align_text(&self.probability, &self.charset, text, width)
     }
 }
 
@@ -554,6 +748,19 @@ const MODEL_HEIGHT: u32 = 416;
 const STRIDES: [u32; 3] = [8, 16, 32];
 
 /// 字符集范围。
+///
+/// 可以组合，例如 `CharsetRange::All - CharsetRange::Digit` 表示模型字符集中除了数字之外的字符，
+/// 也可以从字符串表达式解析，例如 `"cjk + digit - '0'".parse::<CharsetRange>()`，语法如下：
+/// - `0` ~ `7`：内置的字符范围，只能单独使用
+/// - `digit`、`lower`、`upper`：数字、小写字母、大写字母
+/// - `all`：模型字符集中的所有字符
+/// - `cjk`、`cjk_ext_a`、`cjk_symbols`、`hiragana`、`katakana`、`hangul`、`greek`、`cyrillic`、`fullwidth`、`basic_latin`：Unicode 区块
+/// - `U+4E00..U+9FFF`、`U+3007`：码位范围或者单个码位
+/// - `"+-x/="`、`'+-x/='`：字符串中的每个字符
+/// - `a + b` 或者 `a | b` 表示并集，`a - b` 表示差集，可以使用括号
+///
+/// 从字符串转换（`From<&str>`、`From<String>`）得到的是 `CharsetRange::Other`，即自定义字符集，
+/// 不会解析表达式，与服务器的 `expr:` 前缀一致，只有 `parse` 会解析表达式。
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum CharsetRange {
     /// 纯整数 0-9。
@@ -585,11 +792,90 @@ pub enum CharsetRange {
 
     /// 直接设置字符集，即 set_ranges 处理后的结果。
     Charset(Vec<String>),
+
+    /// 模型字符集中的所有字符。
+    All,
+
+    /// 模型字符集中码位在范围内的字符，包含两端，例如 `CodePoints('\u{4e00}', '\u{9fff}')`。
+    CodePoints(char, char),
+
+    /// 模型字符集中属于 Unicode 区块的字符。
+    Block(UnicodeBlock),
+
+    /// 多个字符范围的并集。
+    Union(Vec<CharsetRange>),
+
+    /// 第一个字符范围去掉第二个字符范围中的字符。
+    Difference(Box<CharsetRange>, Box<CharsetRange>),
+}
+
+impl CharsetRange {
+    /// 字符范围包含的字符，charset 是模型的字符集，没有去重，也没有补空字符串。
+    pub(crate) fn characters(self, charset: &[String]) -> Vec<String> {
+        let chars = |v: &str| v.chars().map(|v| v.to_string()).collect::<Vec<String>>();
+
+        match self {
+            CharsetRange::Digit => chars("0123456789"),
+            CharsetRange::Lowercase => chars("abcdefghijklmnopqrstuvwxyz"),
+            CharsetRange::Uppercase => chars("ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+            CharsetRange::LowercaseUppercase => {
+                chars("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ")
+            }
+            CharsetRange::LowercaseDigit => chars("abcdefghijklmnopqrstuvwxyz0123456789"),
+            CharsetRange::UppercaseDigit => chars("ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"),
+            CharsetRange::LowercaseUppercaseDigit => {
+                chars("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789")
+            }
+            CharsetRange::DefaultCharsetLowercaseUppercaseDigit => {
+                // 删除小写字母 a-z、大写字母 A-Z、整数 0-9
+                charset
+                    .iter()
+                    .filter(|v| v.chars().all(|c| !c.is_ascii_alphanumeric()))
+                    .cloned()
+                    .collect()
+            }
+            CharsetRange::Other(v) => chars(&v),
+            CharsetRange::Charset(v) => v,
+            CharsetRange::All => charset.iter().filter(|v| !v.is_empty()).cloned().collect(),
+            CharsetRange::CodePoints(start, end) => Self::code_points(charset, start, end),
+            CharsetRange::Block(v) => {
+                let (start, end) = v.range();
+                Self::code_points(charset, start, end)
+            }
+            CharsetRange::Union(v) => v.into_iter().flat_map(|v| v.characters(charset)).collect(),
+            CharsetRange::Difference(a, b) => {
+                let b = b
+                    .characters(charset)
+                    .into_iter()
+                    .collect::<std::collections::HashSet<_>>();
+
+                a.characters(charset)
+                    .into_iter()
+                    .filter(|v| !b.contains(v))
+                    .collect()
+            }
+        }
+    }
+
+    /// 模型字符集中码位在范围内的单个字符。
+    fn code_points(charset: &[String], start: char, end: char) -> Vec<String> {
+        charset
+            .iter()
+            .filter(|v| {
+                let mut chars = v.chars();
+
+                matches!((chars.next(), chars.next()), (Some(c), None) if (start..=end).contains(&c))
+            })
+            .cloned()
+            .collect()
+    }
 }
 
-impl From<i32> for CharsetRange {
-    fn from(value: i32) -> Self {
-        match value {
+impl TryFrom<i32> for CharsetRange {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self> {
+        Ok(match value {
             0 => Self::Digit,
             1 => Self::Lowercase,
             2 => Self::Uppercase,
@@ -598,11 +884,12 @@ impl From<i32> for CharsetRange {
             5 => Self::UppercaseDigit,
             6 => Self::LowercaseUppercaseDigit,
             7 => Self::DefaultCharsetLowercaseUppercaseDigit,
-            _ => panic!("invalid charset range: {}", value),
-        }
+            _ => return Err(Error::InvalidCharsetRange(value)),
+        })
     }
 }
 
+/// 自定义字符集，字符串中的每个字符都是一个候选字符，不会当作表达式解析。
 impl From<&str> for CharsetRange {
     fn from(value: &str) -> Self {
         CharsetRange::Other(value.to_string())
@@ -661,9 +948,11 @@ pub enum Color {
     Gray,
 }
 
-impl<T: AsRef<str>> From<T> for Color {
-    fn from(value: T) -> Self {
-        match value.as_ref().to_ascii_lowercase().as_str() {
+impl std::str::FromStr for Color {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
             "red" => Color::Red,
             "blue" => Color::Blue,
             "green" => Color::Green,
@@ -674,17 +963,37 @@ impl<T: AsRef<str>> From<T> for Color {
             "black" => Color::Black,
             "white" => Color::White,
             "gray" => Color::Gray,
-            _ => panic!("unknown color: {}", value.as_ref()),
-        }
+            _ => return Err(Error::UnknownColor(s.to_string())),
+        })
     }
 }
 
-pub trait IntoHsvRange {
-    fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))>;
+impl TryFrom<&str> for Color {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        value.parse()
+    }
 }
 
-impl IntoHsvRange for Color {
-    fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))> {
+impl TryFrom<String> for Color {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl TryFrom<&String> for Color {
+    type Error = Error;
+
+    fn try_from(value: &String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl Color {
+    fn hsv_ranges(self) -> Vec<HsvRange> {
         match self {
             Color::Red => vec![
                 ((0, 50, 50), (10, 255, 255)),
@@ -703,76 +1012,96 @@ impl IntoHsvRange for Color {
     }
 }
 
-/// 颜色过滤。
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(untagged)]
-pub enum ColorFilter {
-    /// HSV 范围，每个元素是一个 (min_hsv, max_hsv) 的元组。
-    /// 例如: `[((0, 50, 50), (10, 255, 255))]`。
-    HSVRanges(Vec<((u8, u8, u8), (u8, u8, u8))>),
+/// HSV 范围，(min_hsv, max_hsv)。
+pub type HsvRange = ((u8, u8, u8), (u8, u8, u8));
 
-    /// 颜色范围，例如: `["red", "blue"]`。
-    ColorRanges(Vec<Color>),
+/// 转换到 HSV 范围，颜色名称未知时返回 `Error::UnknownColor`。
+pub trait IntoHsvRange {
+    fn into_hsv_ranges(self) -> Result<Vec<HsvRange>>;
+}
 
-    /// 单个颜色。
-    Color(Color),
+impl IntoHsvRange for Color {
+    fn into_hsv_ranges(self) -> Result<Vec<HsvRange>> {
+        Ok(self.hsv_ranges())
+    }
 }
 
 impl IntoHsvRange for ColorFilter {
-    fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))> {
-        match self {
+    fn into_hsv_ranges(self) -> Result<Vec<HsvRange>> {
+        Ok(match self {
             ColorFilter::HSVRanges(v) => v,
-            ColorFilter::ColorRanges(v) => ColorFilter::from(v).into_hsv_ranges(),
-            ColorFilter::Color(v) => v.into_hsv_ranges(),
-        }
+            ColorFilter::ColorRanges(v) => v.into_iter().flat_map(Color::hsv_ranges).collect(),
+            ColorFilter::Color(v) => v.hsv_ranges(),
+        })
     }
 }
 
 impl IntoHsvRange for &str {
-    fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))> {
-        Color::from(self).into_hsv_ranges()
+    fn into_hsv_ranges(self) -> Result<Vec<HsvRange>> {
+        self.parse::<Color>()?.into_hsv_ranges()
     }
 }
 
 impl IntoHsvRange for String {
-    fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))> {
-        Color::from(self).into_hsv_ranges()
+    fn into_hsv_ranges(self) -> Result<Vec<HsvRange>> {
+        self.as_str().into_hsv_ranges()
     }
 }
 
 impl IntoHsvRange for &String {
-    fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))> {
-        Color::from(self).into_hsv_ranges()
+    fn into_hsv_ranges(self) -> Result<Vec<HsvRange>> {
+        self.as_str().into_hsv_ranges()
     }
 }
 
-impl IntoHsvRange for ((u8, u8, u8), (u8, u8, u8)) {
-    fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))> {
-        vec![self]
+impl IntoHsvRange for HsvRange {
+    fn into_hsv_ranges(self) -> Result<Vec<HsvRange>> {
+        Ok(vec![self])
     }
 }
 
-impl From<&str> for ColorFilter {
-    fn from(value: &str) -> Self {
-        Color::from(value).into_hsv_ranges().into()
+/// 颜色过滤。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum ColorFilter {
+    /// HSV 范围，每个元素是一个 (min_hsv, max_hsv) 的元组。
+    /// 例如: `[((0, 50, 50), (10, 255, 255))]`。
+    HSVRanges(Vec<HsvRange>),
+
+    /// 颜色范围，例如: `["red", "blue"]`。
+    ColorRanges(Vec<Color>),
+
+    /// 单个颜色。
+    Color(Color),
+}
+
+impl TryFrom<&str> for ColorFilter {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        Ok(value.into_hsv_ranges()?.into())
     }
 }
 
-impl From<String> for ColorFilter {
-    fn from(value: String) -> Self {
-        value.as_str().into()
+impl TryFrom<String> for ColorFilter {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.as_str().try_into()
     }
 }
 
-impl From<&String> for ColorFilter {
-    fn from(value: &String) -> Self {
-        value.as_str().into()
+impl TryFrom<&String> for ColorFilter {
+    type Error = Error;
+
+    fn try_from(value: &String) -> Result<Self> {
+        value.as_str().try_into()
     }
 }
 
 impl From<Color> for ColorFilter {
     fn from(value: Color) -> Self {
-        value.into_hsv_ranges().into()
+        value.hsv_ranges().into()
     }
 }
 
@@ -782,65 +1111,75 @@ impl From<&[Color]> for ColorFilter {
     }
 }
 
-impl From<&[&str]> for ColorFilter {
-    fn from(value: &[&str]) -> Self {
-        value
-            .iter()
-            .map(|v| Color::from(*v))
-            .collect::<Vec<_>>()
-            .into()
+impl TryFrom<&[&str]> for ColorFilter {
+    type Error = Error;
+
+    fn try_from(value: &[&str]) -> Result<Self> {
+        value.to_vec().try_into()
     }
 }
 
-impl<T, const N: usize> From<[T; N]> for ColorFilter
+impl<T, const N: usize> TryFrom<[T; N]> for ColorFilter
 where
     T: IntoHsvRange,
 {
-    fn from(value: [T; N]) -> Self {
-        value
-            .into_iter()
-            .flat_map(|v| v.into_hsv_ranges())
-            .collect::<Vec<_>>()
-            .into()
+    type Error = Error;
+
+    fn try_from(value: [T; N]) -> Result<Self> {
+        let mut ranges = Vec::new();
+
+        for i in value {
+            ranges.extend(i.into_hsv_ranges()?);
+        }
+
+        Ok(ranges.into())
     }
 }
 
-impl<'a, T, const N: usize> From<&'a [T; N]> for ColorFilter
+impl<'a, T, const N: usize> TryFrom<&'a [T; N]> for ColorFilter
 where
     T: IntoHsvRange + Clone,
 {
-    fn from(value: &'a [T; N]) -> Self {
-        value
-            .iter()
-            .flat_map(|v| v.clone().into_hsv_ranges())
-            .collect::<Vec<_>>()
-            .into()
+    type Error = Error;
+
+    fn try_from(value: &'a [T; N]) -> Result<Self> {
+        value.clone().try_into()
     }
 }
 
-impl From<&[String]> for ColorFilter {
-    fn from(values: &[String]) -> Self {
-        values.to_vec().into()
+impl TryFrom<&[String]> for ColorFilter {
+    type Error = Error;
+
+    fn try_from(values: &[String]) -> Result<Self> {
+        values.to_vec().try_into()
     }
 }
 
-impl From<Vec<&str>> for ColorFilter {
-    fn from(value: Vec<&str>) -> Self {
-        value
-            .into_iter()
-            .flat_map(|v| Color::from(v).into_hsv_ranges())
-            .collect::<Vec<_>>()
-            .into()
+impl TryFrom<Vec<&str>> for ColorFilter {
+    type Error = Error;
+
+    fn try_from(value: Vec<&str>) -> Result<Self> {
+        let mut ranges = Vec::new();
+
+        for i in value {
+            ranges.extend(i.into_hsv_ranges()?);
+        }
+
+        Ok(ranges.into())
     }
 }
 
-impl From<Vec<String>> for ColorFilter {
-    fn from(value: Vec<String>) -> Self {
-        value
-            .iter()
-            .flat_map(|v| Color::from(v.as_str()).into_hsv_ranges())
-            .collect::<Vec<_>>()
-            .into()
+impl TryFrom<Vec<String>> for ColorFilter {
+    type Error = Error;
+
+    fn try_from(value: Vec<String>) -> Result<Self> {
+        let mut ranges = Vec::new();
+
+        for i in value {
+            ranges.extend(i.into_hsv_ranges()?);
+        }
+
+        Ok(ranges.into())
     }
 }
 
@@ -848,25 +1187,25 @@ impl From<Vec<Color>> for ColorFilter {
     fn from(value: Vec<Color>) -> Self {
         value
             .into_iter()
-            .flat_map(|v| v.into_hsv_ranges())
+            .flat_map(Color::hsv_ranges)
             .collect::<Vec<_>>()
             .into()
     }
 }
 
-impl From<Vec<((u8, u8, u8), (u8, u8, u8))>> for ColorFilter {
-    fn from(value: Vec<((u8, u8, u8), (u8, u8, u8))>) -> Self {
+impl From<Vec<HsvRange>> for ColorFilter {
+    fn from(value: Vec<HsvRange>) -> Self {
         ColorFilter::HSVRanges(value)
     }
 }
 
 impl ColorFilter {
-    /// 过滤颜色，例如 ColorFilter::from("green").filter(image) 表示只保留绿色。
-    pub fn filter<I>(&self, image: I) -> anyhow::Result<image::DynamicImage>
+    /// 过滤颜色，例如 ColorFilter::try_from("green")?.filter(image) 表示只保留绿色。
+    pub fn filter<I>(&self, image: I) -> Result<image::DynamicImage>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
     {
-        let image = image::load_from_memory(image.as_ref())?.to_rgb8();
+        let image = image.to_image()?.to_rgb8();
         let (width, height) = image.dimensions();
         let mut array = ndarray::Array3::<u8>::zeros((height as usize, width as usize, 3));
 
@@ -912,11 +1251,10 @@ impl ColorFilter {
 
         let ranges = match self {
             ColorFilter::HSVRanges(v) => v,
-            ColorFilter::ColorRanges(v) => &v
-                .iter()
-                .flat_map(|v| v.into_hsv_ranges())
-                .collect::<Vec<_>>(),
-            ColorFilter::Color(v) => &v.into_hsv_ranges(),
+            ColorFilter::ColorRanges(v) => {
+                &v.iter().flat_map(|v| v.hsv_ranges()).collect::<Vec<_>>()
+            }
+            ColorFilter::Color(v) => &v.hsv_ranges(),
         };
 
         for (lower, upper) in ranges {
@@ -959,290 +1297,166 @@ impl ColorFilter {
     }
 }
 
-#[derive(Debug)]
-pub struct Ddddocr<'a> {
-    diy: bool,
-    session: ort::Session,
-    charset: Option<std::borrow::Cow<'a, Charset>>,
-    charset_range: Vec<String>,
-}
-
-unsafe impl<'a> Send for Ddddocr<'a> {}
-unsafe impl<'a> Sync for Ddddocr<'a> {}
-
-/// 因为自带模型和自定义模型的参数不同，所以在创建模型的时候会自动判断是否为自定义模型。
-impl<'a> Ddddocr<'a> {
-    /// 设置运行库的路径。
-    ///
-    /// - Unix: `/etc/.../libonnxruntime.so`
-    /// - Windows: `C:\\Program Files\\...\\onnxruntime.dll`
-    ///
-    /// 不需要手动调用此函数，因为程序会自动寻找运行库路径。
-    #[cfg(feature = "load-dynamic")]
-    pub fn set_onnxruntime_path<P>(path: P) -> anyhow::Result<()>
-    where
-        P: AsRef<std::path::Path>,
-    {
-        let path = path.as_ref();
+/// 设置运行库的路径。
+///
+/// - Unix: `/etc/.../libonnxruntime.so`
+/// - Windows: `C:\\Program Files\\...\\onnxruntime.dll`
+///
+/// 不需要手动调用此函数，因为程序会自动寻找运行库路径。
+#[cfg(feature = "load-dynamic")]
+pub fn set_onnxruntime_path<P>(path: P) -> Result<()>
+where
+    P: AsRef<std::path::Path>,
+{
+    let path = path.as_ref();
 
-        let save = std::panic::take_hook();
+    let save = std::panic::take_hook();
 
-        std::panic::set_hook(Box::new(|_| {}));
+    std::panic::set_hook(Box::new(|_| {}));
 
-        let result = std::panic::catch_unwind(|| {
-            ort::init_from(path.to_string_lossy().to_string())
-                .commit()
-                .unwrap()
-        });
+    let result = std::panic::catch_unwind(|| {
+        ort::init_from(path.to_string_lossy().to_string())
+            .commit()
+            .unwrap()
+    });
 
-        std::panic::set_hook(save);
+    std::panic::set_hook(save);
 
-        result.map_err(|v| {
-            anyhow::anyhow!(
-                "{}",
-                v.downcast::<String>().unwrap_or(Box::new(format!(
-                    "failed to load the runtime library: {}",
-                    path.display()
-                )))
-            )
-        })
-    }
+    result.map_err(|v| {
+        Error::RuntimeLibrary(*v.downcast::<String>().unwrap_or(Box::new(format!(
+            "failed to load the runtime library: {}",
+            path.display()
+        ))))
+    })
+}
 
-    /// 从内存加载模型和字符集，只能使用内容识别，使用目标检测会恐慌。
-    pub fn new<MODEL>(model: MODEL, charset: Charset) -> anyhow::Result<Self>
-    where
-        MODEL: AsRef<[u8]>,
-    {
-        Ok(Self {
-            diy: is_diy(model.as_ref()),
-            session: ort::Session::builder()?.commit_from_memory(model.as_ref())?,
-            charset: Some(std::borrow::Cow::Owned(charset)),
-            charset_range: Vec::new(),
-        })
-    }
+/// 内容识别模型。
+#[derive(Debug)]
+pub struct OcrModel<'a> {
+    diy: bool,
+    session: ort::Session,
+    charset: std::borrow::Cow<'a, Charset>,
+    charset_range: Vec<String>,
+    calibration: Option<Calibration>,
+    preprocessor: Preprocessor,
+}
+
+unsafe impl<'a> Send for OcrModel<'a> {}
+unsafe impl<'a> Sync for OcrModel<'a> {}
+
+/// 因为自带模型和自定义模型的参数不同，所以在创建模型的时候会自动判断是否为自定义模型。
+impl<'a> OcrModel<'a> {
+    pub(crate) fn from_session(
+        session: ort::Session,
+        diy: bool,
+        charset: std::borrow::Cow<'a, Charset>,
+    ) -> Result<Self> {
+        let channel = charset.channel as usize;
+        let normalization = if diy {
+            Normalization::imagenet(channel)
+        } else {
+            Normalization::ddddocr(channel)
+        };
 
-    /// 从内存加载模型和字符集，只能使用内容识别，使用目标检测会恐慌。
-    pub fn new_ref<MODEL>(model: MODEL, charset: &'a Charset) -> anyhow::Result<Self>
-    where
-        MODEL: AsRef<[u8]>,
-    {
         Ok(Self {
-            diy: is_diy(model.as_ref()),
-            session: ort::Session::builder()?.commit_from_memory(model.as_ref())?,
-            charset: Some(std::borrow::Cow::Borrowed(charset)),
+            diy,
+            session,
+            preprocessor: Preprocessor::new(&charset, normalization)?,
+            charset,
             charset_range: Vec::new(),
+            calibration: None,
         })
     }
 
-    /// 从内存加载模型和字符集，只能使用内容识别，使用目标检测会恐慌。
-    #[cfg(feature = "cuda")]
-    pub fn new_cuda<MODEL>(model: MODEL, charset: Charset, device_id: i32) -> anyhow::Result<Self>
+    /// 从内存加载模型和字符集，需要设置线程数等选项请使用 `DdddocrBuilder`。
+    pub fn new<MODEL>(model: MODEL, charset: Charset) -> Result<Self>
     where
         MODEL: AsRef<[u8]>,
     {
-        let builder = ort::Session::builder()?;
-
-        let cuda = ort::CUDAExecutionProvider::default()
-            .with_device_id(device_id)
-            .with_arena_extend_strategy(ort::ArenaExtendStrategy::NextPowerOfTwo)
-            .with_memory_limit(2 * 1024 * 1024 * 1024)
-            .with_conv_algorithm_search(ort::CUDAExecutionProviderCuDNNConvAlgoSearch::Exhaustive)
-            .with_copy_in_default_stream(true);
-
-        if !ort::ExecutionProvider::is_available(&cuda)? {
-            anyhow::bail!("please compile ONNX Runtime with CUDA!")
-        }
-
-        ort::ExecutionProvider::register(&cuda, &builder)?;
-
-        Ok(Self {
-            diy: is_diy(model.as_ref()),
-            session: builder.commit_from_memory(model.as_ref())?,
-            charset: Some(std::borrow::Cow::Owned(charset)),
-            charset_range: Vec::new(),
-        })
+        DdddocrBuilder::new().ocr(model, charset)
     }
 
-    /// 从内存加载模型和字符集，只能使用内容识别，使用目标检测会恐慌。
-    #[cfg(feature = "cuda")]
-    pub fn new_cuda_ref<MODEL>(
-        model: MODEL,
-        charset: &'a Charset,
-        device_id: i32,
-    ) -> anyhow::Result<Self>
+    /// 从内存加载模型和字符集。
+    pub fn new_ref<MODEL>(model: MODEL, charset: &'a Charset) -> Result<Self>
     where
         MODEL: AsRef<[u8]>,
     {
-        let builder = ort::Session::builder()?;
-
-        let cuda = ort::CUDAExecutionProvider::default()
-            .with_device_id(device_id)
-            .with_arena_extend_strategy(ort::ArenaExtendStrategy::NextPowerOfTwo)
-            .with_memory_limit(2 * 1024 * 1024 * 1024)
-            .with_conv_algorithm_search(ort::CUDAExecutionProviderCuDNNConvAlgoSearch::Exhaustive)
-            .with_copy_in_default_stream(true);
-
-        if !ort::ExecutionProvider::is_available(&cuda)? {
-            anyhow::bail!("please compile ONNX Runtime with CUDA!")
-        }
-
-        ort::ExecutionProvider::register(&cuda, &builder)?;
-
-        Ok(Self {
-            diy: is_diy(model.as_ref()),
-            session: builder.commit_from_memory(model.as_ref())?,
-            charset: Some(std::borrow::Cow::Borrowed(charset)),
-            charset_range: Vec::new(),
-        })
+        DdddocrBuilder::new().ocr_ref(model, charset)
     }
 
-    /// 从内存加载模型，只能使用目标检测，使用内容识别会恐慌。
-    pub fn new_model<MODEL>(model: MODEL) -> anyhow::Result<Self>
+    /// 从内存加载模型和字符集。
+    #[cfg(feature = "cuda")]
+    pub fn new_cuda<MODEL>(model: MODEL, charset: Charset, device_id: i32) -> Result<Self>
     where
         MODEL: AsRef<[u8]>,
     {
-        Ok(Self {
-            diy: is_diy(model.as_ref()),
-            session: ort::Session::builder()?.commit_from_memory(model.as_ref())?,
-            charset: None,
-            charset_range: Vec::new(),
-        })
+        DdddocrBuilder::new()
+            .execution_provider(ExecutionProvider::cuda(device_id))
+            .ocr(model, charset)
     }
 
-    /// 从内存加载模型，只能使用目标检测，使用内容识别会恐慌。
+    /// 从内存加载模型和字符集。
     #[cfg(feature = "cuda")]
-    pub fn new_model_cuda<MODEL>(model: MODEL, device_id: i32) -> anyhow::Result<Self>
+    pub fn new_cuda_ref<MODEL>(model: MODEL, charset: &'a Charset, device_id: i32) -> Result<Self>
     where
         MODEL: AsRef<[u8]>,
     {
-        let builder = ort::Session::builder()?;
-
-        let cuda = ort::CUDAExecutionProvider::default()
-            .with_device_id(device_id)
-            .with_arena_extend_strategy(ort::ArenaExtendStrategy::NextPowerOfTwo)
-            .with_memory_limit(2 * 1024 * 1024 * 1024)
-            .with_conv_algorithm_search(ort::CUDAExecutionProviderCuDNNConvAlgoSearch::Exhaustive)
-            .with_copy_in_default_stream(true);
-
-        if !ort::ExecutionProvider::is_available(&cuda)? {
-            anyhow::bail!("please compile ONNX Runtime with CUDA!")
-        }
-
-        ort::ExecutionProvider::register(&cuda, &builder)?;
-
-        Ok(Self {
-            diy: is_diy(model.as_ref()),
-            session: builder.commit_from_memory(model.as_ref())?,
-            charset: None,
-            charset_range: Vec::new(),
-        })
+        DdddocrBuilder::new()
+            .execution_provider(ExecutionProvider::cuda(device_id))
+            .ocr_ref(model, charset)
     }
 
-    /// 从文件加载模型和字符集，只能使用内容识别，使用目标检测会恐慌。
-    pub fn with_model_charset<PATH1, PATH2>(model: PATH1, charset: PATH2) -> anyhow::Result<Self>
+    /// 从文件加载模型和字符集。
+    pub fn with_model_charset<PATH1, PATH2>(model: PATH1, charset: PATH2) -> Result<Self>
     where
         PATH1: AsRef<std::path::Path>,
         PATH2: AsRef<std::path::Path>,
     {
-        Self::new(
-            std::fs::read(model)?,
-            serde_json::from_str(&std::fs::read_to_string(charset)?)?,
-        )
+        DdddocrBuilder::new().ocr_with_path(model, charset)
     }
 
-    /// 从文件加载模型和字符集，只能使用内容识别，使用目标检测会恐慌。
+    /// 从文件加载模型和字符集。
     #[cfg(feature = "cuda")]
     pub fn with_model_charset_cuda<PATH1, PATH2>(
         model: PATH1,
         charset: PATH2,
         device_id: i32,
-    ) -> anyhow::Result<Self>
+    ) -> Result<Self>
     where
         PATH1: AsRef<std::path::Path>,
         PATH2: AsRef<std::path::Path>,
     {
-        Self::new_cuda(
-            std::fs::read(model)?,
-            serde_json::from_str(&std::fs::read_to_string(charset)?)?,
-            device_id,
-        )
+        DdddocrBuilder::new()
+            .execution_provider(ExecutionProvider::cuda(device_id))
+            .ocr_with_path(model, charset)
     }
 
-    /// 从文件加载模型，只能使用目标检测，使用内容识别会恐慌。
-    pub fn with_model<P>(model: P) -> anyhow::Result<Self>
-    where
-        P: AsRef<std::path::Path>,
-    {
-        Self::new_model(std::fs::read(model)?)
+    /// 模型的字符集配置。
+    pub fn charset(&self) -> &Charset {
+        &self.charset
     }
 
-    /// 从文件加载模型，只能使用目标检测，使用内容识别会恐慌。
-    #[cfg(feature = "cuda")]
-    pub fn with_model_cuda<P>(model: P, device_id: i32) -> anyhow::Result<Self>
-    where
-        P: AsRef<std::path::Path>,
-    {
-        Self::new_model_cuda(std::fs::read(model)?, device_id)
+    /// 是否为自定义模型，自定义模型不能计算概率，也不能用于 `Ensemble`。
+    pub fn is_diy(&self) -> bool {
+        self.diy
     }
 
-    /// 根据给定 ranges 计算字符集范围。
-    pub fn calc_ranges<R>(&self, ranges: R) -> Vec<String>
+    /// 根据给定 ranges 计算字符集范围，字符串总是当作自定义字符集，例如 `"digit + upper"`
+    /// 表示这几个字符本身，表达式需要先解析，例如 `"digit + upper".parse::<CharsetRange>()?`。
+    pub fn calc_ranges<R>(&self, ranges: R) -> Result<Vec<String>>
     where
-        R: Into<CharsetRange>,
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
     {
-        let mut new_charset = match ranges.into() {
-            CharsetRange::Digit => "0123456789"
-                .chars()
-                .map(|v| v.to_string())
-                .collect::<Vec<String>>(),
-            CharsetRange::Lowercase => "abcdefghijklmnopqrstuvwxyz"
-                .chars()
-                .map(|v| v.to_string())
-                .collect::<Vec<String>>(),
-            CharsetRange::Uppercase => "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
-                .chars()
-                .map(|v| v.to_string())
-                .collect::<Vec<String>>(),
-            CharsetRange::LowercaseUppercase => {
-                "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"
-                    .chars()
-                    .map(|v| v.to_string())
-                    .collect::<Vec<String>>()
-            }
-            CharsetRange::LowercaseDigit => "abcdefghijklmnopqrstuvwxyz0123456789"
-                .chars()
-                .map(|v| v.to_string())
-                .collect::<Vec<String>>(),
-            CharsetRange::UppercaseDigit => "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"
-                .chars()
-                .map(|v| v.to_string())
-                .collect::<Vec<String>>(),
-            CharsetRange::LowercaseUppercaseDigit => {
-                "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"
-                    .chars()
-                    .map(|v| v.to_string())
-                    .collect::<Vec<String>>()
-            }
-            CharsetRange::DefaultCharsetLowercaseUppercaseDigit => {
-                let delete_range = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"
-                    .chars()
-                    .collect::<Vec<char>>();
-
-                self.charset
-                    .as_ref()
-                    .expect("only the ocr model can be used")
-                    .charset
-                    .clone()
-                    .into_iter()
-                    .filter(|v| v.chars().all(|c| !delete_range.contains(&c)))
-                    .collect::<Vec<String>>()
-            }
-            CharsetRange::Other(v) => v.chars().map(|v| v.to_string()).collect::<Vec<String>>(),
-            CharsetRange::Charset(v) => return v,
-        };
+        let ranges = ranges.try_into().map_err(Into::into)?;
+
+        if let CharsetRange::Charset(v) = ranges {
+            return Ok(v);
+        }
 
         // 去重 + 补空字符串
-        new_charset = new_charset
+        let mut new_charset = ranges
+            .characters(&self.charset.charset)
             .into_iter()
             .collect::<std::collections::HashSet<_>>()
             .into_iter()
@@ -1250,34 +1464,78 @@ impl<'a> Ddddocr<'a> {
 
         new_charset.push("".to_string());
 
-        new_charset
+        Ok(new_charset)
     }
 
-    /// 限定 classification_probability 的字符范围，只能使用内容识别，使用目标检测会恐慌。
-    pub fn set_ranges<R>(&mut self, ranges: R)
+    /// 限定 classification_probability 的字符范围，只能使用内容识别，
+    /// ranges 的含义同 `calc_ranges`，字符串不会当作表达式解析。
+    pub fn set_ranges<R>(&mut self, ranges: R) -> Result<()>
     where
-        R: Into<CharsetRange>,
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
     {
-        self.charset_range = self.calc_ranges(ranges)
+        self.charset_range = self.calc_ranges(ranges)?;
+
+        Ok(())
     }
 
     /// 内容识别，返回全字符表的概率，可以通过 `set_ranges` 限定字符范围，仅限于使用官方模型。
-    pub fn classification_probability<I>(&self, image: I) -> anyhow::Result<CharacterProbability>
+    pub fn classification_probability<I>(&self, image: I) -> Result<CharacterProbability>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
     {
         self.classification_probability_with_options(image, false, None, None)
     }
 
+    /// 模型的可信度校准。
+    pub fn calibration(&self) -> Option<&Calibration> {
+        self.calibration.as_ref()
+    }
+
+    /// 设置可信度校准，之后 `CharacterProbability::get_confidence` 返回校准后的值，
+    /// 从文件加载模型的时候，会自动加载字符集旁边的校准文件，见 `Calibration::path_for_charset`，
+    /// 从内存加载的模型（包括开启 inline-model 时的内置模型）没有字符集文件，需要调用这个方法，
+    /// 例如 `set_calibration(Some(Calibration::with_path("model/common.calibration.json")?))`。
+    pub fn set_calibration(&mut self, calibration: Option<Calibration>) {
+        self.calibration = calibration;
+    }
+
+    /// 图片预处理，官方模型使用 `Normalization::ddddocr`，自定义模型使用 `Normalization::imagenet`。
+    pub fn preprocessor(&self) -> &Preprocessor {
+        &self.preprocessor
+    }
+
+    /// 设置图片标准化的参数，适合训练时使用了其他标准化的自定义模型。
+    pub fn set_normalization(&mut self, normalization: Normalization) -> Result<()> {
+        self.preprocessor.set_normalization(normalization)
+    }
+
+    /// 使用标注数据集拟合可信度校准，数据集的格式见 `labeled_images`，
+    /// 会使用 `set_ranges` 的字符范围，所以拟合时的字符范围要与使用时相同，仅限于使用官方模型。
+    pub fn fit_calibration<P>(&self, dir: P, method: CalibrationMethod) -> Result<Calibration>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let mut samples = Vec::new();
+
+        for (path, label) in labeled_images(dir)? {
+            let mut result = self.classification_probability(&path)?;
+
+            samples.push((result.get_raw_confidence(), result.get_text() == label));
+        }
+
+        Calibration::fit(&samples, method)
+    }
+
     /// 内容识别，返回全字符表的概率，可以通过 `set_ranges` 限定字符范围，仅限于使用官方模型。
     /// 如果 `png_fix` 为 true，则支持透明黑色背景的 png 图片。
     pub fn classification_probability_with_png_fix<I>(
         &self,
         image: I,
         png_fix: bool,
-    ) -> anyhow::Result<CharacterProbability>
+    ) -> Result<CharacterProbability>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
     {
         self.classification_probability_with_options(image, png_fix, None, None)
     }
@@ -1288,12 +1546,18 @@ impl<'a> Ddddocr<'a> {
         &self,
         image: I,
         filter: F,
-    ) -> anyhow::Result<CharacterProbability>
+    ) -> Result<CharacterProbability>
     where
-        I: AsRef<[u8]>,
-        F: Into<ColorFilter>,
+        I: ImageInput,
+        F: TryInto<ColorFilter>,
+        F::Error: Into<Error>,
     {
-        self.classification_probability_with_options(image, false, Some(filter.into()), None)
+        self.classification_probability_with_options(
+            image,
+            false,
+            Some(filter.try_into().map_err(Into::into)?),
+            None,
+        )
     }
 
     /// 内容识别，返回指定字符范围的概率，可通过 `ranges` 限定字符范围。
@@ -1301,12 +1565,18 @@ impl<'a> Ddddocr<'a> {
         &self,
         image: I,
         ranges: R,
-    ) -> anyhow::Result<CharacterProbability>
+    ) -> Result<CharacterProbability>
     where
-        I: AsRef<[u8]>,
-        R: Into<CharsetRange>,
+        I: ImageInput,
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
     {
-        self.classification_probability_with_options(image, false, None, Some(ranges.into()))
+        self.classification_probability_with_options(
+            image,
+            false,
+            None,
+            Some(ranges.try_into().map_err(Into::into)?),
+        )
     }
 
     /// 内容识别，返回指定字符范围的概率，可通过 `png_fix` 支持透明黑色背景的 png 图片，并通过 `ranges` 限定字符范围。
@@ -1315,12 +1585,18 @@ impl<'a> Ddddocr<'a> {
         image: I,
         png_fix: bool,
         ranges: R,
-    ) -> anyhow::Result<CharacterProbability>
+    ) -> Result<CharacterProbability>
     where
-        I: AsRef<[u8]>,
-        R: Into<CharsetRange>,
+        I: ImageInput,
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
     {
-        self.classification_probability_with_options(image, png_fix, None, Some(ranges.into()))
+        self.classification_probability_with_options(
+            image,
+            png_fix,
+            None,
+            Some(ranges.try_into().map_err(Into::into)?),
+        )
     }
 
     /// 内容识别，返回指定字符范围的概率，可通过 `filter` 指定颜色过滤，并通过 `ranges` 限定字符范围。
@@ -1329,17 +1605,19 @@ impl<'a> Ddddocr<'a> {
         image: I,
         filter: F,
         ranges: R,
-    ) -> anyhow::Result<CharacterProbability>
+    ) -> Result<CharacterProbability>
     where
-        I: AsRef<[u8]>,
-        F: Into<ColorFilter>,
-        R: Into<CharsetRange>,
+        I: ImageInput,
+        F: TryInto<ColorFilter>,
+        F::Error: Into<Error>,
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
     {
         self.classification_probability_with_options(
             image,
             false,
-            Some(filter.into()),
-            Some(ranges.into()),
+            Some(filter.try_into().map_err(Into::into)?),
+            Some(ranges.try_into().map_err(Into::into)?),
         )
     }
 
@@ -1353,394 +1631,662 @@ impl<'a> Ddddocr<'a> {
         png_fix: bool,
         filter: Option<ColorFilter>,
         ranges: Option<CharsetRange>,
-    ) -> anyhow::Result<CharacterProbability>
+    ) -> Result<CharacterProbability>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
     {
-        if self.diy {
-            // 嘿，傻瓜，这里明明写了只能用官方模型，你是故意不看吗？发生 panic 的话自己负责哦！
-            panic!("can only use the official model");
-        }
-
-        let mut _temp = Vec::new();
-        let charset_ranges = match &ranges {
-            Some(v) => 'a: {
-                let new_charset = match v {
-                    CharsetRange::Digit => "0123456789"
-                        .chars()
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>(),
-                    CharsetRange::Lowercase => "abcdefghijklmnopqrstuvwxyz"
-                        .chars()
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>(),
-                    CharsetRange::Uppercase => "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
-                        .chars()
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>(),
-                    CharsetRange::LowercaseUppercase => {
-                        "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"
-                            .chars()
-                            .map(|v| v.to_string())
-                            .collect::<Vec<String>>()
-                    }
-                    CharsetRange::LowercaseDigit => "abcdefghijklmnopqrstuvwxyz0123456789"
-                        .chars()
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>(),
-                    CharsetRange::UppercaseDigit => "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"
-                        .chars()
-                        .map(|v| v.to_string())
-                        .collect::<Vec<String>>(),
-                    CharsetRange::LowercaseUppercaseDigit => {
-                        "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"
-                            .chars()
-                            .map(|v| v.to_string())
-                            .collect::<Vec<String>>()
-                    }
-                    CharsetRange::DefaultCharsetLowercaseUppercaseDigit => {
-                        // 删除小写字母 a-z、大写字母 A-Z、整数 0-9
-                        let delete_range =
-                            "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"
-                                .chars()
-                                .collect::<Vec<char>>();
-
-                        self.charset
-                            .as_ref()
-                            .ok_or(anyhow::anyhow!("only the ocr model can be used"))?
-                            .charset
-                            .clone()
-                            .into_iter()
-                            .filter(|v| v.chars().all(|c| !delete_range.contains(&c)))
-                            .collect::<Vec<String>>()
-                    }
-                    CharsetRange::Other(v) => {
-                        v.chars().map(|v| v.to_string()).collect::<Vec<String>>()
-                    }
-                    CharsetRange::Charset(v) => break 'a v,
-                };
-
-                // 去重
-                _temp = new_charset
-                    .into_iter()
-                    .collect::<std::collections::HashSet<_>>()
-                    .into_iter()
-                    .collect::<Vec<String>>();
-
-                _temp.push("".to_string());
-
-                &_temp
-            }
-            None => &self.charset_range,
-        };
-
-        let image = match filter {
-            Some(v) => v.filter(image.as_ref())?,
-            None => image::load_from_memory(image.as_ref())?,
-        };
-
-        let charset = self.charset.as_ref().unwrap();
-        let word = charset.word;
-        let resize = charset.image;
-        let channel = charset.channel;
-        let charset = &charset.charset;
-
-        // 使用 ANTIALIAS (Lanczos3) 缩放图片
-        let image = if resize[0] == -1 {
-            if word {
-                image.resize_exact(
-                    resize[1] as u32,
-                    resize[1] as u32,
-                    image::imageops::FilterType::Lanczos3,
-                )
-            } else {
-                image.resize_exact(
-                    image.width() * resize[1] as u32 / image.height(),
-                    resize[1] as u32,
-                    image::imageops::FilterType::Lanczos3,
-                )
-            }
-        } else {
-            image.resize_exact(
-                resize[0] as u32,
-                resize[1] as u32,
-                image::imageops::FilterType::Lanczos3,
-            )
-        };
-
-        // 设置图片的通道数为模型所需的通道数
-        let image_bytes = if channel == 1 {
-            image::EncodableLayout::as_bytes(image.to_luma8().as_ref()).to_vec()
-        } else if png_fix {
-            png_rgba_black_preprocess(&image).to_rgb8().to_vec()
-        } else {
-            image.to_rgb8().to_vec()
-        };
-
-        // 图片转换到张量
-        let channel = channel as usize;
-        let width = image.width() as usize;
-        let height = image.height() as usize;
-        let image = ndarray::Array::from_shape_vec((channel, height, width), image_bytes)?;
-        let mut tensor = ndarray::Array::from_shape_vec(
-            (1, channel, height, width),
-            vec![0f32; height * width],
-        )?;
-
-        // 根据配置标准化图像张量
-        for i in 0..height {
-            for j in 0..width {
-                let now = image[[0, i, j]] as f32;
-                if self.diy {
-                    // 自定义模型
-                    if channel == 1 {
-                        tensor[[0, 0, i, j]] = ((now / 255f32) - 0.456f32) / 0.224f32;
-                    } else {
-                        let r = image[[0, i, j]] as f32;
-                        let g = image[[1, i, j]] as f32;
-                        let b = image[[2, i, j]] as f32;
-                        tensor[[0, 0, i, j]] = ((r / 255f32) - 0.485f32) / 0.229f32;
-                        tensor[[0, 1, i, j]] = ((g / 255f32) - 0.456f32) / 0.224f32;
-                        tensor[[0, 2, i, j]] = ((b / 255f32) - 0.406f32) / 0.225f32;
-                    }
-                } else {
-                    tensor[[0, 0, i, j]] = ((now / 255f32) - 0.5f32) / 0.5f32;
-                }
-            }
-        }
-
-        let ort_outs = &self.session.run(ort::inputs![tensor]?)?;
-
-        let ort_outs = &ort_outs[0].try_extract_tensor()?;
-
-        // 长这样 [[[1,2,3,4]], [[1,2,3,4]], [[1,2,3,4]]]
-        let ort_outs = ort_outs.mapv(f32::exp) / ort_outs.mapv(f32::exp).sum();
-
-        // 长这样 [[1,2,3,4], [1,2,3,4], [1,2,3,4]]
-        let ort_outs_sum = ort_outs.sum_axis(ndarray::Axis(2));
-
-        // 根据形状创建一个零数组
-        let mut ort_outs_probability = ndarray::Array::<f32, _>::zeros(ort_outs.raw_dim());
-
-        for i in 0..ort_outs.shape()[0] {
-            let mut a = ort_outs_probability.slice_mut(ndarray::s![i, .., ..]);
-            let b = ort_outs.slice(ndarray::s![i, .., ..]);
-            let c = ort_outs_sum.slice(ndarray::s![i, ..]);
-            let d = &b / &c;
-
-            a.assign(&d);
-        }
-
-        // 调用 next 后，长这样 [[1,2,3,4], [1,2,3,4], [1,2,3,4]]
-        let ort_outs_probability = ort_outs_probability
-            .axis_iter(ndarray::Axis(1))
-            .next()
-            .ok_or(anyhow::anyhow!("expect axis 1"))?;
-
-        let mut result = Vec::new();
-
-        // 扁平化
-        for i in ort_outs_probability.axis_iter(ndarray::Axis(0)) {
-            result.push(i.into_diag().to_vec());
-        }
-
-        if charset_ranges.is_empty() {
-            // 返回全部字符的概率
-            Ok(CharacterProbability {
-                text: None,
-                charset: charset.clone(),
-                probability: result,
-                confidence: None,
+        self.classification_probability_batch_with_options(&[image], png_fix, filter, ranges)?
+            .pop()
+            .ok_or_else(|| {
+                Error::Shape(ndarray::ShapeError::from_kind(
+                    ndarray::ErrorKind::IncompatibleShape,
+                ))
             })
-        } else {
-            // 根据指定的字符范围，从模型输出的概率结果中提取对应字符的概率
-            // 如果字符不在字符集中，则将其概率设置为 -1.0，表示未知字符
-            let mut probability_result_index = Vec::new();
-
-            for i in charset_ranges {
-                if let Some(v) = charset.iter().position(|v| v == i) {
-                    probability_result_index.push(v);
-                } else {
-                    probability_result_index.push(usize::MAX);
-                }
-            }
+    }
 
-            let mut probability_result = Vec::new();
+    /// 内容识别，使用 CTC 束搜索返回概率最高的 top_n 个候选结果，可以通过 `set_ranges` 限定字符范围，仅限于使用官方模型。
+    /// 第一个结果被拒绝的时候，可以提交第二个结果，而不用重新获取验证码。
+    pub fn classification_candidates<I>(
+        &self,
+        image: I,
+        beam_width: usize,
+        top_n: usize,
+    ) -> Result<Vec<Candidate>>
+    where
+        I: ImageInput,
+    {
+        self.classification_candidates_with_options(image, false, None, None, beam_width, top_n)
+    }
 
-            for item in &result {
-                let mut inner_vec = Vec::new();
+    /// 内容识别，使用 CTC 束搜索返回概率最高的 top_n 个候选结果，仅限于使用官方模型。
+    /// png_fix、filter、ranges 的含义同 `classification_probability_with_options`。
+    pub fn classification_candidates_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        beam_width: usize,
+        top_n: usize,
+    ) -> Result<Vec<Candidate>>
+    where
+        I: ImageInput,
+    {
+        Ok(self
+            .classification_probability_with_options(image, png_fix, filter, ranges)?
+            .beam_search(beam_width, top_n))
+    }
 
-                for &i in &probability_result_index {
-                    if i != usize::MAX {
-                        inner_vec.push(item[i]);
-                    } else {
-                        inner_vec.push(-1.0);
-                    }
-                }
+    /// 内容识别，返回每个字符的可信度、所在的时间步和原图中的横坐标范围，可以通过 `set_ranges` 限定字符范围，仅限于使用官方模型。
+    /// 可以用来找出可能识别错误的字符，或者裁剪单个字符重新识别。
+    pub fn classification_aligned<I>(&self, image: I) -> Result<Vec<AlignedCharacter>>
+    where
+        I: ImageInput,
+    {
+        self.classification_aligned_with_options(image, false, None, None)
+    }
 
-                probability_result.push(inner_vec);
-            }
+    /// 内容识别，返回每个字符的可信度和位置，仅限于使用官方模型。
+    /// png_fix、filter、ranges 的含义同 `classification_probability_with_options`。
+    pub fn classification_aligned_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+    ) -> Result<Vec<AlignedCharacter>>
+    where
+        I: ImageInput,
+    {
+        let image = image.to_image()?;
 
-            Ok(CharacterProbability {
-                text: None,
-                charset: charset_ranges.clone(),
-                probability: probability_result,
-                confidence: None,
-            })
-        }
+        Ok(self
+            .classification_probability_with_options(&*image, png_fix, filter, ranges)?
+            .align(image.width()))
     }
 
-    /// 内容识别。
-    pub fn classification<I>(&self, image: I) -> anyhow::Result<String>
+    /// 内容识别，返回满足格式的概率最高的结果，例如 `"\\d{4}"` 表示 4 个数字，仅限于使用官方模型。
+    /// 如果没有满足格式的结果，则返回 None。
+    pub fn classification_with_pattern<I, P>(
+        &self,
+        image: I,
+        pattern: P,
+    ) -> Result<Option<Candidate>>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
+        P: TryInto<Pattern>,
+        P::Error: Into<Error>,
     {
-        self.classification_with_options(image, false, None)
+        self.classification_with_pattern_and_options(
+            image,
+            false,
+            None,
+            None,
+            pattern.try_into().map_err(Into::into)?,
+            DEFAULT_BEAM_WIDTH,
+        )
     }
 
-    /// 内容识别。
-    pub fn classification_with_path<P>(&self, path: P) -> anyhow::Result<String>
+    /// 内容识别，返回满足格式的概率最高的结果，仅限于使用官方模型。
+    /// png_fix、filter、ranges 的含义同 `classification_probability_with_options`，beam_width 的含义同 `ctc_beam_search`。
+    pub fn classification_with_pattern_and_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        pattern: Pattern,
+        beam_width: usize,
+    ) -> Result<Option<Candidate>>
     where
-        P: AsRef<std::path::Path>,
+        I: ImageInput,
     {
-        self.classification(std::fs::read(path)?)
+        Ok(self
+            .classification_probability_with_options(image, png_fix, filter, ranges)?
+            .pattern_search(&pattern, beam_width, 1)?
+            .pop())
     }
 
-    /// 内容识别，如果 png_fix 为 true，则支持透明黑色背景的 png 图片。
-    pub fn classification_with_png_fix<I>(&self, image: I, png_fix: bool) -> anyhow::Result<String>
+    /// 内容识别，返回词典中概率最高的词语和与第二名的差距，适合成语、固定单词这类验证码，仅限于使用官方模型。
+    /// 如果词典中没有任何词语有可能，例如字符都不在字符集中，则返回 None。
+    pub fn classification_with_lexicon<I>(
+        &self,
+        image: I,
+        lexicon: &Lexicon,
+    ) -> Result<Option<LexiconMatch>>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
     {
-        self.classification_with_options(image, png_fix, None)
+        self.classification_with_lexicon_and_options(image, false, None, None, lexicon)
     }
 
-    /// 内容识别，如果 png_fix 为 true，则支持透明黑色背景的 png 图片。
-    pub fn classification_with_path_png_fix<P>(
+    /// 内容识别，返回词典中概率最高的词语，仅限于使用官方模型。
+    /// png_fix、filter、ranges 的含义同 `classification_probability_with_options`。
+    pub fn classification_with_lexicon_and_options<I>(
         &self,
-        path: P,
+        image: I,
         png_fix: bool,
-    ) -> anyhow::Result<String>
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        lexicon: &Lexicon,
+    ) -> Result<Option<LexiconMatch>>
     where
-        P: AsRef<std::path::Path>,
+        I: ImageInput,
     {
-        self.classification_with_png_fix(std::fs::read(path)?, png_fix)
+        Ok(self
+            .classification_probability_with_options(image, png_fix, filter, ranges)?
+            .lexicon_search(lexicon))
     }
 
-    /// 内容识别，如果 filter 为 red，则表示只识别红色。
-    pub fn classification_with_filter<I, F>(&self, image: I, filter: F) -> anyhow::Result<String>
+    /// 批量内容识别，返回每张图片全字符表的概率，可以通过 `set_ranges` 限定字符范围，仅限于使用官方模型。
+    /// 所有图片只运行一次推理，适合大量图片的场景。
+    pub fn classification_probability_batch<I>(
+        &self,
+        images: &[I],
+    ) -> Result<Vec<CharacterProbability>>
     where
-        I: AsRef<[u8]>,
-        F: Into<ColorFilter>,
+        I: ImageInput,
     {
-        self.classification_with_options(image, false, Some(filter.into()))
+        self.classification_probability_batch_with_options(images, false, None, None)
     }
 
-    /// 内容识别，如果 filter 为 red，则表示只识别红色。
-    pub fn classification_with_path_filter<P, F>(
+    /// 批量内容识别，返回每张图片全字符表的概率，仅限于使用官方模型，参数的含义同 `classification_probability_with_options`。
+    /// 图片会缩放到模型所需的高度，然后填充到这一批中最宽图片的宽度，每张图片只解码自己真实宽度对应的部分。
+    pub fn classification_probability_batch_with_options<I>(
         &self,
-        path: P,
-        filter: F,
-    ) -> anyhow::Result<String>
+        images: &[I],
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+    ) -> Result<Vec<CharacterProbability>>
     where
-        P: AsRef<std::path::Path>,
-        F: Into<ColorFilter>,
+        I: ImageInput,
     {
-        self.classification_with_filter(std::fs::read(path)?, filter)
+        self.probability_batch(images, png_fix, filter, ranges, false, None)
     }
 
-    /// 内容识别，如果 png_fix 为 true，则支持透明黑色背景的 png 图片，如果 filter 为 red，则表示只识别红色。
-    pub fn classification_with_options<I>(
+    /// 内容识别，返回全字符表的概率和对数概率，可以通过 `set_ranges` 限定字符范围，仅限于使用官方模型。
+    pub fn classification_log_probability<I>(&self, image: I) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.classification_log_probability_with_options(image, false, None, None)
+    }
+
+    /// 内容识别，返回全字符表的概率和对数概率，仅限于使用官方模型，参数的含义同 `classification_probability_with_options`。
+    /// 对数概率由每个时间步的 log-softmax 直接得到，不会因为概率太小而变成 0。
+    pub fn classification_log_probability_with_options<I>(
         &self,
         image: I,
         png_fix: bool,
         filter: Option<ColorFilter>,
-    ) -> anyhow::Result<String>
+        ranges: Option<CharsetRange>,
+    ) -> Result<CharacterProbability>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
     {
-        let image = match filter {
-            Some(v) => v.filter(image.as_ref())?,
-            None => image::load_from_memory(image.as_ref())?,
-        };
+        self.probability_batch(&[image], png_fix, filter, ranges, true, None)?
+            .pop()
+            .ok_or_else(|| {
+                Error::Shape(ndarray::ShapeError::from_kind(
+                    ndarray::ErrorKind::IncompatibleShape,
+                ))
+            })
+    }
 
-        let charset = self.charset.as_ref().unwrap();
-        let word = charset.word;
-        let resize = charset.image;
-        let channel = charset.channel;
-        let charset = &charset.charset;
-
-        // 使用 ANTIALIAS (Lanczos3) 缩放图片
-        let image = if resize[0] == -1 {
-            if word {
-                image.resize_exact(
-                    resize[1] as u32,
-                    resize[1] as u32,
-                    image::imageops::FilterType::Lanczos3,
-                )
-            } else {
-                image.resize_exact(
-                    image.width() * resize[1] as u32 / image.height(),
-                    resize[1] as u32,
-                    image::imageops::FilterType::Lanczos3,
-                )
-            }
-        } else {
-            image.resize_exact(
-                resize[0] as u32,
-                resize[1] as u32,
-                image::imageops::FilterType::Lanczos3,
-            )
+    /// 不区分大小写的内容识别，可以通过 `set_ranges` 限定字符范围，仅限于使用官方模型。
+    /// 每个时间步只有大小写不同的字符的概率会相加，结果使用 case 指定的大小写，适合不区分大小写的验证码。
+    pub fn classification_probability_case_insensitive<I>(
+        &self,
+        image: I,
+        case: LetterCase,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.classification_probability_case_insensitive_with_options(
+            image, false, None, None, case,
+        )
+    }
+
+    /// 不区分大小写的内容识别，仅限于使用官方模型，参数的含义同 `classification_probability_with_options`。
+    /// 限定字符范围时，字符的其他大小写形式也会参与合并，例如字符范围只有 `a`，`A` 的概率也会加到 `a` 上。
+    pub fn classification_probability_case_insensitive_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        case: LetterCase,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.probability_batch(&[image], png_fix, filter, ranges, false, Some(case))?
+            .pop()
+            .ok_or_else(|| {
+                Error::Shape(ndarray::ShapeError::from_kind(
+                    ndarray::ErrorKind::IncompatibleShape,
+                ))
+            })
+    }
+
+    /// 不区分大小写的内容识别，同时返回合并后的对数概率，
+    /// 参数的含义同 `classification_probability_case_insensitive_with_options`。
+    pub fn classification_log_probability_case_insensitive_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        case: LetterCase,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.probability_batch(&[image], png_fix, filter, ranges, true, Some(case))?
+            .pop()
+            .ok_or_else(|| {
+                Error::Shape(ndarray::ShapeError::from_kind(
+                    ndarray::ErrorKind::IncompatibleShape,
+                ))
+            })
+    }
+
+    /// 测试时增强，识别已经预处理的图片的所有变体，然后按照 strategy 组合结果，所有变体只运行一次推理，
+    /// toggled 是使用相反的 png_fix 预处理的图片，用于 `Augmentation::PngFix`。
+    fn classification_tta(
+        &self,
+        image: &image::DynamicImage,
+        toggled: &image::DynamicImage,
+        ranges: Option<CharsetRange>,
+        augmentations: &[Augmentation],
+        strategy: EnsembleStrategy,
+    ) -> Result<EnsembleResult> {
+        let size = self.preprocessor.size(image.width(), image.height())?;
+        let images = augmentations
+            .iter()
+            .map(|v| v.apply(if v.toggles_png_fix() { toggled } else { image }, size))
+            .collect::<Vec<_>>();
+
+        ensemble::combine(
+            self.probability_batch(&images, false, None, ranges, false, None)?,
+            strategy,
+        )
+    }
+
+    /// 批量计算概率，如果 log_probability 为 true，则同时返回对数概率，
+    /// 如果 case 不为 None，则合并只有大小写不同的字符。
+    fn probability_batch<I>(
+        &self,
+        images: &[I],
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        log_probability: bool,
+        case: Option<LetterCase>,
+    ) -> Result<Vec<CharacterProbability>>
+    where
+        I: ImageInput,
+    {
+        if self.diy {
+            // 嘿，傻瓜，这里明明写了只能用官方模型，你是故意不看吗？
+            return Err(Error::UnsupportedCustomModel);
+        }
+
+        let _temp;
+        let charset_ranges = match ranges {
+            Some(v) => {
+                _temp = self.calc_ranges(v)?;
+                &_temp
+            }
+            None => &self.charset_range,
         };
 
-        // 设置图片的通道数为模型所需的通道数
-        let image_bytes = if channel == 1 {
-            image::EncodableLayout::as_bytes(image.to_luma8().as_ref()).to_vec()
-        } else if png_fix {
-            png_rgba_black_preprocess(&image).to_rgb8().to_vec()
-        } else {
-            image.to_rgb8().to_vec()
+        let _temp;
+        let charset_ranges = match case {
+            Some(_) if !charset_ranges.is_empty() => {
+                _temp = case_folding::case_variants(charset_ranges, &self.charset.charset);
+                &_temp
+            }
+            _ => charset_ranges,
         };
 
-        // 图片转换到张量
-        let channel = channel as usize;
-        let width = image.width() as usize;
-        let height = image.height() as usize;
-        let image = ndarray::Array::from_shape_vec((channel, height, width), image_bytes)?;
-        let mut tensor = ndarray::Array::from_shape_vec(
-            (1, channel, height, width),
-            vec![0f32; channel * height * width],
-        )?;
+        let images = images
+            .iter()
+            .map(|v| load_image(v, filter.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
 
-        // 根据配置标准化图像张量
-        for i in 0..height {
-            for j in 0..width {
-                let now = image[[0, i, j]] as f32;
+        Ok(self
+            .log_probability_batch(&images, png_fix)?
+            .into_iter()
+            .map(|v| {
+                let mut result = self.character_probability(v, charset_ranges, log_probability);
 
-                if self.diy {
-                    // 自定义模型
-                    if channel == 1 {
-                        tensor[[0, 0, i, j]] = ((now / 255f32) - 0.456f32) / 0.224f32;
-                    } else {
-                        let r = image[[0, i, j]] as f32;
-                        let g = image[[1, i, j]] as f32;
-                        let b = image[[2, i, j]] as f32;
-                        tensor[[0, 0, i, j]] = ((r / 255f32) - 0.485f32) / 0.229f32;
-                        tensor[[0, 1, i, j]] = ((g / 255f32) - 0.456f32) / 0.224f32;
-                        tensor[[0, 2, i, j]] = ((b / 255f32) - 0.406f32) / 0.225f32;
-                    }
-                } else {
-                    tensor[[0, 0, i, j]] = ((now / 255f32) - 0.5f32) / 0.5f32;
+                if let Some(case) = case {
+                    result.fold_case(case);
                 }
+
+                result
+            })
+            .collect())
+    }
+
+    /// 所有图片只运行一次推理，返回每张图片每个时间步全字符表的对数概率，只包含图片真实宽度对应的时间步。
+    fn log_probability_batch<T>(&self, images: &[T], png_fix: bool) -> Result<Vec<Vec<Vec<f32>>>>
+    where
+        T: std::borrow::Borrow<image::DynamicImage>,
+    {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (tensor, widths) = self.preprocessor.batch(images, png_fix)?;
+        let max_width = widths.iter().copied().max().unwrap_or(0);
+
+        let ort_outs = self.session.run(ort::inputs![tensor]?)?;
+
+        // 长这样 [[[1,2,3,4], [1,2,3,4]], [[1,2,3,4], [1,2,3,4]]]，形状为 (时间步, 图片, 字符)
+        let ort_outs = ort_outs[0]
+            .try_extract_tensor::<f32>()?
+            .into_dimensionality::<ndarray::Ix3>()?;
+
+        Ok(widths
+            .iter()
+            .enumerate()
+            .map(|(n, &width)| {
+                let frames = valid_frames(ort_outs.shape()[0], width, max_width);
+
+                ort_outs
+                    .slice(ndarray::s![..frames, n, ..])
+                    .rows()
+                    .into_iter()
+                    .map(log_softmax)
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// 根据字符范围，从全字符表的对数概率中提取对应字符的概率，如果 keep_log 为 true，则同时保留对数概率。
+    fn character_probability(
+        &self,
+        log_probability: Vec<Vec<f32>>,
+        charset_ranges: &[String],
+        keep_log: bool,
+    ) -> CharacterProbability {
+        let charset = &self.charset.charset;
+
+        if charset_ranges.is_empty() {
+            // 返回全部字符的概率
+            return CharacterProbability {
+                text: None,
+                charset: charset.clone(),
+                probability: log_probability
+                    .iter()
+                    .map(|row| row.iter().map(|v| v.exp()).collect())
+                    .collect(),
+                log_probability: keep_log.then_some(log_probability),
+                confidence: None,
+                unknown: Vec::new(),
+                calibration: self.calibration.clone(),
+            };
+        }
+
+        // 根据指定的字符范围，从模型输出的概率结果中提取对应字符的概率
+        // 如果字符不在字符集中，则放到 unknown 中单独返回
+        let mut probability_result_index = Vec::new();
+        let mut probability_result_charset = Vec::new();
+        let mut unknown = Vec::new();
+
+        for i in charset_ranges {
+            if let Some(v) = charset.iter().position(|v| v == i) {
+                probability_result_index.push(v);
+                probability_result_charset.push(i.clone());
+            } else {
+                unknown.push(i.clone());
+            }
+        }
+
+        // 空白必须保留，否则无法区分连续的相同字符
+        if let Some(blank) = ctc::blank_index(charset) {
+            if !probability_result_index.contains(&blank) {
+                probability_result_index.push(blank);
+                probability_result_charset.push(charset[blank].clone());
             }
         }
 
-        if word {
+        let mut probability_result = Vec::new();
+        let mut log_probability_result = Vec::new();
+
+        for item in &log_probability {
+            let log_inner_vec = probability_result_index
+                .iter()
+                .map(|&i| item[i])
+                .collect::<Vec<_>>();
+
+            probability_result.push(log_inner_vec.iter().map(|v| v.exp()).collect());
+            log_probability_result.push(log_inner_vec);
+        }
+
+        CharacterProbability {
+            text: None,
+            charset: probability_result_charset,
+            probability: probability_result,
+            log_probability: keep_log.then_some(log_probability_result),
+            confidence: None,
+            unknown,
+            calibration: self.calibration.clone(),
+        }
+    }
+
+    /// 内容识别。
+    pub fn classification<I>(&self, image: I) -> Result<String>
+    where
+        I: ImageInput,
+    {
+        Ok(self
+            .classification_with_options(image, &ClassificationOptions::default())?
+            .text)
+    }
+
+    /// 内容识别，如果 png_fix 为 true，则支持透明黑色背景的 png 图片。
+    pub fn classification_with_png_fix<I>(&self, image: I, png_fix: bool) -> Result<String>
+    where
+        I: ImageInput,
+    {
+        let options = ClassificationOptions {
+            png_fix: png_fix.into(),
+            ..Default::default()
+        };
+
+        Ok(self.classification_with_options(image, &options)?.text)
+    }
+
+    /// 内容识别，如果 filter 为 red，则表示只识别红色。
+    pub fn classification_with_filter<I, F>(&self, image: I, filter: F) -> Result<String>
+    where
+        I: ImageInput,
+        F: TryInto<ColorFilter>,
+        F::Error: Into<Error>,
+    {
+        let options = ClassificationOptions {
+            filter: Some(filter.try_into().map_err(Into::into)?),
+            ..Default::default()
+        };
+
+        Ok(self.classification_with_options(image, &options)?.text)
+    }
+
+    /// 内容识别，选项见 `ClassificationOptions`，图片解码之后依次经过 png_fix、预处理流水线、颜色过滤和浅色文字反转再识别，
+    /// 见 `ClassificationOptions::preprocess`。
+    ///
+    /// 设置了可信度阈值的时候同时返回 `Recognition`，设置了 augmentations 的时候使用测试时增强，
+    /// 这两种情况和设置了 ranges 的时候，识别结果来自概率的贪心解码，仅限于使用官方模型。
+    pub fn classification_with_options<I>(
+        &self,
+        image: I,
+        options: &ClassificationOptions,
+    ) -> Result<Classification>
+    where
+        I: ImageInput,
+    {
+        let source = image.to_image()?;
+        let png_fix = options.png_fix.resolve(&*source)?;
+        let image = options.apply(&source, png_fix)?;
+
+        if !options.needs_probability() {
+            return Ok(Classification {
+                text: self.classification_image(&image, false)?,
+                png_fix,
+                recognition: None,
+                ensemble: None,
+            });
+        }
+
+        if !options.augmentations.is_empty() {
+            // 只有需要的时候才使用相反的 png_fix 再预处理一次
+            let toggled = if options.augmentations.iter().any(|v| v.toggles_png_fix()) {
+                Some(options.apply(&source, !png_fix)?)
+            } else {
+                None
+            };
+
+            let result = self.classification_tta(
+                &image,
+                toggled.as_deref().unwrap_or(&image),
+                options.ranges.clone(),
+                &options.augmentations,
+                options.strategy,
+            )?;
+
+            return Ok(Classification {
+                text: result.text.clone(),
+                png_fix,
+                recognition: options.checks_confidence().then(|| {
+                    result.check_confidence(options.min_confidence, options.min_char_confidence)
+                }),
+                ensemble: Some(result),
+            });
+        }
+
+        let mut result = self.classification_probability_with_options(
+            &*image,
+            false,
+            None,
+            options.ranges.clone(),
+        )?;
+
+        Ok(Classification {
+            text: result.get_text().to_string(),
+            png_fix,
+            recognition: options.checks_confidence().then(|| {
+                result.check_confidence(options.min_confidence, options.min_char_confidence)
+            }),
+            ensemble: None,
+        })
+    }
+
+    /// 内容识别，并使用 normalizer 处理识别结果，形近字符按照 `set_ranges` 的字符范围替换。
+    pub fn classification_with_normalizer<I>(
+        &self,
+        image: I,
+        normalizer: &TextNormalizer,
+    ) -> Result<String>
+    where
+        I: ImageInput,
+    {
+        self.classification_with_normalizer_and_options(image, false, None, None, normalizer)
+    }
+
+    /// 内容识别，并使用 normalizer 处理识别结果，png_fix、filter 的含义同 `ClassificationOptions`。
+    /// 识别时不限定字符范围，ranges 只用于形近字符替换，为 None 则使用 set_ranges 的字符范围。
+    pub fn classification_with_normalizer_and_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        normalizer: &TextNormalizer,
+    ) -> Result<String>
+    where
+        I: ImageInput,
+    {
+        let options = ClassificationOptions {
+            png_fix: png_fix.into(),
+            filter,
+            ..Default::default()
+        };
+        let text = self.classification_with_options(image, &options)?.text;
+
+        self.normalize_text(&text, normalizer, ranges)
+    }
+
+    /// 使用 normalizer 处理识别结果，ranges 是允许的字符范围，为 None 则使用 set_ranges 的字符范围。
+    pub fn normalize_text(
+        &self,
+        text: &str,
+        normalizer: &TextNormalizer,
+        ranges: Option<CharsetRange>,
+    ) -> Result<String> {
+        Ok(match ranges {
+            Some(v) => normalizer.normalize(text, &self.calc_ranges(v)?),
+            None => normalizer.normalize(text, &self.charset_range),
+        })
+    }
+
+    /// 批量内容识别，所有图片只运行一次推理，适合大量图片的场景。
+    pub fn classification_batch<I>(&self, images: &[I]) -> Result<Vec<String>>
+    where
+        I: ImageInput,
+    {
+        self.classification_batch_with_options(images, false, None)
+    }
+
+    /// 批量内容识别，png_fix、filter 的含义同 `ClassificationOptions`。
+    /// 图片会缩放到模型所需的高度，然后填充到这一批中最宽图片的宽度，每张图片只解码自己真实宽度对应的部分。
+    pub fn classification_batch_with_options<I>(
+        &self,
+        images: &[I],
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+    ) -> Result<Vec<String>>
+    where
+        I: ImageInput,
+    {
+        let images = images
+            .iter()
+            .map(|v| load_image(v, filter.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+
+        self.classification_images(&images, png_fix)
+    }
+
+    /// 识别一张已经解码的图片。
+    fn classification_image(&self, image: &image::DynamicImage, png_fix: bool) -> Result<String> {
+        if !self.charset.word && !self.diy {
+            return self
+                .classification_images(std::slice::from_ref(image), png_fix)?
+                .pop()
+                .ok_or_else(|| {
+                    Error::Shape(ndarray::ShapeError::from_kind(
+                        ndarray::ErrorKind::IncompatibleShape,
+                    ))
+                });
+        }
+
+        let charset = &self.charset.charset;
+        let tensor = self
+            .preprocessor
+            .tensor(image, png_fix)?
+            .insert_axis(ndarray::Axis(0));
+
+        if self.charset.word {
             Ok(self.session.run(ort::inputs![tensor]?)?[1]
                 .try_extract_tensor::<i64>()?
                 .iter()
                 .map(|&v| charset[v as usize].to_string())
                 .collect::<String>())
-        } else if self.diy {
+        } else {
             // todo: 自定义模型未经测试
             let result = &self.session.run(ort::inputs![tensor]?)?[0];
             let result = result.try_extract_tensor::<u32>()?;
@@ -1758,90 +2304,116 @@ impl<'a> Ddddocr<'a> {
                 })
                 .map(|&v| charset[v as usize].to_string())
                 .collect::<String>())
-        } else {
-            let result = &self.session.run(ort::inputs![tensor]?)?[0];
-            let result = result.try_extract_tensor::<f32>()?;
-            let mut last_item = 0;
+        }
+    }
 
-            // 输入长这样 [[[1,2,3,4], [1,2,3,4], [1,2,3,4]]]
-            // 我们要获取   ^^^^^^^^^  ^^^^^^^^^  ^^^^^^^^^
-            // 最后结果 [3, 3, 3]
-            // 这是最大值的索引
-            let result = result
-                .rows()
-                .into_iter()
-                .map(|v| {
-                    // 找出数组中元素值最大的那个，然后获取他在数组中的索引
-                    v.iter()
-                        .enumerate()
-                        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                        .unwrap_or((0, &0.0))
-                        .0
-                })
-                .collect::<Vec<usize>>();
+    /// 批量识别已经解码的图片，cnn 模型和自定义模型的输出不同，所以逐张识别。
+    fn classification_images<T>(&self, images: &[T], png_fix: bool) -> Result<Vec<String>>
+    where
+        T: std::borrow::Borrow<image::DynamicImage>,
+    {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
 
-            // 过滤无效字符
-            Ok(result
+        if self.charset.word || self.diy {
+            return images
                 .iter()
-                .filter(|&&v| {
-                    if v != 0 && v != last_item {
-                        last_item = v;
-                        true
-                    } else {
-                        false
-                    }
-                })
-                .map(|&v| charset[v].to_string())
-                .collect::<String>())
+                .map(|v| self.classification_image(v.borrow(), png_fix))
+                .collect();
         }
+
+        // 与 classification_probability 走同一条路径，所以 get_text 的结果总是相同
+        Ok(self
+            .log_probability_batch(images, png_fix)?
+            .into_iter()
+            .map(|v| {
+                let mut result = self.character_probability(v, &[], false);
+                result.get_text().to_string()
+            })
+            .collect())
     }
 
-    /// 根据坐标裁剪图片，然后进行内容识别。
-    pub fn classification_bbox<I>(
-        &self,
-        image: I,
-        bbox: &Vec<BBox>,
-    ) -> anyhow::Result<Vec<(BBox, String)>>
+    /// 根据坐标裁剪图片，然后批量进行内容识别。
+    pub fn classification_bbox<I>(&self, image: I, bbox: &[BBox]) -> Result<Vec<(BBox, String)>>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
     {
-        let image = image::load_from_memory(image.as_ref())?;
+        let image = image.to_image()?;
 
-        let mut result = Vec::new();
+        let images = bbox
+            .iter()
+            .map(|i| image.crop_imm(i.x1, i.y1, i.x2 - i.x1 + 1, i.y2 - i.y1 + 1))
+            .collect::<Vec<_>>();
+
+        Ok(bbox
+            .iter()
+            .copied()
+            .zip(self.classification_images(&images, false)?)
+            .collect())
+    }
+}
+
+/// 目标检测模型。
+#[derive(Debug)]
+pub struct DetectionModel {
+    session: ort::Session,
+}
 
-        for i in bbox {
-            let mut buffer = std::io::Cursor::new(Vec::new());
+unsafe impl Send for DetectionModel {}
+unsafe impl Sync for DetectionModel {}
 
-            // todo: 使用 png 格式会不会有问题啊？
-            image::imageops::crop_imm(&image, i.x1, i.y1, i.x2 - i.x1 + 1, i.y2 - i.y1 + 1)
-                .to_image()
-                .write_to(&mut buffer, image::ImageFormat::Png)?;
+impl DetectionModel {
+    pub(crate) fn from_session(session: ort::Session) -> Result<Self> {
+        Ok(Self { session })
+    }
 
-            result.push((*i, self.classification(buffer.into_inner())?));
-        }
+    /// 从内存加载模型，需要设置线程数等选项请使用 `DdddocrBuilder`。
+    pub fn new<MODEL>(model: MODEL) -> Result<Self>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        DdddocrBuilder::new().detection(model)
+    }
 
-        Ok(result)
+    /// 从内存加载模型。
+    #[cfg(feature = "cuda")]
+    pub fn new_cuda<MODEL>(model: MODEL, device_id: i32) -> Result<Self>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        DdddocrBuilder::new()
+            .execution_provider(ExecutionProvider::cuda(device_id))
+            .detection(model)
     }
 
-    /// 根据坐标裁剪图片，然后进行内容识别。
-    pub fn classification_bbox_with_path<P>(
-        &self,
-        path: P,
-        bbox: &Vec<BBox>,
-    ) -> anyhow::Result<Vec<(BBox, String)>>
+    /// 从文件加载模型。
+    pub fn with_model<P>(model: P) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Self::new(std::fs::read(model)?)
+    }
+
+    /// 从文件加载模型。
+    #[cfg(feature = "cuda")]
+    pub fn with_model_cuda<P>(model: P, device_id: i32) -> Result<Self>
     where
         P: AsRef<std::path::Path>,
     {
-        self.classification_bbox(std::fs::read(path)?, bbox)
+        Self::new_cuda(std::fs::read(model)?, device_id)
     }
 
     /// 目标检测。
-    pub fn detection<I>(&self, image: I) -> anyhow::Result<Vec<BBox>>
+    pub fn detection<I>(&self, image: I) -> Result<Vec<BBox>>
     where
-        I: AsRef<[u8]>,
+        I: ImageInput,
     {
-        let image = image::load_from_memory(image.as_ref())?.to_rgb8();
+        let image = image.to_image()?.to_rgb8();
         let (h, w) = (image.height(), image.width());
+
+        ensure_not_empty(w, h)?;
+
         let r = (MODEL_WIDTH as f32 / h as f32).min(MODEL_WIDTH as f32 / w as f32);
         let new_w = (w as f32 * r) as u32;
         let new_h = (h as f32 * r) as u32;
@@ -1915,7 +2487,7 @@ impl<'a> Ddddocr<'a> {
         let scores: Vec<f32> = dets.iter().map(|d| d.0).collect();
         let mut order: Vec<usize> = (0..scores.len()).collect();
 
-        order.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap());
+        order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
 
         let mut keep = Vec::new();
 
@@ -1974,219 +2546,1898 @@ impl<'a> Ddddocr<'a> {
 
         Ok(result)
     }
-
-    /// 目标检测。
-    pub fn detection_with_path<P>(&self, path: P) -> anyhow::Result<Vec<BBox>>
-    where
-        P: AsRef<std::path::Path>,
-    {
-        self.detection(std::fs::read(path)?)
-    }
 }
 
-// cargo test --no-default-features --features download-binaries
-#[cfg(test)]
-mod tests {
-    use image::Pixel;
+/// 兼容旧版本的接口，内容识别和目标检测共用一个类型，用错了会返回 `Error::WrongModelKind`。
+///
+/// 新代码请直接使用 `OcrModel` 和 `DetectionModel`，用错了会编译失败。
+#[derive(Debug)]
+pub enum Ddddocr<'a> {
+    /// 内容识别模型。
+    Ocr(OcrModel<'a>),
 
-    use super::*;
+    /// 目标检测模型。
+    Detection(DetectionModel),
+}
 
-    fn read_image(path: &str) -> Vec<u8> {
-        std::fs::read(path).unwrap_or_else(|e| {
-            panic!("failed to read image {}: {}", path, e);
-        })
+impl<'a> From<OcrModel<'a>> for Ddddocr<'a> {
+    fn from(value: OcrModel<'a>) -> Self {
+        Ddddocr::Ocr(value)
     }
+}
 
-    #[test]
-    fn classification_probability() {
-        let mut ddddocr = ddddocr_classification().unwrap();
+impl From<DetectionModel> for Ddddocr<'_> {
+    fn from(value: DetectionModel) -> Self {
+        Ddddocr::Detection(value)
+    }
+}
 
-        // CharsetRange::LowercaseUppercase 大写字母和小写字母
-        ddddocr.set_ranges(3);
+impl<'a> Ddddocr<'a> {
+    /// 设置运行库的路径，见 `set_onnxruntime_path`。
+    #[cfg(feature = "load-dynamic")]
+    pub fn set_onnxruntime_path<P>(path: P) -> Result<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        set_onnxruntime_path(path)
+    }
+
+    /// 从内存加载模型和字符集，只能使用内容识别。
+    pub fn new<MODEL>(model: MODEL, charset: Charset) -> Result<Self>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        OcrModel::new(model, charset).map(Ddddocr::Ocr)
+    }
+
+    /// 从内存加载模型和字符集，只能使用内容识别。
+    pub fn new_ref<MODEL>(model: MODEL, charset: &'a Charset) -> Result<Self>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        OcrModel::new_ref(model, charset).map(Ddddocr::Ocr)
+    }
+
+    /// 从内存加载模型和字符集，只能使用内容识别。
+    #[cfg(feature = "cuda")]
+    pub fn new_cuda<MODEL>(model: MODEL, charset: Charset, device_id: i32) -> Result<Self>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        OcrModel::new_cuda(model, charset, device_id).map(Ddddocr::Ocr)
+    }
+
+    /// 从内存加载模型和字符集，只能使用内容识别。
+    #[cfg(feature = "cuda")]
+    pub fn new_cuda_ref<MODEL>(model: MODEL, charset: &'a Charset, device_id: i32) -> Result<Self>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        OcrModel::new_cuda_ref(model, charset, device_id).map(Ddddocr::Ocr)
+    }
+
+    /// 从内存加载模型，只能使用目标检测。
+    pub fn new_model<MODEL>(model: MODEL) -> Result<Self>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        DetectionModel::new(model).map(Ddddocr::Detection)
+    }
+
+    /// 从内存加载模型，只能使用目标检测。
+    #[cfg(feature = "cuda")]
+    pub fn new_model_cuda<MODEL>(model: MODEL, device_id: i32) -> Result<Self>
+    where
+        MODEL: AsRef<[u8]>,
+    {
+        DetectionModel::new_cuda(model, device_id).map(Ddddocr::Detection)
+    }
+
+    /// 从文件加载模型和字符集，只能使用内容识别。
+    pub fn with_model_charset<PATH1, PATH2>(model: PATH1, charset: PATH2) -> Result<Self>
+    where
+        PATH1: AsRef<std::path::Path>,
+        PATH2: AsRef<std::path::Path>,
+    {
+        OcrModel::with_model_charset(model, charset).map(Ddddocr::Ocr)
+    }
+
+    /// 从文件加载模型和字符集，只能使用内容识别。
+    #[cfg(feature = "cuda")]
+    pub fn with_model_charset_cuda<PATH1, PATH2>(
+        model: PATH1,
+        charset: PATH2,
+        device_id: i32,
+    ) -> Result<Self>
+    where
+        PATH1: AsRef<std::path::Path>,
+        PATH2: AsRef<std::path::Path>,
+    {
+        OcrModel::with_model_charset_cuda(model, charset, device_id).map(Ddddocr::Ocr)
+    }
+
+    /// 从文件加载模型，只能使用目标检测。
+    pub fn with_model<P>(model: P) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        DetectionModel::with_model(model).map(Ddddocr::Detection)
+    }
+
+    /// 从文件加载模型，只能使用目标检测。
+    #[cfg(feature = "cuda")]
+    pub fn with_model_cuda<P>(model: P, device_id: i32) -> Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        DetectionModel::with_model_cuda(model, device_id).map(Ddddocr::Detection)
+    }
+
+    /// 获取内容识别模型。
+    pub fn as_ocr(&self) -> Result<&OcrModel<'a>> {
+        match self {
+            Ddddocr::Ocr(v) => Ok(v),
+            Ddddocr::Detection(_) => Err(Error::WrongModelKind {
+                expected: ModelKind::Ocr,
+            }),
+        }
+    }
+
+    /// 获取内容识别模型。
+    pub fn as_ocr_mut(&mut self) -> Result<&mut OcrModel<'a>> {
+        match self {
+            Ddddocr::Ocr(v) => Ok(v),
+            Ddddocr::Detection(_) => Err(Error::WrongModelKind {
+                expected: ModelKind::Ocr,
+            }),
+        }
+    }
+
+    /// 获取目标检测模型。
+    pub fn as_detection(&self) -> Result<&DetectionModel> {
+        match self {
+            Ddddocr::Detection(v) => Ok(v),
+            Ddddocr::Ocr(_) => Err(Error::WrongModelKind {
+                expected: ModelKind::Detection,
+            }),
+        }
+    }
+
+    /// 根据给定 ranges 计算字符集范围，字符串总是当作自定义字符集，例如 `"digit + upper"`
+    /// 表示这几个字符本身，表达式需要先解析，例如 `"digit + upper".parse::<CharsetRange>()?`。
+    pub fn calc_ranges<R>(&self, ranges: R) -> Result<Vec<String>>
+    where
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
+    {
+        self.as_ocr()?.calc_ranges(ranges)
+    }
+
+    /// 限定 classification_probability 的字符范围，只能使用内容识别，
+    /// ranges 的含义同 `calc_ranges`，字符串不会当作表达式解析。
+    pub fn set_ranges<R>(&mut self, ranges: R) -> Result<()>
+    where
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
+    {
+        self.as_ocr_mut()?.set_ranges(ranges)
+    }
+
+    /// 见 `OcrModel::set_calibration`。
+    pub fn set_calibration(&mut self, calibration: Option<Calibration>) -> Result<()> {
+        self.as_ocr_mut()?.set_calibration(calibration);
+
+        Ok(())
+    }
+
+    /// 见 `OcrModel::preprocessor`。
+    pub fn preprocessor(&self) -> Result<&Preprocessor> {
+        Ok(self.as_ocr()?.preprocessor())
+    }
+
+    /// 见 `OcrModel::set_normalization`。
+    pub fn set_normalization(&mut self, normalization: Normalization) -> Result<()> {
+        self.as_ocr_mut()?.set_normalization(normalization)
+    }
+
+    /// 见 `OcrModel::fit_calibration`。
+    pub fn fit_calibration<P>(&self, dir: P, method: CalibrationMethod) -> Result<Calibration>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.as_ocr()?.fit_calibration(dir, method)
+    }
+
+    /// 见 `OcrModel::classification_probability`。
+    pub fn classification_probability<I>(&self, image: I) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_probability(image)
+    }
+
+    /// 见 `OcrModel::classification_probability_with_png_fix`。
+    pub fn classification_probability_with_png_fix<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_probability_with_png_fix(image, png_fix)
+    }
+
+    /// 见 `OcrModel::classification_probability_with_filter`。
+    pub fn classification_probability_with_filter<I, F>(
+        &self,
+        image: I,
+        filter: F,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+        F: TryInto<ColorFilter>,
+        F::Error: Into<Error>,
+    {
+        self.as_ocr()?
+            .classification_probability_with_filter(image, filter)
+    }
+
+    /// 见 `OcrModel::classification_probability_with_ranges`。
+    pub fn classification_probability_with_ranges<I, R>(
+        &self,
+        image: I,
+        ranges: R,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
+    {
+        self.as_ocr()?
+            .classification_probability_with_ranges(image, ranges)
+    }
+
+    /// 见 `OcrModel::classification_probability_with_png_fix_and_ranges`。
+    pub fn classification_probability_with_png_fix_and_ranges<I, R>(
+        &self,
+        image: I,
+        png_fix: bool,
+        ranges: R,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
+    {
+        self.as_ocr()?
+            .classification_probability_with_png_fix_and_ranges(image, png_fix, ranges)
+    }
+
+    /// 见 `OcrModel::classification_probability_with_filter_and_ranges`。
+    pub fn classification_probability_with_filter_and_ranges<I, F, R>(
+        &self,
+        image: I,
+        filter: F,
+        ranges: R,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+        F: TryInto<ColorFilter>,
+        F::Error: Into<Error>,
+        R: TryInto<CharsetRange>,
+        R::Error: Into<Error>,
+    {
+        self.as_ocr()?
+            .classification_probability_with_filter_and_ranges(image, filter, ranges)
+    }
+
+    /// 见 `OcrModel::classification_probability_with_options`。
+    pub fn classification_probability_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_probability_with_options(image, png_fix, filter, ranges)
+    }
+
+    /// 内容识别，返回全字符表的概率和对数概率，仅限于使用官方模型。
+    pub fn classification_log_probability<I>(&self, image: I) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_log_probability(image)
+    }
+
+    /// 内容识别，返回全字符表的概率和对数概率，参数的含义同 `OcrModel::classification_log_probability_with_options`。
+    pub fn classification_log_probability_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_log_probability_with_options(image, png_fix, filter, ranges)
+    }
+
+    /// 不区分大小写的内容识别，仅限于使用官方模型。
+    pub fn classification_probability_case_insensitive<I>(
+        &self,
+        image: I,
+        case: LetterCase,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_probability_case_insensitive(image, case)
+    }
+
+    /// 见 `OcrModel::classification_probability_case_insensitive_with_options`。
+    pub fn classification_probability_case_insensitive_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        case: LetterCase,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_probability_case_insensitive_with_options(
+                image, png_fix, filter, ranges, case,
+            )
+    }
+
+    /// 见 `OcrModel::classification_log_probability_case_insensitive_with_options`。
+    pub fn classification_log_probability_case_insensitive_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        case: LetterCase,
+    ) -> Result<CharacterProbability>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_log_probability_case_insensitive_with_options(
+                image, png_fix, filter, ranges, case,
+            )
+    }
+
+    /// 内容识别。
+    pub fn classification<I>(&self, image: I) -> Result<String>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification(image)
+    }
+
+    /// 内容识别，如果 png_fix 为 true，则支持透明黑色背景的 png 图片。
+    pub fn classification_with_png_fix<I>(&self, image: I, png_fix: bool) -> Result<String>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_with_png_fix(image, png_fix)
+    }
+
+    /// 内容识别，如果 filter 为 red，则表示只识别红色。
+    pub fn classification_with_filter<I, F>(&self, image: I, filter: F) -> Result<String>
+    where
+        I: ImageInput,
+        F: TryInto<ColorFilter>,
+        F::Error: Into<Error>,
+    {
+        self.as_ocr()?.classification_with_filter(image, filter)
+    }
+
+    /// 见 `OcrModel::classification_with_options`。
+    pub fn classification_with_options<I>(
+        &self,
+        image: I,
+        options: &ClassificationOptions,
+    ) -> Result<Classification>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_with_options(image, options)
+    }
+
+    /// 内容识别，并使用 normalizer 处理识别结果。
+    pub fn classification_with_normalizer<I>(
+        &self,
+        image: I,
+        normalizer: &TextNormalizer,
+    ) -> Result<String>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_with_normalizer(image, normalizer)
+    }
+
+    /// 见 `OcrModel::classification_with_normalizer_and_options`。
+    pub fn classification_with_normalizer_and_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        normalizer: &TextNormalizer,
+    ) -> Result<String>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_with_normalizer_and_options(image, png_fix, filter, ranges, normalizer)
+    }
+
+    /// 见 `OcrModel::normalize_text`。
+    pub fn normalize_text(
+        &self,
+        text: &str,
+        normalizer: &TextNormalizer,
+        ranges: Option<CharsetRange>,
+    ) -> Result<String> {
+        self.as_ocr()?.normalize_text(text, normalizer, ranges)
+    }
+
+    /// 批量内容识别，所有图片只运行一次推理，适合大量图片的场景。
+    pub fn classification_batch<I>(&self, images: &[I]) -> Result<Vec<String>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_batch(images)
+    }
+
+    /// 内容识别，返回满足格式的概率最高的结果，仅限于使用官方模型。
+    pub fn classification_with_pattern<I, P>(
+        &self,
+        image: I,
+        pattern: P,
+    ) -> Result<Option<Candidate>>
+    where
+        I: ImageInput,
+        P: TryInto<Pattern>,
+        P::Error: Into<Error>,
+    {
+        self.as_ocr()?.classification_with_pattern(image, pattern)
+    }
+
+    /// 内容识别，返回满足格式的概率最高的结果，参数的含义同 `OcrModel::classification_with_pattern_and_options`。
+    pub fn classification_with_pattern_and_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        pattern: Pattern,
+        beam_width: usize,
+    ) -> Result<Option<Candidate>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_with_pattern_and_options(
+            image, png_fix, filter, ranges, pattern, beam_width,
+        )
+    }
+
+    /// 内容识别，返回词典中概率最高的词语，仅限于使用官方模型。
+    pub fn classification_with_lexicon<I>(
+        &self,
+        image: I,
+        lexicon: &Lexicon,
+    ) -> Result<Option<LexiconMatch>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_with_lexicon(image, lexicon)
+    }
+
+    /// 内容识别，返回词典中概率最高的词语，参数的含义同 `OcrModel::classification_with_lexicon_and_options`。
+    pub fn classification_with_lexicon_and_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        lexicon: &Lexicon,
+    ) -> Result<Option<LexiconMatch>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_with_lexicon_and_options(image, png_fix, filter, ranges, lexicon)
+    }
+
+    /// 内容识别，返回每个字符的可信度和位置，仅限于使用官方模型。
+    pub fn classification_aligned<I>(&self, image: I) -> Result<Vec<AlignedCharacter>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_aligned(image)
+    }
+
+    /// 内容识别，返回每个字符的可信度和位置，参数的含义同 `OcrModel::classification_aligned_with_options`。
+    pub fn classification_aligned_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+    ) -> Result<Vec<AlignedCharacter>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_aligned_with_options(image, png_fix, filter, ranges)
+    }
+
+    /// 内容识别，使用 CTC 束搜索返回概率最高的 top_n 个候选结果，仅限于使用官方模型。
+    pub fn classification_candidates<I>(
+        &self,
+        image: I,
+        beam_width: usize,
+        top_n: usize,
+    ) -> Result<Vec<Candidate>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_candidates(image, beam_width, top_n)
+    }
+
+    /// 内容识别，使用 CTC 束搜索返回概率最高的 top_n 个候选结果，参数的含义同 `OcrModel::classification_candidates_with_options`。
+    pub fn classification_candidates_with_options<I>(
+        &self,
+        image: I,
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+        beam_width: usize,
+        top_n: usize,
+    ) -> Result<Vec<Candidate>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_candidates_with_options(
+            image, png_fix, filter, ranges, beam_width, top_n,
+        )
+    }
+
+    /// 批量内容识别，png_fix、filter 的含义同 `ClassificationOptions`。
+    pub fn classification_batch_with_options<I>(
+        &self,
+        images: &[I],
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+    ) -> Result<Vec<String>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_batch_with_options(images, png_fix, filter)
+    }
+
+    /// 批量内容识别，返回每张图片全字符表的概率，仅限于使用官方模型。
+    pub fn classification_probability_batch<I>(
+        &self,
+        images: &[I],
+    ) -> Result<Vec<CharacterProbability>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_probability_batch(images)
+    }
+
+    /// 批量内容识别，返回每张图片全字符表的概率，参数的含义同 `classification_probability_with_options`。
+    pub fn classification_probability_batch_with_options<I>(
+        &self,
+        images: &[I],
+        png_fix: bool,
+        filter: Option<ColorFilter>,
+        ranges: Option<CharsetRange>,
+    ) -> Result<Vec<CharacterProbability>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?
+            .classification_probability_batch_with_options(images, png_fix, filter, ranges)
+    }
+
+    /// 根据坐标裁剪图片，然后进行内容识别。
+    pub fn classification_bbox<I>(&self, image: I, bbox: &[BBox]) -> Result<Vec<(BBox, String)>>
+    where
+        I: ImageInput,
+    {
+        self.as_ocr()?.classification_bbox(image, bbox)
+    }
+
+    /// 目标检测。
+    pub fn detection<I>(&self, image: I) -> Result<Vec<BBox>>
+    where
+        I: ImageInput,
+    {
+        self.as_detection()?.detection(image)
+    }
+}
+
+// cargo test --no-default-features --features download-binaries
+#[cfg(test)]
+mod tests {
+    use image::Pixel;
+
+    use super::*;
+
+    fn read_image(path: &str) -> Vec<u8> {
+        std::fs::read(path).unwrap_or_else(|e| {
+            panic!("failed to read image {}: {}", path, e);
+        })
+    }
+
+    #[test]
+    fn classification_probability() {
+        let mut ddddocr = ddddocr_classification().unwrap();
+
+        // CharsetRange::LowercaseUppercase 大写字母和小写字母
+        ddddocr.set_ranges(3).unwrap();
+
+        let mut result = ddddocr
+            .classification_probability(read_image("image/3.png"))
+            .unwrap();
+
+        println!("识别结果: {}", result.get_text());
+        println!("识别可信度: {}", result.get_confidence());
+
+        // 哦呀，看来数据有点儿太多了，小心卡死哦！
+        // println!("概率: {}", result.json());
+    }
+
+    #[test]
+    fn classification_filter() {
+        let ddddocr = ddddocr_classification().unwrap();
+
+        println!(
+            "{}",
+            ddddocr
+                .classification_with_filter(read_image("image/4.png"), "green")
+                .unwrap()
+        );
+
+        println!(
+            "{}",
+            ddddocr
+                .classification_with_filter(
+                    read_image("image/4.png"),
+                    [((40, 50, 50), (80, 255, 255))]
+                )
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn classification() {
+        let ddddocr = ddddocr_classification().unwrap();
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/1.png")).unwrap()
+        );
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/2.png")).unwrap()
+        );
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/3.png")).unwrap()
+        );
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/4.png")).unwrap()
+        );
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/su.png")).unwrap()
+        );
+    }
+
+    #[test]
+    fn classification_batch() {
+        let ddddocr = ddddocr_classification().unwrap();
+        let images = ["image/1.png", "image/2.png", "image/3.png", "image/4.png"].map(read_image);
+
+        let result = ddddocr.classification_batch(&images).unwrap();
+
+        for (image, text) in images.iter().zip(&result) {
+            assert_eq!(&ddddocr.classification(image).unwrap(), text);
+        }
+
+        let mut probability = ddddocr.classification_probability_batch(&images).unwrap();
+
+        // 两条路径使用同一个解码器
+        for (i, text) in probability.iter_mut().zip(&result) {
+            assert_eq!(i.get_text(), text);
+        }
+
+        assert!(ddddocr
+            .classification_batch(&Vec::<Vec<u8>>::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn classification_old() {
+        let ddddocr = ddddocr_classification_old().unwrap();
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/1.png")).unwrap()
+        );
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/2.png")).unwrap()
+        );
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/3.png")).unwrap()
+        );
+
+        println!(
+            "{}",
+            ddddocr.classification(read_image("image/4.png")).unwrap()
+        );
+    }
+
+    #[test]
+    fn classification_bbox() {
+        let input = read_image("image/6.jpg");
+        let ddddocr = ddddocr_detection().unwrap();
+        let result = ddddocr.detection(input.clone()).unwrap();
+        let ddddocr = ddddocr_classification().unwrap();
+        let result = ddddocr.classification_bbox(input, &result).unwrap();
+
+        println!("{:?}", result);
+    }
+
+    #[test]
+    fn wrong_model_kind() {
+        let ddddocr = Ddddocr::from(ddddocr_detection().unwrap());
+
+        assert!(matches!(
+            ddddocr.classification(read_image("image/1.png")),
+            Err(Error::WrongModelKind {
+                expected: ModelKind::Ocr
+            })
+        ));
+    }
+
+    #[test]
+    fn detection() {
+        let ddddocr = ddddocr_detection().unwrap();
+        let input = read_image("image/5.jpg");
+        let result = ddddocr.detection(input.clone()).unwrap();
+
+        println!("{:?}", result);
+
+        // 绘制红框
+        let mut image = image::load_from_memory(&input).unwrap().to_rgb8();
+
+        for v in result {
+            for i in v.x1..=v.x2 {
+                image[(i, v.y1)] = *image::Rgb::from_slice(&[237, 28, 36]);
+            }
+
+            for i in v.x1..=v.x2 {
+                image[(i, v.y2)] = *image::Rgb::from_slice(&[237, 28, 36]);
+            }
+
+            for i in v.y1..=v.y2 {
+                image[(v.x1, i)] = *image::Rgb::from_slice(&[237, 28, 36]);
+            }
+
+            for i in v.y1..=v.y2 {
+                image[(v.x2, i)] = *image::Rgb::from_slice(&[237, 28, 36]);
+            }
+        }
+
+        image.save("./output1.jpg").unwrap();
+
+        let input = read_image("image/6.jpg");
+        let result = ddddocr.detection(input.clone()).unwrap();
+
+        println!("{:?}", result);
+
+        // 绘制红框
+        let mut image = image::load_from_memory(&input).unwrap().to_rgb8();
+
+        for v in result {
+            for i in v.x1..=v.x2 {
+                image[(i, v.y1)] = *image::Rgb::from_slice(&[237, 28, 36]);
+            }
+
+            for i in v.x1..=v.x2 {
+                image[(i, v.y2)] = *image::Rgb::from_slice(&[237, 28, 36]);
+            }
+
+            for i in v.y1..=v.y2 {
+                image[(v.x1, i)] = *image::Rgb::from_slice(&[237, 28, 36]);
+            }
+
+            for i in v.y1..=v.y2 {
+                image[(v.x2, i)] = *image::Rgb::from_slice(&[237, 28, 36]);
+            }
+        }
+
+        image.save("./output2.jpg").unwrap();
+    }
+
+    #[test]
+    fn slide_match() {
+        let result =
+            crate::slide_match(read_image("image/hk.png"), read_image("image/bg.png")).unwrap();
+
+        println!("{:?}", result);
+
+        let result =
+            crate::slide_match(read_image("image/a.png"), read_image("image/b.png")).unwrap();
+
+        println!("{:?}", result);
+
+        let result =
+            crate::simple_slide_match(read_image("image/a.png"), read_image("image/b.png"))
+                .unwrap();
+
+        println!("{:?}", result);
+    }
+
+    #[test]
+    fn comparison_match() {
+        let result =
+            crate::slide_comparison(read_image("image/c.jpg"), read_image("image/d.jpg")).unwrap();
+        println!("{:?}", result);
+    }
+
+    #[test]
+    fn greedy_decode() {
+        let charset = ["", "a", "b"].map(String::from);
+
+        // 连续的相同字符合并，空白隔开的相同字符保留
+        let probability = vec![
+            vec![0.1, 0.8, 0.1],
+            vec![0.1, 0.8, 0.1],
+            vec![0.8, 0.1, 0.1],
+            vec![0.1, 0.8, 0.1],
+            vec![0.1, 0.1, 0.8],
+            vec![0.1, 0.1, 0.8],
+        ];
+
+        assert_eq!(ctc_greedy_decode(&probability, &charset), "aab");
+
+        let mut result = CharacterProbability::from_probability(&charset, probability);
+
+        assert_eq!(result.get_text(), "aab");
+
+        // 很大的输出也不会溢出
+        let row = log_softmax(ndarray::arr1(&[1000.0, 1001.0, 999.0]).view());
+
+        assert!(row.iter().all(|v| v.is_finite()));
+        assert!((row.iter().map(|v| v.exp()).sum::<f32>() - 1.0).abs() < 1e-6);
+        assert_eq!(ctc_greedy_decode(&[row], &charset), "a");
+    }
+
+    #[test]
+    fn renormalize() {
+        let mut result = CharacterProbability {
+            log_probability: Some(vec![
+                vec![0.2f32.ln(), 0.1f32.ln(), 0.1f32.ln()],
+                vec![0.05f32.ln(), 0.05f32.ln(), 0.4f32.ln()],
+            ]),
+            ..CharacterProbability::from_probability(
+                &["a", "b", ""],
+                vec![vec![0.2, 0.1, 0.1], vec![0.05, 0.05, 0.4]],
+            )
+        };
+
+        // 限定字符范围后概率之和不是 1，可信度偏低
+        assert!((result.get_confidence() - 0.3).abs() < 1e-6);
+
+        result.renormalize();
+
+        for row in &result.probability {
+            assert!((row.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        }
+
+        for row in result.log_probability.as_ref().unwrap() {
+            assert!((row.iter().map(|v| v.exp()).sum::<f32>() - 1.0).abs() < 1e-6);
+        }
+
+        assert!((result.get_confidence() - 0.65).abs() < 1e-6);
+        assert_eq!(result.get_text(), "a");
+    }
+
+    #[test]
+    fn charset_range() {
+        let charset = ["", "一", "二", "a", "0", "1", "ア"].map(String::from);
+        let characters = |v: &str| v.parse::<CharsetRange>().unwrap().characters(&charset);
+
+        assert_eq!(
+            characters("cjk + digit - '0'"),
+            ["一", "二", "1", "2", "3", "4", "5", "6", "7", "8", "9"]
+        );
+        assert_eq!(characters("all - (digit | lower)"), ["一", "二", "ア"]);
+        assert_eq!(characters("U+4E00..u+4e01"), ["一"]);
+        assert_eq!(characters("KATAKANA"), ["ア"]);
+        assert_eq!(characters("\"+-\" + U+4E8C + U+3D"), ["+", "-", "二"]);
+        assert_eq!(characters(" 3 ").len(), 52);
+
+        let range = CharsetRange::All - (CharsetRange::Digit + CharsetRange::Lowercase);
+        assert_eq!(range.characters(&charset), ["一", "二", "ア"]);
+
+        // 字符串转换得到的是自定义字符集，不会解析表达式
+        assert_eq!(
+            CharsetRange::from("digit").characters(&charset),
+            ["d", "i", "g", "i", "t"]
+        );
+
+        for i in [
+            "",
+            "cjk +",
+            "foo",
+            "(digit",
+            "digit)",
+            "'abc",
+            "''",
+            "U+110000",
+            "U+9FFF..U+4E00",
+            "0123456789+-x/=",
+        ] {
+            assert!(
+                matches!(
+                    i.parse::<CharsetRange>(),
+                    Err(Error::InvalidCharsetExpression(_))
+                ),
+                "{}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn ensemble() {
+        // 两个模型的字符集顺序不同，b 只在第一个模型中
+        let a = CharacterProbability::from_probability(
+            &["", "a", "b"],
+            vec![vec![0.4, 0.5, 0.1], vec![0.9, 0.0, 0.1]],
+        );
+        let b = CharacterProbability::from_probability(
+            &["a", ""],
+            vec![vec![0.2, 0.8], vec![0.1, 0.9]],
+        );
+
+        let mut fused = ensemble::fuse(&[a.clone(), b.clone()]).unwrap();
+
+        assert_eq!(fused.charset, ["", "a"]);
+        assert!((fused.probability[0][0] - 0.6).abs() < 1e-6);
+        assert!((fused.probability[0][1] - 0.35).abs() < 1e-6);
+        assert_eq!(fused.get_text(), "");
+
+        // 时间步数量不同
+        let c = CharacterProbability::from_probability(&["", "a"], vec![vec![0.1, 0.9]]);
+        assert!(ensemble::fuse(&[a, c]).is_none());
+
+        assert_eq!(
+            "Fusion".parse::<EnsembleStrategy>().unwrap(),
+            EnsembleStrategy::Fusion
+        );
+        assert!("average".parse::<EnsembleStrategy>().is_err());
+
+        let options = ClassificationOptions {
+            augmentations: DEFAULT_AUGMENTATIONS.to_vec(),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            Ensemble::new([]).classification_with_options(
+                read_image("image/3.png"),
+                &ClassificationOptions::default(),
+                EnsembleStrategy::Vote
+            ),
+            Err(Error::InvalidOption(_))
+        ));
+        assert!(matches!(
+            Ensemble::new([]).classification_with_options(
+                read_image("image/3.png"),
+                &options,
+                EnsembleStrategy::Vote
+            ),
+            Err(Error::InvalidOption(_))
+        ));
+    }
+
+    #[test]
+    fn ensemble_models() {
+        let new = ddddocr_classification().unwrap();
+        let old = ddddocr_classification_old().unwrap();
+        let ensemble = Ensemble::new([&new, &old]);
+
+        // 字符范围在每个模型的字符集上分别计算
+        let options = ClassificationOptions {
+            ranges: Some(CharsetRange::Digit),
+            min_confidence: Some(0.0),
+            ..Default::default()
+        };
+        let result = ensemble
+            .classification_with_options(
+                read_image("image/3.png"),
+                &options,
+                EnsembleStrategy::Vote,
+            )
+            .unwrap();
+        let combined = result.ensemble.unwrap();
+
+        assert_eq!(result.text, combined.text);
+        assert!(result.text.chars().all(|v| v.is_ascii_digit()));
+        assert_eq!(combined.members.len(), 2);
+        assert_eq!(
+            result.recognition.unwrap().status,
+            RecognitionStatus::Accepted
+        );
+    }
+
+    #[test]
+    fn preprocessor() {
+        let charset = |channel: i64| Charset {
+            word: false,
+            image: [-1, 16],
+            channel,
+            charset: Vec::new(),
+        };
+
+        let red = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            8,
+            4,
+            image::Rgb([255, 0, 0]),
+        ));
+        let white = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+            2,
+            4,
+            image::Rgb([255, 255, 255]),
+        ));
+
+        // 3 通道，每个通道使用各自的参数
+        let preprocessor = Preprocessor::new(&charset(3), Normalization::imagenet(3)).unwrap();
+        let tensor = preprocessor.tensor(&red, false).unwrap();
+
+        assert_eq!(tensor.shape(), [3, 16, 32]);
+        assert!(tensor
+            .index_axis(ndarray::Axis(0), 0)
+            .iter()
+            .all(|v| (v - (1.0 - 0.485) / 0.229).abs() < 1e-3));
+        assert!(tensor
+            .index_axis(ndarray::Axis(0), 1)
+            .iter()
+            .all(|v| (v + 0.456 / 0.224).abs() < 1e-3));
+        assert!(tensor
+            .index_axis(ndarray::Axis(0), 2)
+            .iter()
+            .all(|v| (v + 0.406 / 0.225).abs() < 1e-3));
+
+        // 单通道，宽度不足的部分重复最右边的一列
+        let preprocessor = Preprocessor::new(&charset(1), Normalization::ddddocr(1)).unwrap();
+        let (batch, widths) = preprocessor.batch(&[&red, &white], false).unwrap();
+
+        assert_eq!(batch.shape(), [2, 1, 16, 32]);
+        assert_eq!(widths, [32, 8]);
+        assert!(batch
+            .slice(ndarray::s![1, 0, .., ..])
+            .iter()
+            .all(|v| (v - 1.0).abs() < 1e-3));
+
+        assert!(Preprocessor::new(&charset(1), Normalization::imagenet(3)).is_err());
+        assert!(Preprocessor::new(&charset(4), Normalization::ddddocr(4)).is_err());
+
+        // 很宽的图片不会溢出，缩放后的宽度超出范围时返回错误
+        assert_eq!(
+            preprocessor.size(u32::MAX / 4, 8).unwrap(),
+            (u32::MAX / 4 * 2, 16)
+        );
+        assert!(matches!(
+            preprocessor.size(u32::MAX, 1),
+            Err(Error::InvalidOption(_))
+        ));
+        assert!(matches!(
+            preprocessor.size(0, 1),
+            Err(Error::ImageTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn augmentation() {
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(10, 6, |x, _| {
+            if x < 5 {
+                image::Rgba([100, 100, 100, 255])
+            } else {
+                image::Rgba([150, 150, 150, 0])
+            }
+        }));
+
+        // 填充和裁剪之后缩放回原来的大小
+        let padded = Augmentation::Pad(2).apply(&image, (10, 6));
+        assert_eq!((padded.width(), padded.height()), (10, 6));
+        assert_ne!(padded, image);
+
+        let cropped = Augmentation::Crop(2).apply(&image, (10, 6));
+        assert_eq!((cropped.width(), cropped.height()), (10, 6));
+        assert_eq!(Augmentation::Crop(3).apply(&image, (10, 6)), image);
+
+        let resized = Augmentation::Resize(ResizeFilter::Nearest).apply(&image, (20, 12));
+        assert_eq!((resized.width(), resized.height()), (20, 12));
+
+        let contrast = Augmentation::Contrast.apply(&image, (0, 0)).to_rgba8();
+        assert_eq!(contrast[(0, 0)], image::Rgba([0, 0, 0, 255]));
+        assert_eq!(contrast[(9, 0)], image::Rgba([255, 255, 255, 0]));
+
+        let inverted = Augmentation::Invert.apply(&image, (0, 0)).to_rgba8();
+        assert_eq!(inverted[(0, 0)], image::Rgba([155, 155, 155, 255]));
+
+        assert!(Augmentation::PngFix.toggles_png_fix());
+        assert!(!Augmentation::Original.toggles_png_fix());
+
+        let augmentations = serde_json::from_str::<Vec<Augmentation>>(
+            r#"["original", {"resize": "catmullRom"}, {"pad": 4}, "pngFix"]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            augmentations,
+            [
+                Augmentation::Original,
+                Augmentation::Resize(ResizeFilter::CatmullRom),
+                Augmentation::Pad(4),
+                Augmentation::PngFix,
+            ]
+        );
+    }
+
+    #[test]
+    fn tta_fusion() {
+        let charset =
+            serde_json::from_str::<Charset>(include_str!("../model/common.json")).unwrap();
+        let preprocessor = Preprocessor::new(&charset, Normalization::ddddocr(1)).unwrap();
+        let image = image::load_from_memory(&read_image("image/3.png")).unwrap();
+        let size = preprocessor.size(image.width(), image.height()).unwrap();
+
+        // 所有默认的变体缩放后的大小都相同，所以时间步数量相同
+        for v in DEFAULT_AUGMENTATIONS {
+            let image = v.apply(&image, size);
+
+            assert_eq!(
+                preprocessor.size(image.width(), image.height()).unwrap(),
+                size,
+                "{:?}",
+                v
+            );
+        }
+
+        let options = ClassificationOptions {
+            augmentations: DEFAULT_AUGMENTATIONS.to_vec(),
+            strategy: EnsembleStrategy::Fusion,
+            ..Default::default()
+        };
+
+        assert!(options.needs_probability());
+        assert!(!options.checks_confidence());
+
+        let ddddocr = ddddocr_classification().unwrap();
+        let result = ddddocr
+            .classification_with_options(read_image("image/3.png"), &options)
+            .unwrap();
+        let ensemble = result.ensemble.unwrap();
+
+        assert_eq!(result.text, ensemble.text);
+        assert!(result.recognition.is_none());
+        assert_eq!(ensemble.strategy, EnsembleStrategy::Fusion);
+        assert_eq!(ensemble.members.len(), DEFAULT_AUGMENTATIONS.len());
+    }
+
+    #[test]
+    fn pipeline() {
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(10, 6, |x, y| {
+            if (x, y) == (7, 3) {
+                image::Rgba([0, 0, 0, 255])
+            } else if x < 5 {
+                image::Rgba([40, 40, 40, 255])
+            } else {
+                image::Rgba([200, 200, 200, 255])
+            }
+        }));
+
+        let pipeline = "[\"grayscale\", {\"close\": 1}, \"otsu\", {\"pad\": 2}]"
+            .parse::<Pipeline>()
+            .unwrap();
+        assert_eq!(pipeline.len(), 4);
+
+        let result = pipeline.input(&image).to_image().unwrap().to_rgba8();
+        assert_eq!((result.width(), result.height()), (14, 10));
+        assert_eq!(result[(0, 0)], image::Rgba([255, 255, 255, 255]));
+        assert_eq!(result[(3, 3)], image::Rgba([0, 0, 0, 255]));
+        // 闭运算去掉了白色背景上的黑点
+        assert_eq!(result[(9, 5)], image::Rgba([255, 255, 255, 255]));
+
+        let threshold = BuiltinStage::Threshold(100).apply(image.clone()).unwrap();
+        assert_eq!(threshold.to_luma8()[(0, 0)], image::Luma([0]));
+        assert_eq!(threshold.to_luma8()[(9, 0)], image::Luma([255]));
+
+        // 透明部分当作白色
+        let transparent = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            2,
+            2,
+            image::Rgba([0; 4]),
+        ));
+        let gray = BuiltinStage::Grayscale.apply(transparent).unwrap();
+        assert_eq!(gray.to_luma8()[(0, 0)], image::Luma([255]));
+
+        let inverted = BuiltinStage::Invert.apply(image.clone()).unwrap();
+        assert_eq!(
+            inverted.to_rgba8()[(0, 0)],
+            image::Rgba([215, 215, 215, 255])
+        );
+
+        assert!(BuiltinStage::Gaussian(0.0).apply(image.clone()).is_err());
+        assert!(BuiltinStage::AdaptiveThreshold(2)
+            .apply(image.clone())
+            .is_ok());
+        assert!(BuiltinStage::Open(1).apply(image.clone()).is_ok());
+        assert!(BuiltinStage::Median(1).apply(image.clone()).is_ok());
+
+        assert!(
+            Pipeline::new().input(&image).to_image().unwrap() == std::borrow::Cow::Borrowed(&image)
+        );
+        assert!("[{\"unknown\": 1}]".parse::<Pipeline>().is_err());
+    }
+
+    #[test]
+    fn noise() {
+        // 白色背景上的黑色方块，一条横穿的细线和一个噪点
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(30, 12, |x, y| {
+            if (5..12).contains(&x) && (2..10).contains(&y) {
+                image::Rgba([0, 0, 0, 255])
+            } else if y == 6 || (25..27).contains(&x) && (1..3).contains(&y) {
+                image::Rgba([60, 60, 60, 255])
+            } else {
+                image::Rgba([250, 250, 250, 255])
+            }
+        }));
 
-        let mut result = ddddocr
-            .classification_probability(read_image("image/3.png"))
-            .unwrap();
+        let removal = NoiseRemoval {
+            max_line_width: 2,
+            ..Default::default()
+        };
+        let cleaned = removal.clean(&image).unwrap().to_rgba8();
+        assert_eq!(cleaned[(8, 5)], image::Rgba([0, 0, 0, 255]));
+        assert_eq!(cleaned[(8, 6)], image::Rgba([0, 0, 0, 255]));
+        assert_eq!(cleaned[(20, 6)], image::Rgba([250, 250, 250, 255]));
+        assert_eq!(cleaned[(25, 1)], image::Rgba([250, 250, 250, 255]));
+
+        // 默认不去除干扰线，细线与方块连通，不会被当作噪点
+        let cleaned = NoiseRemoval::default().clean(&image).unwrap().to_rgba8();
+        assert_eq!(cleaned[(20, 6)], image::Rgba([60, 60, 60, 255]));
+        assert_eq!(cleaned[(25, 1)], image::Rgba([250, 250, 250, 255]));
+
+        // 细字体的文字使用默认参数不会被去除，例如笔画宽度为 1 的 "L"
+        let thin = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 16, |x, y| {
+            if x == 4 && (2..14).contains(&y) || y == 13 && (4..12).contains(&x) {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        }));
+        assert_eq!(NoiseRemoval::default().clean(&thin).unwrap(), thin);
+
+        let removal = serde_json::from_str::<NoiseRemoval>(r#"{"minArea": 2}"#).unwrap();
+        assert_eq!(removal.min_area, 2);
+        assert_eq!(
+            removal.max_line_width,
+            NoiseRemoval::default().max_line_width
+        );
 
-        println!("识别结果: {}", result.get_text());
-        println!("识别可信度: {}", result.get_confidence());
+        let pipeline =
+            r#"[{"removeNoise": {"maxLineWidth": 2}}, "otsu"]"#.parse::<Pipeline>().unwrap();
+        let cleaned = pipeline.input(&image).to_image().unwrap().to_luma8();
+        assert_eq!(cleaned[(20, 6)], image::Luma([255]));
+    }
 
-        // 哦呀，看来数据有点儿太多了，小心卡死哦！
-        // println!("概率: {}", result.json());
+    #[test]
+    fn inversion() {
+        // 深色背景上的浅色方块
+        let light = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 16, |x, y| {
+            if (10..22).contains(&x) && (4..12).contains(&y) {
+                image::Rgb([220, 220, 220])
+            } else {
+                image::Rgb([30, 30, 30])
+            }
+        }));
+
+        let mut dark = light.clone();
+        dark.invert();
+
+        assert!(is_light_text(&light));
+        assert!(!is_light_text(&dark));
+
+        // 中间只比背景亮一点，例如渐变背景，不认为是浅色文字
+        let flat = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(32, 16, |x, y| {
+            if (10..22).contains(&x) && (4..12).contains(&y) {
+                image::Rgb([140, 140, 140])
+            } else {
+                image::Rgb([130, 130, 130])
+            }
+        }));
+
+        assert!(!is_light_text(&flat));
+        assert!(!Inversion::Auto.should_invert(&flat));
+
+        let result = Inversion::Auto.apply(&light).to_rgb8();
+        assert_eq!(result[(0, 0)], image::Rgb([255, 255, 255]));
+        assert_eq!(result[(15, 8)], image::Rgb([0, 0, 0]));
+
+        // 已经是深色文字，只拉伸对比度
+        let result = Inversion::Auto.apply(&dark).to_rgb8();
+        assert_eq!(result[(0, 0)], image::Rgb([255, 255, 255]));
+        assert_eq!(result[(15, 8)], image::Rgb([0, 0, 0]));
+
+        let result = Inversion::On.apply(&dark).to_rgb8();
+        assert_eq!(result[(0, 0)], image::Rgb([0, 0, 0]));
+
+        assert!(matches!(
+            Inversion::Off.apply(&light),
+            std::borrow::Cow::Borrowed(_)
+        ));
+
+        assert_eq!("auto".parse::<Inversion>().unwrap(), Inversion::Auto);
+        assert!("maybe".parse::<Inversion>().is_err());
+
+        let pipeline = r#"[{"inversion": "auto"}]"#.parse::<Pipeline>().unwrap();
+        let result = pipeline.input(&light).to_image().unwrap().to_rgb8();
+        assert_eq!(result[(0, 0)], image::Rgb([255, 255, 255]));
     }
 
     #[test]
-    fn classification_filter() {
+    fn png_fix_pipeline() {
+        // 把 3.png 的浅色背景换成透明黑色，得到透明黑色背景的 png
+        let source = image::load_from_memory(&read_image("image/3.png"))
+            .unwrap()
+            .to_rgba8();
+        let transparent = image::RgbaImage::from_fn(source.width(), source.height(), |x, y| {
+            let pixel = source[(x, y)];
+
+            if image::Pixel::to_luma(&pixel)[0] > 128 {
+                image::Rgba([0, 0, 0, 0])
+            } else {
+                image::Rgba([pixel[0], pixel[1], pixel[2], 255])
+            }
+        });
+        let background = transparent
+            .enumerate_pixels()
+            .find(|(_, _, v)| v[3] == 0)
+            .map(|(x, y, _)| (x, y))
+            .unwrap();
+
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(transparent)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        // 去掉透明度通道的步骤，如果在 png_fix 之前执行，背景会变成黑色
+        #[derive(Debug)]
+        struct DropAlpha;
+
+        impl PreprocessStage for DropAlpha {
+            fn apply(&self, image: image::DynamicImage) -> Result<image::DynamicImage> {
+                Ok(image::DynamicImage::ImageRgb8(image.to_rgb8()))
+            }
+        }
+
+        let options = ClassificationOptions {
+            png_fix: PngFix::Auto,
+            pipeline: Pipeline::new().stage(DropAlpha),
+            ..Default::default()
+        };
+
+        let (image, png_fix) = options.preprocess(&png).unwrap();
+        assert!(png_fix);
+        assert_eq!(image.to_rgb8()[background], image::Rgb([255, 255, 255]));
+
+        // 流水线执行之后已经没有透明度通道，无法判断
+        assert!(!PngFix::Auto.resolve(options.pipeline.input(&png)).unwrap());
+
         let ddddocr = ddddocr_classification().unwrap();
+        let result = ddddocr.classification_with_options(&png, &options).unwrap();
 
-        println!(
-            "{}",
-            ddddocr
-                .classification_with_filter(read_image("image/4.png"), "green")
-                .unwrap()
+        assert!(result.png_fix);
+        assert_eq!(
+            result.text,
+            ddddocr.classification(read_image("image/3.png")).unwrap()
         );
+    }
 
-        println!(
-            "{}",
-            ddddocr
-                .classification_with_filter(
-                    read_image("image/4.png"),
-                    [((40, 50, 50), (80, 255, 255))]
-                )
-                .unwrap()
-        );
+    #[test]
+    fn png_fix() {
+        // 透明黑色背景上的不透明黑色文字
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(20, 10, |x, _| {
+            if (5..15).contains(&x) {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            }
+        }));
+
+        assert!(needs_png_fix(&image));
+        assert!(PngFix::Auto.resolve(&image).unwrap());
+        assert!(!PngFix::Off.resolve(&image).unwrap());
+
+        // 透明部分已经是白色，或者没有透明度通道
+        let white = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            20,
+            10,
+            image::Rgba([255, 255, 255, 0]),
+        ));
+        assert!(!needs_png_fix(&white));
+        assert!(!needs_png_fix(&image::DynamicImage::ImageRgb8(
+            image.to_rgb8()
+        )));
+        assert!(PngFix::On.resolve(&white).unwrap());
+
+        assert_eq!("auto".parse::<PngFix>().unwrap(), PngFix::Auto);
+        assert_eq!("true".parse::<PngFix>().unwrap(), PngFix::On);
+        assert_eq!(PngFix::from(false), PngFix::Off);
+        assert!("maybe".parse::<PngFix>().is_err());
+
+        // 单通道模型也会填充透明部分，背景是白色而不是黑色
+        let charset = Charset {
+            word: false,
+            image: [-1, 16],
+            channel: 1,
+            charset: Vec::new(),
+        };
+        let preprocessor = Preprocessor::new(&charset, Normalization::ddddocr(1)).unwrap();
+
+        let fixed = preprocessor.tensor(&image, true).unwrap();
+        assert!((fixed[[0, 0, 0]] - 1.0).abs() < 1e-3);
+
+        let unfixed = preprocessor.tensor(&image, false).unwrap();
+        assert!((unfixed[[0, 0, 0]] + 1.0).abs() < 1e-3);
     }
 
     #[test]
-    fn classification() {
-        let ddddocr = ddddocr_classification().unwrap();
+    fn calibration() {
+        // 过于自信：原始可信度为 0.9，但是只有 60% 是正确的
+        let samples = (0..100)
+            .map(|i| (0.9, i % 5 < 3))
+            .chain((0..100).map(|i| (0.5, i % 5 < 1)))
+            .collect::<Vec<_>>();
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/1.png")).unwrap()
-        );
+        let temperature = Calibration::fit(&samples, CalibrationMethod::Temperature).unwrap();
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/2.png")).unwrap()
-        );
+        assert!(temperature.apply(0.9) < 0.9);
+        assert!(temperature.apply(0.9) > temperature.apply(0.5));
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/3.png")).unwrap()
-        );
+        let isotonic = Calibration::fit(&samples, CalibrationMethod::Isotonic).unwrap();
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/4.png")).unwrap()
+        assert!((isotonic.apply(0.9) - 0.6).abs() < 1e-6);
+        assert!((isotonic.apply(0.5) - 0.2).abs() < 1e-6);
+        assert!((isotonic.apply(0.7) - 0.4).abs() < 1e-6);
+        assert!((isotonic.apply(1.0) - 0.6).abs() < 1e-6);
+
+        let json = serde_json::to_string(&isotonic).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Calibration>(&json).unwrap(),
+            isotonic
         );
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/su.png")).unwrap()
+        assert!(matches!(
+            Calibration::fit(&[], CalibrationMethod::Isotonic),
+            Err(Error::InvalidCalibration(_))
+        ));
+        assert_eq!(
+            Calibration::path_for_charset("model/common.json"),
+            std::path::Path::new("model/common.calibration.json")
         );
+
+        // 校准后的可信度
+        let mut result = CharacterProbability {
+            calibration: Some(isotonic),
+            ..CharacterProbability::from_probability(&["", "a"], vec![vec![0.1, 0.9]])
+        };
+
+        assert!((result.get_raw_confidence() - 0.9).abs() < 1e-6);
+        assert!((result.get_confidence() - 0.6).abs() < 1e-6);
     }
 
     #[test]
-    fn classification_old() {
-        let ddddocr = ddddocr_classification_old().unwrap();
+    fn calibration_dataset() {
+        let dir = "image/calibration";
+        let images = labeled_images(dir).unwrap();
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/1.png")).unwrap()
-        );
+        assert_eq!(images.len(), 4);
+        assert!(images.iter().any(|(_, label)| label == "8a62n1"));
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/2.png")).unwrap()
+        let ddddocr = ddddocr_classification().unwrap();
+        let mut samples = images
+            .iter()
+            .map(|(path, label)| {
+                let mut result = ddddocr.classification_probability(path).unwrap();
+
+                (result.get_raw_confidence(), result.get_text() == label)
+            })
+            .collect::<Vec<_>>();
+        samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let accuracy = samples.iter().filter(|v| v.1).count() as f64 / samples.len() as f64;
+
+        // 温度缩放保持可信度的顺序，结果在 (0, 1) 之内
+        let temperature = ddddocr
+            .fit_calibration(dir, CalibrationMethod::Temperature)
+            .unwrap();
+        let calibrated = samples
+            .iter()
+            .map(|v| temperature.apply(v.0))
+            .collect::<Vec<_>>();
+
+        assert!(calibrated.iter().all(|&v| v > 0.0 && v < 1.0));
+        assert!(calibrated.windows(2).all(|v| v[0] <= v[1]));
+
+        // 保序回归是单调的，最低和最高的可信度分别不高于和不低于数据集的准确率
+        let isotonic = ddddocr
+            .fit_calibration(dir, CalibrationMethod::Isotonic)
+            .unwrap();
+        let calibrated = samples
+            .iter()
+            .map(|v| isotonic.apply(v.0))
+            .collect::<Vec<_>>();
+
+        assert!(calibrated.iter().all(|&v| (0.0..=1.0).contains(&v)));
+        assert!(calibrated.windows(2).all(|v| v[0] <= v[1]));
+        assert!(calibrated[0] <= accuracy + 1e-9);
+        assert!(calibrated[calibrated.len() - 1] >= accuracy - 1e-9);
+    }
+
+    #[test]
+    fn check_confidence() {
+        let mut result = CharacterProbability::from_probability(
+            &["", "a", "b"],
+            vec![
+                vec![0.1, 0.9, 0.0],
+                vec![0.9, 0.1, 0.0],
+                vec![0.4, 0.0, 0.6],
+                vec![0.5, 0.0, 0.5],
+            ],
         );
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/3.png")).unwrap()
+        let recognition = result.check_confidence(None, None);
+
+        assert_eq!(recognition.text, "ab");
+        assert!(recognition.is_accepted());
+        assert!((recognition.confidence - 0.725).abs() < 1e-6);
+        assert_eq!(recognition.char_confidence.len(), 2);
+        assert!((recognition.char_confidence[1] - 0.6).abs() < 1e-6);
+
+        // b 的可信度低于字符阈值，但仍然返回最好的猜测
+        let recognition = result.check_confidence(Some(0.7), Some(0.8));
+
+        assert_eq!(recognition.status, RecognitionStatus::Unsure);
+        assert_eq!(recognition.text, "ab");
+
+        assert!(!result.check_confidence(Some(0.8), None).is_accepted());
+        assert!(result.check_confidence(Some(0.7), Some(0.6)).is_accepted());
+
+        // 贪心解码的结果与 check_confidence 相同
+        let recognition = result.check_text_confidence("ab", None, None);
+
+        assert!((recognition.confidence - 0.725).abs() < 1e-6);
+        assert!((recognition.char_confidence[0] - 0.9).abs() < 1e-6);
+        assert!((recognition.char_confidence[1] - 0.6).abs() < 1e-6);
+
+        // 其他解码方法的结果，可信度对应这个结果的路径
+        let recognition = result.check_text_confidence("a", Some(0.7), None);
+
+        assert_eq!(recognition.text, "a");
+        assert!((recognition.confidence - 0.675).abs() < 1e-6);
+        assert_eq!(recognition.char_confidence.len(), 1);
+        assert!((recognition.char_confidence[0] - 0.9).abs() < 1e-6);
+        assert_eq!(recognition.status, RecognitionStatus::Unsure);
+
+        let aligned = result.align_text("a", 40).unwrap();
+
+        assert_eq!((aligned[0].start, aligned[0].end), (0, 1));
+        assert_eq!((aligned[0].x1, aligned[0].x2), (0, 9));
+
+        // 不可能出现的结果
+        let recognition = result.check_text_confidence("ba", None, None);
+
+        assert_eq!(recognition.confidence, 0.0);
+        assert_eq!(recognition.char_confidence, [0.0, 0.0]);
+        assert!(result.align_text("ba", 40).is_none());
+    }
+
+    #[test]
+    fn text_normalizer() {
+        let digit = CharsetRange::Digit.characters(&[]);
+
+        // 没有开启任何处理的时候保持不变
+        assert_eq!(TextNormalizer::new().normalize("ＯＩ５", &digit), "ＯＩ５");
+
+        let normalizer = TextNormalizer::standard();
+
+        assert_eq!(normalizer.normalize("ＯＩ５①", &[]), "OI51");
+        assert_eq!(normalizer.normalize("ＯＩ５①", &digit), "0151");
+        assert_eq!(normalizer.normalize("O1S", &["O".to_string()]), "O1S");
+
+        let normalizer = TextNormalizer::new()
+            .fullwidth(true)
+            .map('|', "1")
+            .confusables(["9gq"]);
+
+        assert_eq!(normalizer.normalize("｜ｑ㎏", &digit), "19㎏");
+        assert_eq!(
+            normalizer.normalize("9", &CharsetRange::Lowercase.characters(&[])),
+            "g"
         );
 
-        println!(
-            "{}",
-            ddddocr.classification(read_image("image/4.png")).unwrap()
+        let normalizer = serde_json::from_str::<TextNormalizer>(
+            r#"{"fullwidth": true, "confusables": ["0Oo"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(normalizer.normalize("ｏ1", &digit), "01");
+    }
+
+    #[test]
+    fn case_folding() {
+        let charset = ["", "O", "o", "S", "s", "0", "ß"].map(String::from);
+
+        // 概率分散在 O 和 o 上，贪心解码会选中 0
+        let probability = vec![vec![0.1, 0.25, 0.25, 0.0, 0.0, 0.4, 0.0]];
+        let mut result = CharacterProbability {
+            log_probability: Some(
+                probability
+                    .iter()
+                    .map(|row| row.iter().map(|v: &f32| v.ln()).collect())
+                    .collect(),
+            ),
+            ..CharacterProbability::from_probability(&charset, probability)
+        };
+
+        assert_eq!(result.get_text(), "0");
+
+        result.fold_case(LetterCase::Upper);
+
+        assert_eq!(result.charset, ["", "O", "S", "0", "ß"]);
+        assert_eq!(result.get_text(), "O");
+        assert!((result.probability[0][1] - 0.5).abs() < 1e-6);
+        assert!((result.log_probability.as_ref().unwrap()[0][1] - 0.5f32.ln()).abs() < 1e-6);
+        assert!(result.log_probability.as_ref().unwrap()[0][2].is_infinite());
+
+        assert_eq!(LetterCase::Lower.convert("Ａ"), "ａ");
+        assert_eq!(LetterCase::Upper.convert("ß"), "ß");
+        assert_eq!(
+            case_folding::case_variants(&["o".to_string(), "".to_string()], &charset),
+            ["o", "", "O"]
         );
     }
 
     #[test]
-    fn classification_bbox() {
-        let input = read_image("image/6.jpg");
-        let ddddocr = ddddocr_detection().unwrap();
-        let result = ddddocr.detection(input.clone()).unwrap();
-        let ddddocr = ddddocr_classification().unwrap();
-        let result = ddddocr.classification_bbox(input, &result).unwrap();
+    fn beam_search() {
+        let charset = ["", "a", "b"].map(String::from);
+        let probability = vec![vec![0.4, 0.35, 0.25], vec![0.4, 0.35, 0.25]];
 
-        println!("{:?}", result);
+        // 贪心解码的结果是空字符串，但是 "a" 的所有路径加起来概率更高
+        let result = ctc_beam_search(&probability, &charset, 3, 3);
+
+        assert_eq!(
+            result.iter().map(|v| v.text.as_str()).collect::<Vec<_>>(),
+            ["a", "b", ""]
+        );
+        assert!((result[0].probability - 0.4025).abs() < 1e-6);
+
+        // 第二个时间步 "a" 不在概率最高的 2 个字符里，但是前缀 "a" 的重复概率仍然要保留
+        let charset = ["", "a", "b", "c"].map(String::from);
+        let probability = vec![vec![0.5, 0.5, 0.0, 0.0], vec![0.2, 0.2, 0.3, 0.3]];
+
+        let result = ctc_beam_search(&probability, &charset, 2, 1);
+        assert_eq!(result[0].text, "a");
+        assert!((result[0].probability - 0.2).abs() < 1e-6);
     }
 
     #[test]
-    fn detection() {
-        let ddddocr = ddddocr_detection().unwrap();
-        let input = read_image("image/5.jpg");
-        let result = ddddocr.detection(input.clone()).unwrap();
+    fn pattern_search() {
+        let charset = ["", "a", "b", "1", "2"].map(String::from);
 
-        println!("{:?}", result);
+        // 模型认为是 "ab1"，但是格式要求 1 个字母和 2 个数字
+        let probability = vec![
+            vec![0.1, 0.7, 0.1, 0.05, 0.05],
+            vec![0.1, 0.05, 0.5, 0.05, 0.3],
+            vec![0.1, 0.05, 0.05, 0.7, 0.1],
+        ];
 
-        // 绘制红框
-        let mut image = image::load_from_memory(&input).unwrap().to_rgb8();
+        let result = ctc_beam_search(&probability, &charset, 10, 1);
+        assert_eq!(result[0].text, "ab1");
 
-        for v in result {
-            for i in v.x1..=v.x2 {
-                image[(i, v.y1)] = *image::Rgb::from_slice(&[237, 28, 36]);
-            }
+        let pattern = Pattern::try_from(r"^[a-z]\d{2}$").unwrap();
+        let result = ctc_pattern_search(&probability, &charset, &pattern, 10, 2).unwrap();
 
-            for i in v.x1..=v.x2 {
-                image[(i, v.y2)] = *image::Rgb::from_slice(&[237, 28, 36]);
-            }
+        assert_eq!(result[0].text, "a21");
 
-            for i in v.y1..=v.y2 {
-                image[(v.x1, i)] = *image::Rgb::from_slice(&[237, 28, 36]);
-            }
+        let result = ctc_pattern_search(&probability, &charset, &Pattern::from(2), 10, 1).unwrap();
+        assert_eq!(result[0].text.chars().count(), 2);
 
-            for i in v.y1..=v.y2 {
-                image[(v.x2, i)] = *image::Rgb::from_slice(&[237, 28, 36]);
-            }
-        }
+        let pattern = Pattern::from(vec![CharsetRange::Digit; 3]);
+        let result = ctc_pattern_search(&probability, &charset, &pattern, 10, 1).unwrap();
+        assert!(result[0].text.chars().all(|c| c.is_ascii_digit()));
 
-        image.save("./output1.jpg").unwrap();
+        for i in ["a{2", "[a-", "(ab", "*a", r"[\D]", "a{3,1}"] {
+            assert!(
+                matches!(Pattern::try_from(i), Err(Error::InvalidPattern(_))),
+                "{}",
+                i
+            );
+        }
 
-        let input = read_image("image/6.jpg");
-        let result = ddddocr.detection(input.clone()).unwrap();
+        assert!(Pattern::try_from(r"(?:ab|c)+[0-9_-]*\w?.{1,}").is_ok());
+    }
 
-        println!("{:?}", result);
+    #[test]
+    fn lexicon_search() {
+        let charset = ["", "a", "b"].map(String::from);
+        let probability = vec![vec![0.2, 0.7, 0.1], vec![0.3, 0.1, 0.6]];
 
-        // 绘制红框
-        let mut image = image::load_from_memory(&input).unwrap().to_rgb8();
+        // "a" 的路径有 "aa"、"a_"、"_a"，概率之和是 0.07 + 0.21 + 0.02
+        assert!((ctc_probability(&probability, &charset, "a") - 0.3).abs() < 1e-6);
+        assert!((ctc_probability(&probability, &charset, "ab") - 0.42).abs() < 1e-6);
+        assert_eq!(ctc_probability(&probability, &charset, "aa"), 0.0);
+        assert_eq!(ctc_probability(&probability, &charset, "c"), 0.0);
 
-        for v in result {
-            for i in v.x1..=v.x2 {
-                image[(i, v.y1)] = *image::Rgb::from_slice(&[237, 28, 36]);
-            }
+        let lexicon = Lexicon::from(vec!["ba", "ab", " a ", "aa", "abc", "c", "ab"]);
+        assert_eq!(lexicon.words(), ["ba", "ab", "a", "aa", "abc", "c"]);
 
-            for i in v.x1..=v.x2 {
-                image[(i, v.y2)] = *image::Rgb::from_slice(&[237, 28, 36]);
-            }
+        let result = ctc_lexicon_search(&probability, &charset, &lexicon).unwrap();
 
-            for i in v.y1..=v.y2 {
-                image[(v.x1, i)] = *image::Rgb::from_slice(&[237, 28, 36]);
-            }
+        assert_eq!(result.text, "ab");
+        assert!((result.probability - 0.42).abs() < 1e-6);
+        assert!((result.margin.unwrap() - (0.42f64 / 0.3).ln()).abs() < 1e-6);
 
-            for i in v.y1..=v.y2 {
-                image[(v.x2, i)] = *image::Rgb::from_slice(&[237, 28, 36]);
-            }
-        }
+        let result = ctc_lexicon_search(&probability, &charset, &Lexicon::new(["b", "c"])).unwrap();
+        assert_eq!(result.text, "b");
+        assert_eq!(result.margin, None);
 
-        image.save("./output2.jpg").unwrap();
+        assert!(ctc_lexicon_search(&probability, &charset, &Lexicon::new(["c"])).is_none());
     }
 
     #[test]
-    fn slide_match() {
-        let result =
-            crate::slide_match(read_image("image/hk.png"), read_image("image/bg.png")).unwrap();
+    fn language_model() {
+        let model = NgramModel::train(["一己之见", "一己之私", "已经"], 3);
+        let charset = ["", "己", "已", "一", "之", "见"].map(String::from);
 
-        println!("{:?}", result);
+        // 模型更倾向于字形相似的 "已"
+        let probability = vec![
+            vec![0.1, 0.0, 0.0, 0.9, 0.0, 0.0],
+            vec![0.1, 0.4, 0.5, 0.0, 0.0, 0.0],
+            vec![0.1, 0.0, 0.0, 0.0, 0.9, 0.0],
+            vec![0.1, 0.0, 0.0, 0.0, 0.0, 0.9],
+        ];
 
-        let result =
-            crate::slide_match(read_image("image/a.png"), read_image("image/b.png")).unwrap();
+        let result = ctc_beam_search(&probability, &charset, 10, 1);
+        assert_eq!(result[0].text, "一已之见");
 
-        println!("{:?}", result);
+        let result = ctc_language_model_search(&probability, &charset, &model, 1.0, 10, 2);
 
-        let result =
-            crate::simple_slide_match(read_image("image/a.png"), read_image("image/b.png"))
-                .unwrap();
+        assert_eq!(result[0].text, "一己之见");
 
-        println!("{:?}", result);
+        // 每个上文之后的概率之和为 1
+        let vocabulary = ["一", "己", "之", "见", "私", "已", "经", "</s>", "<unk>"];
+
+        for history in [&["<s>", "一"][..], &["一", "己"], &["之"], &[]] {
+            let sum = vocabulary
+                .iter()
+                .map(|v| model.log_probability(history, v).exp())
+                .sum::<f64>();
+
+            assert!((sum - 1.0).abs() < 1e-6, "{:?} {}", history, sum);
+        }
+
+        let arpa = model.to_arpa().parse::<NgramModel>().unwrap();
+        assert_eq!(arpa.order(), 3);
+
+        for i in ["一己之见", "已之", "未知"] {
+            assert!((arpa.score(i) - model.score(i)).abs() < 1e-4);
+        }
+
+        assert!(model.score("一己之见") > model.score("一已之见"));
+        assert!(matches!(
+            "foo".parse::<NgramModel>(),
+            Err(Error::InvalidLanguageModel(_))
+        ));
     }
 
     #[test]
-    fn comparison_match() {
-        let result =
-            crate::slide_comparison(read_image("image/c.jpg"), read_image("image/d.jpg")).unwrap();
-        println!("{:?}", result);
+    fn align() {
+        let charset = ["", "a", "b"].map(String::from);
+        let probability = vec![
+            vec![0.1, 0.8, 0.1],
+            vec![0.1, 0.6, 0.3],
+            vec![0.9, 0.05, 0.05],
+            vec![0.2, 0.1, 0.7],
+        ];
+
+        let result = ctc_align(&probability, &charset, 40);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].text, "a");
+        assert_eq!((result[0].start, result[0].end), (0, 2));
+        assert_eq!((result[0].x1, result[0].x2), (0, 19));
+        assert!((result[0].confidence - 0.8).abs() < 1e-6);
+        assert_eq!(result[1].text, "b");
+        assert_eq!((result[1].x1, result[1].x2), (30, 39));
+    }
+
+    #[test]
+    fn image_input() {
+        let bgr = (1, 1, PixelLayout::Bgr8, &[1u8, 2, 3][..]);
+        assert_eq!(bgr.to_image().unwrap().to_rgb8().into_raw(), [3, 2, 1]);
+
+        assert!(matches!(
+            (2, 2, PixelLayout::Rgba8, &[0u8; 4][..]).to_image(),
+            Err(Error::InvalidPixelBuffer {
+                expected: 16,
+                actual: 4
+            })
+        ));
+
+        let target = image::load_from_memory(&read_image("image/c.jpg")).unwrap();
+        let background = std::path::Path::new("image/d.jpg");
+
+        assert_eq!(
+            crate::slide_comparison(&target, background).unwrap(),
+            crate::slide_comparison(target.to_rgb8(), read_image("image/d.jpg")).unwrap()
+        );
+    }
+
+    #[test]
+    fn error() {
+        assert!(matches!(
+            CharsetRange::try_from(8),
+            Err(Error::InvalidCharsetRange(8))
+        ));
+
+        assert!(matches!(
+            ColorFilter::try_from("pink"),
+            Err(Error::UnknownColor(_))
+        ));
+
+        assert!(matches!(
+            crate::slide_match(read_image("image/bg.png"), read_image("image/hk.png")),
+            Err(Error::ImageTooSmall { .. })
+        ));
     }
 
     #[test]
@@ -1,5 +1,6 @@
 use base64::prelude::*;
 use clap::ArgGroup;
+use clap::CommandFactory;
 use clap::Parser;
 use ddddocr::*;
 use enable_ansi_support::enable_ansi_support;
@@ -26,6 +27,7 @@ use salvo::prelude::*;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
+use std::collections::HashMap;
 use std::num::NonZero;
 use std::sync::LazyLock;
 use std::sync::OnceLock;
@@ -37,18 +39,15 @@ use tracing::warn;
 use tracing_subscriber::EnvFilter;
 
 static ARGS: OnceLock<Args> = OnceLock::new();
-static OCR: OnceLock<Ddddocr> = OnceLock::new();
-static DET: OnceLock<Ddddocr> = OnceLock::new();
-static CACHE: LazyLock<Mutex<LruCache<String, Vec<String>>>> =
+static OCR: OnceLock<Vec<(String, OcrModel)>> = OnceLock::new();
+static DET: OnceLock<DetectionModel> = OnceLock::new();
+static LEXICONS: OnceLock<HashMap<String, Lexicon>> = OnceLock::new();
+static LANGUAGE_MODEL: OnceLock<NgramModel> = OnceLock::new();
+static CACHE: LazyLock<Mutex<LruCache<(String, String), Vec<String>>>> =
     LazyLock::new(|| Mutex::new(LruCache::new(NonZero::new(64).unwrap())));
 
 #[derive(Parser, Debug, Clone)]
 #[clap(
-    group(
-        ArgGroup::new("ocr_old")
-            .args(&["ocr", "old"])
-            .multiple(false)
-    ),
     group(
         ArgGroup::new("mcp_only_mcp")
             .args(&["mcp", "only_mcp"])
@@ -67,11 +66,11 @@ struct Args {
     #[arg(long)]
     only_mcp: bool,
 
-    /// 开启内容识别，与 old 互斥。
+    /// 开启内容识别，模型名称为 default。
     #[arg(long)]
     ocr: bool,
 
-    /// 开启旧版模型内容识别，与 ocr 互斥。
+    /// 开启旧版模型内容识别，模型名称为 old，可以与 ocr 同时开启。
     #[arg(long)]
     old: bool,
 
@@ -86,7 +85,8 @@ struct Args {
     /// 全局默认字符集，用于概率识别，  
     /// 如果 API 未提供字符集，则使用此参数，  
     /// 当值为 0~7 时，表示选择内置字符集，
-    /// 其他值表示自定义字符集，例如 "0123456789+-x/="，  
+    /// 以 `expr:` 开头时表示字符范围表达式，例如 "expr:cjk + digit"、"expr:all - lower - upper"、"expr:U+4E00..U+9FFF"，
+    /// 其他值表示自定义字符集，例如 "0123456789+-x/="，即使看起来像表达式，例如 "lower" 也只包含 l、o、w、e、r 这几个字符，  
     /// 如果未设置，则使用完整字符集，不做限制。
     #[arg(long)]
     ocr_charset_range: Option<String>,
@@ -97,6 +97,17 @@ struct Args {
     #[arg(long, default_value_t = { "model/common.onnx".to_string() })]
     ocr_path: String,
 
+    /// 旧版内容识别模型以及字符集路径，
+    /// 如果你开启了 features 的 inline-model 选项（默认开启），则不用管这个选项，
+    /// 模型 model/common_old.onnx 和字符集 model/common_old.json 要同名。
+    #[arg(long, default_value_t = { "model/common_old.onnx".to_string() })]
+    old_path: String,
+
+    /// 自定义内容识别模型，格式为 `名称=路径`，可以多次使用，
+    /// 例如 `--ocr-model digit=model/digit.onnx`，字符集与模型同名，API 通过 model 参数按名称选择模型。
+    #[arg(long)]
+    ocr_model: Vec<String>,
+
     /// 目标检测模型路径，
     /// 如果你开启了 features 的 inline-model 选项（默认开启），则不用管这个选项，除非你想使用自定义模型。
     #[arg(long, default_value_t = { "model/common_det.onnx".to_string() })]
@@ -106,6 +117,74 @@ struct Args {
     /// 即 https 的支持。
     #[arg(long)]
     acme: Option<String>,
+
+    /// 算子内部的并行线程数，
+    /// 如果未设置，则由 ONNX Runtime 决定。
+    #[arg(long)]
+    intra_threads: Option<usize>,
+
+    /// 算子之间的并行线程数，
+    /// 设置后会开启并行执行模式。
+    #[arg(long)]
+    inter_threads: Option<usize>,
+
+    /// 图优化等级，
+    /// 可选 disable、basic、extended、all。
+    #[arg(long)]
+    graph_optimization_level: Option<GraphOptimizationLevel>,
+
+    /// 是否开启 CPU 内存池，例如 `--memory-arena false`。
+    #[arg(long)]
+    memory_arena: Option<bool>,
+
+    /// 是否开启内存模式优化，例如 `--memory-pattern false`。
+    #[arg(long)]
+    memory_pattern: Option<bool>,
+
+    /// 执行提供者，按顺序注册，用逗号分隔，例如 `xnnpack,cpu`，
+    /// 可选 cpu、xnnpack、xnnpack:线程数、onednn、cuda、cuda:显卡编号，
+    /// 除了 cpu 之外，都需要开启 features 的同名选项。
+    #[arg(long, value_delimiter = ',')]
+    execution_providers: Vec<ExecutionProvider>,
+
+    /// 预加载的词典，格式为 `名称=路径`，可以多次使用，
+    /// 例如 `--lexicon idiom=idiom.txt --lexicon word=word.txt`，
+    /// 文件每行一个词语，API 通过 lexicon 参数按名称选择词典。
+    #[arg(long)]
+    lexicon: Vec<String>,
+
+    /// 字符 n-gram 语言模型的路径，ARPA 格式，
+    /// 加载后识别结果会用语言模型重新打分，让通顺的结果胜过字形相似的无意义结果，仅限于使用官方模型。
+    #[arg(long)]
+    language_model: Option<String>,
+
+    /// 语言模型的默认权重，越大越偏向通顺的结果，
+    /// 默认为 0，即只有 API 提供了 language_model_weight 才使用语言模型，
+    /// 大于 0 时所有请求都会使用语言模型，这时候不能使用自定义模型。
+    #[arg(long, default_value_t = 0.0)]
+    language_model_weight: f64,
+
+    /// 默认内容识别模型的可信度校准文件，
+    /// 如果未设置，则使用字符集旁边的校准文件（如果存在），例如 model/common.calibration.json，
+    /// 其他模型总是使用字符集旁边的校准文件。
+    #[arg(long)]
+    ocr_calibration: Option<String>,
+
+    /// 校准工具，使用标注数据集拟合默认内容识别模型的可信度校准，保存到字符集旁边，然后退出，
+    /// 文件名中第一个 `_` 之前的部分是标签，例如 `a3bc_0001.png`，会使用 `--ocr-charset-range`。
+    #[arg(long)]
+    calibrate: Option<String>,
+
+    /// 校准方法，可选 temperature、isotonic。
+    #[arg(long, default_value = "temperature")]
+    calibration_method: CalibrationMethod,
+}
+
+impl Args {
+    /// 是否加载了至少一个内容识别模型。
+    fn ocr_enabled(&self) -> bool {
+        self.ocr || self.old || !self.ocr_model.is_empty()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -113,19 +192,85 @@ struct OCRRequest {
     /// 要进行识别的图片，base64 编码。
     image: String,
 
-    /// 如果 png_fix 为 true，则支持透明黑色背景的 png 图片。
-    png_fix: Option<bool>,
+    /// 如果 png_fix 为 true，则支持透明黑色背景的 png 图片，
+    /// `"auto"` 表示根据透明度通道自动判断，默认为 `"auto"`，实际使用的设置在响应的 png_fix 中返回。
+    png_fix: Option<Value>,
 
     /// 是否返回概率信息。
     probability: Option<bool>,
 
+    /// 是否返回对数概率信息，即每个时间步的 log-softmax，概率很小的时候比 probability 更精确。
+    log_probability: Option<bool>,
+
+    /// 限定字符范围时，是否把每个时间步的概率在限定的字符和空白上重新归一化，使它们之和为 1。
+    renormalize: Option<bool>,
+
     /// 限定字符范围，只对本次 ocr 生效，
     /// 如果参数是 0 到 7，对应内置的字符集，
+    /// 以 `expr:` 开头时表示字符范围表达式，例如 `"expr:cjk + digit"`、`"expr:all - lower - upper"`、`"expr:U+4E00..U+9FFF"`，
     /// 除此之外的参数，表示自定义字符集，例如 `"0123456789+-x/="`。
     charset_range: Option<String>,
 
     /// 颜色过滤，例如 `red` 或 `["red", "blue"]` 或 `[[[0, 50, 50], [10, 255, 255]]]`。
     color_filter: Option<Value>,
+
+    /// 限定识别结果的格式，返回满足格式的概率最高的结果，
+    /// 例如正则表达式 `"\\d{4}"`，固定长度 `4`，长度范围 `{"min": 4, "max": 6}`，
+    /// 或者每个位置的字符范围 `["Digit", "Digit", {"Other": "+-x"}]`。
+    pattern: Option<Value>,
+
+    /// 使用启动时预加载的词典，返回词典中概率最高的词语，例如 `idiom`，不能与 pattern 同时使用。
+    lexicon: Option<String>,
+
+    /// 语言模型的权重，只对本次 ocr 生效，0 表示不使用语言模型，
+    /// 需要启动时通过 `--language-model` 加载，如果未提供，则使用 `--language-model-weight`。
+    language_model_weight: Option<f64>,
+
+    /// 不区分大小写，每个时间步只有大小写不同的字符的概率会相加，
+    /// `true` 或 `"lower"` 表示结果使用小写字母，`"upper"` 表示结果使用大写字母。
+    case_insensitive: Option<Value>,
+
+    /// 识别结果的后处理，例如 `{"nfkc": true, "fullwidth": true, "mapping": {"|": "1"}, "confusables": ["0Oo", "5Ss"]}`，
+    /// 形近字符替换使用 charset_range 作为允许的字符范围，例如只允许数字的时候 `O` 会变成 `0`。
+    normalize: Option<Value>,
+
+    /// 整体可信度的阈值，低于阈值时 status 为 `unsure`，text 仍然是最好的猜测，
+    /// 与 pattern、lexicon 或者语言模型同时使用时，可信度根据返回的 text 计算。
+    min_confidence: Option<f64>,
+
+    /// 单个字符可信度的阈值，任意一个字符低于阈值时 status 为 `unsure`。
+    min_char_confidence: Option<f64>,
+
+    /// 使用的内容识别模型，`"default"` 表示新版模型，`"old"` 表示旧版模型，
+    /// 也可以是 `--ocr-model` 加载的自定义模型的名称，如果未提供，则使用第一个加载的模型。
+    model: Option<String>,
+
+    /// 组合所有已经加载的官方内容识别模型的结果，可选 `"vote"`、`"confidence"`、`"fusion"`，
+    /// 至少需要加载两个官方模型，例如 `--ocr --old`，自定义模型不参与组合，不能与 model、pattern、lexicon 同时使用。
+    ensemble: Option<String>,
+
+    /// 测试时增强，用多个图片变体识别同一张图片，然后组合结果，不能与 ensemble、pattern、lexicon 同时使用，
+    /// `true` 表示使用默认的变体，也可以是变体列表，例如 `["original", {"resize": "nearest"}, {"pad": 4}, {"crop": 2}, "contrast", "pngFix", "invert"]`。
+    tta: Option<Value>,
+
+    /// 测试时增强组合结果的方法，可选 `"vote"`、`"confidence"`、`"fusion"`，默认为 `"vote"`。
+    tta_strategy: Option<String>,
+
+    /// 识别之前的图片预处理步骤，按顺序执行，在 png_fix 之后、color_filter 之前，
+    /// 例如 `["grayscale", {"median": 1}, "otsu", {"close": 1}]`，可用的步骤见 `BuiltinStage`。
+    preprocess: Option<Value>,
+
+    /// 去除干扰线和噪点，在 preprocess 之前执行，`true` 表示使用默认参数（只去除噪点），
+    /// 也可以是参数，例如 `{"minArea": 8, "maxLineWidth": 2, "minLineLength": 8, "inpaintRadius": 2}`。
+    remove_noise: Option<Value>,
+
+    /// 是否返回 png_fix、预处理、颜色过滤和反转之后的图片，用于调试预处理的参数。
+    return_image: Option<bool>,
+
+    /// 把浅色文字反转为深色文字并拉伸对比度，在 color_filter 之后执行，
+    /// `"auto"` 表示根据边缘与中间的亮度自动判断，`true` 或 `"on"` 表示总是反转，默认为 `"off"`，
+    /// 不是 `"off"` 的时候即使不需要反转也会拉伸对比度。
+    invert: Option<Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -135,6 +280,31 @@ struct OCRResponse {
 
     /// 概率信息。
     probability: Option<Vec<Vec<f32>>>,
+
+    /// 对数概率信息。
+    log_probability: Option<Vec<Vec<f32>>>,
+
+    /// 限定的字符范围中不在模型字符集里的字符，它们不会出现在概率信息中。
+    unknown: Option<Vec<String>>,
+
+    /// 使用词典时，识别结果与第二名的对数概率之差，越大越可靠，
+    /// 如果词典中只有一个词语有可能，则为空。
+    margin: Option<f64>,
+
+    /// 设置了可信度阈值时，`accepted` 表示可信，`unsure` 表示建议刷新验证码。
+    status: Option<String>,
+
+    /// 设置了可信度阈值、使用组合识别或者测试时增强时，识别结果的整体可信度。
+    confidence: Option<f64>,
+
+    /// 设置了 return_image 时，png_fix、预处理、颜色过滤和反转之后的图片，base64 编码的 png。
+    image: Option<String>,
+
+    /// 实际使用的 png_fix，png_fix 为 `"auto"` 时是自动判断的结果。
+    png_fix: Option<bool>,
+
+    /// 使用组合识别或者测试时增强时实际使用的方法，`"fusion"` 无法使用的时候是 `"confidence"`。
+    strategy: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -198,6 +368,12 @@ struct StatusResponse {
 
     /// 已经开启的功能。
     enabled_features: Vec<String>,
+
+    /// 已经加载的词典名称。
+    lexicons: Vec<String>,
+
+    /// 已经加载的内容识别模型名称，第一个是默认模型。
+    models: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -209,72 +385,347 @@ struct APIResponse<T> {
 
 #[endpoint(responses((status_code = 200, body = APIResponse<OCRResponse>)))]
 async fn route_ocr(req: JsonBody<OCRRequest>, res: &mut Response) -> anyhow::Result<()> {
-    let image = BASE64_STANDARD.decode(&req.image)?;
-    let png_fix = req.png_fix.unwrap_or_default();
-    let probability = req.probability.unwrap_or_default();
+    let mut pipeline = Pipeline::new();
+
+    match req.remove_noise.clone() {
+        Some(Value::Bool(true)) => pipeline.push(NoiseRemoval::default()),
+        Some(Value::Bool(false)) | None => {}
+        Some(v) => pipeline.push(serde_json::from_value::<NoiseRemoval>(v)?),
+    }
+
+    if let Some(v) = req.preprocess.clone() {
+        for stage in serde_json::from_value::<Vec<BuiltinStage>>(v)? {
+            pipeline.push(stage);
+        }
+    }
+
     let color_filter = if let Some(v) = req.color_filter.clone() {
         Some(serde_json::from_value::<ColorFilter>(v)?)
     } else {
         None
     };
 
-    let charset_range = if let Some(ref v) = req.charset_range {
-        let ocr_charset_range = match v.as_str() {
-            "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" => {
-                CharsetRange::from(v.parse::<i32>().unwrap())
+    let inversion = match req.invert.clone() {
+        Some(Value::Bool(true)) => Inversion::On,
+        Some(Value::Bool(false)) | None => Inversion::Off,
+        Some(Value::String(v)) => v.parse::<Inversion>()?,
+        Some(v) => anyhow::bail!("invalid invert: {}", v),
+    };
+
+    let image = BASE64_STANDARD.decode(&req.image)?;
+    let png_fix = match req.png_fix.clone() {
+        Some(Value::Bool(v)) => PngFix::from(v),
+        Some(Value::String(v)) => v.parse::<PngFix>()?,
+        Some(v) => anyhow::bail!("invalid png_fix: {}", v),
+        None => PngFix::Auto,
+    };
+    let probability = req.probability.unwrap_or_default();
+    let log_probability = req.log_probability.unwrap_or_default();
+    let renormalize = req.renormalize.unwrap_or_default();
+
+    let pattern = match req.pattern.clone() {
+        Some(Value::String(v)) => Some(Pattern::try_from(v)?),
+        Some(v) => Some(serde_json::from_value::<Pattern>(v)?),
+        None => None,
+    };
+
+    let (model_name, ocr) = ocr_model(req.model.as_deref())?;
+
+    let lexicon = match req.lexicon {
+        Some(ref v) => Some(
+            LEXICONS
+                .get()
+                .and_then(|lexicons| lexicons.get(v))
+                .ok_or_else(|| anyhow::anyhow!("unknown lexicon: {}", v))?,
+        ),
+        None => None,
+    };
+
+    if pattern.is_some() && lexicon.is_some() {
+        anyhow::bail!("pattern and lexicon cannot be used together");
+    }
+
+    let language_model = match (LANGUAGE_MODEL.get(), req.language_model_weight) {
+        (Some(model), v) => Some((
+            model,
+            v.unwrap_or(ARGS.get().unwrap().language_model_weight),
+        )),
+        (None, Some(v)) if v > 0.0 => anyhow::bail!("no language model is loaded"),
+        (None, _) => None,
+    }
+    .filter(|(_, v)| *v > 0.0);
+
+    let case = match req.case_insensitive.clone() {
+        Some(Value::Bool(v)) => v.then_some(LetterCase::Lower),
+        Some(v) => Some(serde_json::from_value::<LetterCase>(v)?),
+        None => None,
+    };
+
+    let normalizer = match req.normalize.clone() {
+        Some(v) => Some(serde_json::from_value::<TextNormalizer>(v)?),
+        None => None,
+    };
+
+    let ensemble = match req.ensemble {
+        Some(ref v) => {
+            if official_models().len() < 2 {
+                anyhow::bail!(
+                    "the ensemble needs at least two official models, start the server with --ocr --old"
+                );
             }
-            v => CharsetRange::from(v),
-        };
 
+            if req.model.is_some() || pattern.is_some() || lexicon.is_some() {
+                anyhow::bail!("ensemble cannot be used with model, pattern or lexicon");
+            }
+
+            Some(v.parse::<EnsembleStrategy>()?)
+        }
+        None => None,
+    };
+
+    let tta = match req.tta.clone() {
+        Some(Value::Bool(v)) => v.then(|| DEFAULT_AUGMENTATIONS.to_vec()),
+        Some(v) => {
+            let augmentations = serde_json::from_value::<Vec<Augmentation>>(v)?;
+
+            if augmentations.is_empty() {
+                anyhow::bail!("tta needs at least one augmentation");
+            }
+
+            Some(augmentations)
+        }
+        None => None,
+    };
+
+    if tta.is_some() && (ensemble.is_some() || pattern.is_some() || lexicon.is_some()) {
+        anyhow::bail!("tta cannot be used with ensemble, pattern or lexicon");
+    }
+
+    let tta_strategy = match req.tta_strategy {
+        Some(ref v) => v.parse::<EnsembleStrategy>()?,
+        None => EnsembleStrategy::Vote,
+    };
+
+    let ranges = req
+        .charset_range
+        .as_deref()
+        .map(parse_charset_range)
+        .transpose()?;
+
+    let charset_range = if let (Some(v), Some(ranges)) = (&req.charset_range, &ranges) {
         Some(CharsetRange::Charset(
             CACHE
                 .lock()
                 .await
-                .get_or_insert(v.to_string(), || {
-                    OCR.get().unwrap().calc_ranges(ocr_charset_range)
-                })
+                .try_get_or_insert((model_name.clone(), v.to_string()), || {
+                    ocr.calc_ranges(ranges.clone())
+                })?
                 .clone(),
         ))
     } else {
         None
     };
 
-    let (text, probability) = if charset_range.is_some() || probability {
+    let options = ClassificationOptions {
+        png_fix,
+        pipeline,
+        filter: color_filter,
+        inversion,
+        ranges: charset_range.clone(),
+        ..Default::default()
+    };
+
+    // 只解码一次，png_fix 根据原图判断，并且在所有预处理之前执行，
+    // 测试时增强需要使用原图重新预处理，其他方式都识别预处理之后的图片
+    let (source, image, png_fix) = spawn_blocking({
+        let options = options.clone();
+
+        move || -> anyhow::Result<_> {
+            let source = image.to_image()?.into_owned();
+            let (image, png_fix) = options.preprocess(&source)?;
+
+            Ok((source, image, png_fix))
+        }
+    })
+    .await??;
+
+    let preprocessed_image = if req.return_image.unwrap_or_default() {
+        let image = image.clone();
+
+        Some(
+            spawn_blocking(move || -> anyhow::Result<String> {
+                let mut buffer = std::io::Cursor::new(Vec::new());
+                image.write_to(&mut buffer, image::ImageFormat::Png)?;
+
+                Ok(BASE64_STANDARD.encode(buffer.into_inner()))
+            })
+            .await??,
+        )
+    } else {
+        None
+    };
+
+    let mut response = if let Some(strategy) = ensemble {
+        // 字符范围在每个模型的字符集上分别计算，而不是使用默认模型计算的结果
+        let options = ClassificationOptions {
+            png_fix: png_fix.into(),
+            ranges,
+            ..options
+        };
+
+        let result = spawn_blocking(move || {
+            Ensemble::new(official_models()).classification_with_options(source, &options, strategy)
+        })
+        .await??
+        .ensemble
+        .ok_or_else(|| anyhow::anyhow!("the ensemble has no models"))?;
+
+        combined_response(result, req.min_confidence, req.min_char_confidence)
+    } else if let Some(augmentations) = tta {
+        let options = ClassificationOptions {
+            png_fix: png_fix.into(),
+            augmentations,
+            strategy: tta_strategy,
+            ..options
+        };
+
+        let result = spawn_blocking(move || ocr.classification_with_options(source, &options))
+            .await??
+            .ensemble
+            .ok_or_else(|| anyhow::anyhow!("tta needs at least one augmentation"))?;
+
+        combined_response(result, req.min_confidence, req.min_char_confidence)
+    } else if charset_range.is_some()
+        || probability
+        || log_probability
+        || pattern.is_some()
+        || lexicon.is_some()
+        || language_model.is_some()
+        || case.is_some()
+        || req.min_confidence.is_some()
+        || req.min_char_confidence.is_some()
+    {
         let mut result = spawn_blocking({
-            let color_filter = color_filter.clone();
             let charset_range = charset_range.clone();
 
-            move || {
-                OCR.get().unwrap().classification_probability_with_options(
+            move || match (log_probability, case) {
+                (true, Some(case)) => ocr
+                    .classification_log_probability_case_insensitive_with_options(
+                        image,
+                        false,
+                        None,
+                        charset_range,
+                        case,
+                    ),
+                (true, None) => ocr.classification_log_probability_with_options(
                     image,
-                    png_fix,
-                    color_filter,
+                    false,
+                    None,
                     charset_range,
-                )
+                ),
+                (false, Some(case)) => ocr
+                    .classification_probability_case_insensitive_with_options(
+                        image,
+                        false,
+                        None,
+                        charset_range,
+                        case,
+                    ),
+                (false, None) => {
+                    ocr.classification_probability_with_options(image, false, None, charset_range)
+                }
             }
         })
         .await??;
 
-        (
-            result.get_text().to_string(),
-            probability.then_some(result.probability),
-        )
-    } else {
-        let text = spawn_blocking({
-            let color_filter = color_filter.clone();
+        if renormalize {
+            result.renormalize();
+        }
+
+        let searched = pattern.is_some() || lexicon.is_some() || language_model.is_some();
 
-            move || {
-                OCR.get()
-                    .unwrap()
-                    .classification_with_options(image, png_fix, color_filter)
+        let (text, margin) = match (pattern, lexicon) {
+            (Some(v), _) => {
+                let text = result
+                    .pattern_search(&v, DEFAULT_BEAM_WIDTH, 1)?
+                    .pop()
+                    .ok_or_else(|| anyhow::anyhow!("no result matches the pattern"))?
+                    .text;
+
+                (text, None)
             }
-        })
-        .await??;
+            (None, Some(v)) => {
+                let result = result
+                    .lexicon_search(v)
+                    .ok_or_else(|| anyhow::anyhow!("no word in the lexicon is possible"))?;
+
+                (result.text, result.margin)
+            }
+            (None, None) => match language_model {
+                Some((model, weight)) => {
+                    let text = result
+                        .language_model_search(model, weight, DEFAULT_BEAM_WIDTH, 1)
+                        .pop()
+                        .map(|v| v.text)
+                        .unwrap_or_default();
+
+                    (text, None)
+                }
+                None => (result.get_text().to_string(), None),
+            },
+        };
 
-        (text, None)
+        // 可信度要对应返回的结果，而不是贪心解码的结果
+        let recognition =
+            (req.min_confidence.is_some() || req.min_char_confidence.is_some()).then(|| {
+                if searched {
+                    result.check_text_confidence(&text, req.min_confidence, req.min_char_confidence)
+                } else {
+                    result.check_confidence(req.min_confidence, req.min_char_confidence)
+                }
+            });
+
+        OCRResponse {
+            text,
+            probability: probability.then_some(result.probability),
+            log_probability: result.log_probability,
+            unknown: Some(result.unknown),
+            margin,
+            status: recognition.as_ref().map(|v| {
+                match v.status {
+                    RecognitionStatus::Accepted => "accepted",
+                    RecognitionStatus::Unsure => "unsure",
+                }
+                .to_string()
+            }),
+            confidence: recognition.map(|v| v.confidence),
+            image: None,
+            png_fix: None,
+            strategy: None,
+        }
+    } else {
+        let text = spawn_blocking(move || ocr.classification(image)).await??;
+
+        OCRResponse {
+            text,
+            probability: None,
+            log_probability: None,
+            unknown: None,
+            margin: None,
+            status: None,
+            confidence: None,
+            image: None,
+            png_fix: None,
+            strategy: None,
+        }
     };
 
-    let response = OCRResponse { text, probability };
+    response.image = preprocessed_image;
+    response.png_fix = Some(png_fix);
+
+    if let Some(normalizer) = normalizer {
+        response.text = ocr.normalize_text(&response.text, &normalizer, charset_range)?;
+    }
 
     let response = APIResponse {
         code: 200,
@@ -292,6 +743,42 @@ async fn route_ocr(req: JsonBody<OCRRequest>, res: &mut Response) -> anyhow::Res
     Ok(())
 }
 
+/// 组合识别和测试时增强的响应，设置了可信度阈值时同时返回状态。
+fn combined_response(
+    result: EnsembleResult,
+    min_confidence: Option<f64>,
+    min_char_confidence: Option<f64>,
+) -> OCRResponse {
+    let recognition = (min_confidence.is_some() || min_char_confidence.is_some())
+        .then(|| result.check_confidence(min_confidence, min_char_confidence));
+
+    OCRResponse {
+        text: result.text,
+        probability: None,
+        log_probability: None,
+        unknown: None,
+        margin: None,
+        status: recognition.map(|v| {
+            match v.status {
+                RecognitionStatus::Accepted => "accepted",
+                RecognitionStatus::Unsure => "unsure",
+            }
+            .to_string()
+        }),
+        confidence: Some(result.confidence),
+        image: None,
+        png_fix: None,
+        strategy: Some(
+            match result.strategy {
+                EnsembleStrategy::Vote => "vote",
+                EnsembleStrategy::Confidence => "confidence",
+                EnsembleStrategy::Fusion => "fusion",
+            }
+            .to_string(),
+        ),
+    }
+}
+
 #[endpoint(responses((status_code = 200, body = APIResponse<DETResponse>)))]
 async fn route_det(req: JsonBody<DETRequest>, res: &mut Response) -> anyhow::Result<()> {
     let image = BASE64_STANDARD.decode(&req.image)?;
@@ -389,7 +876,7 @@ async fn route_status(res: &mut Response) {
     let args = ARGS.get().unwrap();
     let mut enabled_features = Vec::new();
 
-    if args.ocr || args.old {
+    if args.ocr_enabled() {
         enabled_features.push("ocr".to_string());
     }
 
@@ -401,6 +888,10 @@ async fn route_status(res: &mut Response) {
         enabled_features.push("slide".to_string());
     };
 
+    if official_models().len() >= 2 {
+        enabled_features.push("ensemble".to_string());
+    };
+
     if args.mcp {
         enabled_features.push("mcp".to_string());
     };
@@ -409,9 +900,23 @@ async fn route_status(res: &mut Response) {
         enabled_features.push("only_mcp".to_string());
     };
 
+    let mut lexicons = LEXICONS
+        .get()
+        .map(|v| v.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    lexicons.sort();
+
+    let models = OCR
+        .get()
+        .map(|v| v.iter().map(|(name, _)| name.clone()).collect())
+        .unwrap_or_default();
+
     let response = StatusResponse {
         service_status: "running".to_string(),
         enabled_features,
+        lexicons,
+        models,
     };
 
     let response = APIResponse {
@@ -494,7 +999,9 @@ async fn route_mcp(
                     let args = ARGS.get().unwrap();
 
                     match v.params.name.as_ref() {
-                        "ocr" if args.ocr => route_ocr.handle(req, depot, res, ctrl).await,
+                        "ocr" if args.ocr_enabled() => {
+                            route_ocr.handle(req, depot, res, ctrl).await
+                        }
                         "det" if args.det => route_det.handle(req, depot, res, ctrl).await,
                         "slide_match" if args.slide => {
                             route_slide_match.handle(req, depot, res, ctrl).await
@@ -604,7 +1111,7 @@ async fn main() {
         )
         .init();
 
-    if !(args.ocr || args.old) && !args.det && !args.slide {
+    if !args.ocr_enabled() && !args.det && !args.slide {
         warn!("no enabled features, default enabled all features");
 
         args.ocr = true;
@@ -617,6 +1124,15 @@ async fn main() {
 
     init_ocr(&args);
 
+    if let Some(ref dir) = args.calibrate {
+        calibrate(&args, dir);
+        return;
+    }
+
+    init_lexicon(&args);
+
+    init_language_model(&args);
+
     if args.slide {
         info!("slide enabled successfully");
     }
@@ -635,7 +1151,7 @@ async fn main() {
         .hoop(salvo::prelude::Logger::new())
         .push(Router::with_path("/status").get(route_status));
 
-    if (args.ocr || args.old) && !args.only_mcp {
+    if args.ocr_enabled() && !args.only_mcp {
         router = router.push(Router::with_path("/ocr").post(route_ocr));
     }
 
@@ -674,89 +1190,289 @@ async fn main() {
     }
 }
 
+/// 解析字符范围，0 到 7 是内置字符集，`expr:` 开头的是字符范围表达式，其他的都是自定义字符集。
+fn parse_charset_range(v: &str) -> anyhow::Result<CharsetRange> {
+    Ok(match v.strip_prefix("expr:") {
+        Some(expression) => expression.parse()?,
+        None => match v {
+            "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" => {
+                CharsetRange::try_from(v.parse::<i32>()?)?
+            }
+            v => CharsetRange::from(v),
+        },
+    })
+}
+
 fn ocr_charset_range(args: &Args) -> Option<CharsetRange> {
-    args.ocr_charset_range.as_ref().map(|v| match v.as_str() {
-        "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" => {
-            CharsetRange::from(v.parse::<i32>().unwrap())
-        }
-        v => CharsetRange::from(v),
+    args.ocr_charset_range.as_deref().map(|v| {
+        parse_charset_range(v)
+            .unwrap_or_else(|e| cli_error(format!("invalid --ocr-charset-range: {}", e)))
     })
 }
 
-#[cfg(feature = "inline-model")]
-fn init_ocr(args: &Args) {
-    let ocr_charset_range = ocr_charset_range(args);
+/// 以命令行参数错误的形式退出，而不是 panic。
+fn cli_error(message: impl std::fmt::Display) -> ! {
+    Args::command()
+        .error(clap::error::ErrorKind::ValueValidation, message)
+        .exit()
+}
 
-    if args.ocr {
-        let mut ddddocr = ddddocr_classification().unwrap();
+/// 已经加载的官方内容识别模型，用于组合识别，自定义模型不能计算概率。
+fn official_models() -> Vec<&'static OcrModel<'static>> {
+    OCR.get()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|(_, v)| v)
+        .filter(|v| !v.is_diy())
+        .collect()
+}
 
-        if let Some(v) = ocr_charset_range {
-            ddddocr.set_ranges(v)
-        };
+/// 按名称选择内容识别模型，如果未提供名称，则使用第一个加载的模型。
+fn ocr_model(name: Option<&str>) -> anyhow::Result<&'static (String, OcrModel<'static>)> {
+    let models = OCR.get().map(Vec::as_slice).unwrap_or_default();
+
+    match name {
+        Some(name) => models
+            .iter()
+            .find(|(v, _)| v == name)
+            .ok_or_else(|| anyhow::anyhow!("unknown model: {}", name)),
+        None => models
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("no ocr model is loaded")),
+    }
+}
 
-        OCR.set(ddddocr).unwrap();
+/// 自定义内容识别模型的名称和路径。
+fn custom_models(args: &Args) -> Vec<(String, String)> {
+    args.ocr_model
+        .iter()
+        .map(|v| {
+            let (name, path) = v.split_once('=').unwrap_or_else(|| {
+                cli_error(format!(
+                    "invalid --ocr-model {}: the ocr model should be in the format name=path",
+                    v
+                ))
+            });
 
-        info!("ocr enabled successfully");
-    } else if args.old {
-        let mut ddddocr = ddddocr_classification_old().unwrap();
+            if name == "default" || name == "old" {
+                cli_error(format!(
+                    "invalid --ocr-model {}: the ocr model name {} is reserved",
+                    v, name
+                ));
+            }
 
-        if let Some(v) = ocr_charset_range {
-            ddddocr.set_ranges(v)
-        };
+            (name.to_string(), path.to_string())
+        })
+        .collect()
+}
 
-        OCR.set(ddddocr).unwrap();
+/// 内容识别模型的路径，字符集与模型同名。
+fn model_path(args: &Args, name: &str) -> std::path::PathBuf {
+    match name {
+        "default" => args.ocr_path.clone().into(),
+        "old" => args.old_path.clone().into(),
+        _ => custom_models(args)
+            .into_iter()
+            .find(|(v, _)| v == name)
+            .map(|(_, path)| path.into())
+            .unwrap_or_else(|| cli_error(format!("unknown ocr model: {}", name))),
+    }
+}
 
-        info!("old enabled successfully");
+/// 字符集旁边的校准文件的路径。
+fn calibration_path(args: &Args, name: &str) -> std::path::PathBuf {
+    Calibration::path_for_charset(model_path(args, name).with_extension("json"))
+}
+
+/// `--ocr-calibration` 只对名称为 default 的模型生效，其他模型使用字符集旁边的校准文件（如果存在）。
+fn ocr_calibration(args: &Args, name: &str) -> Option<Calibration> {
+    match args.ocr_calibration {
+        Some(ref v) if name == "default" => {
+            Some(Calibration::with_path(v).expect("failed to load the ocr calibration"))
+        }
+        _ => {
+            let path = calibration_path(args, name);
+
+            path.is_file()
+                .then(|| Calibration::with_path(path).expect("failed to load the ocr calibration"))
+        }
     }
+}
 
-    if args.det {
-        DET.set(ddddocr_detection().unwrap()).unwrap();
+fn calibrate(args: &Args, dir: &str) {
+    let (name, ocr) = ocr_model(None).expect("the calibration tool needs an ocr model");
+    let calibration = ocr
+        .fit_calibration(dir, args.calibration_method)
+        .expect("failed to fit the calibration");
+    let path = calibration_path(args, name);
 
-        info!("det enabled successfully");
+    calibration
+        .save(&path)
+        .expect("failed to save the calibration");
+
+    info!("calibration saved to {}: {:?}", path.display(), calibration);
+}
+
+fn init_lexicon(args: &Args) {
+    let mut lexicons = HashMap::new();
+
+    for v in &args.lexicon {
+        let (name, path) = v
+            .split_once('=')
+            .expect("the lexicon should be in the format name=path");
+
+        let lexicon = Lexicon::with_path(path).expect("failed to open the lexicon file");
+
+        info!("lexicon {} loaded with {} words", name, lexicon.len());
+
+        lexicons.insert(name.to_string(), lexicon);
     }
+
+    LEXICONS.set(lexicons).unwrap();
 }
 
-#[cfg(not(feature = "inline-model"))]
-fn init_ocr(args: &Args) {
-    use std::fs::{read, read_to_string};
-    use std::path::PathBuf;
-    use std::str::FromStr;
+fn init_language_model(args: &Args) {
+    if let Some(v) = &args.language_model {
+        let model = NgramModel::with_path(v).expect("failed to open the language model file");
 
-    let ocr_charset_range = ocr_charset_range(args);
+        info!("language model loaded with order {}", model.order());
 
-    if args.ocr || args.old {
-        let mut path = PathBuf::from(args.ocr_path.clone());
+        LANGUAGE_MODEL.set(model).unwrap();
+    }
+}
 
-        let model = read(&path).expect("failed to open the ocr model file");
+fn builder(args: &Args) -> DdddocrBuilder {
+    let mut builder = DdddocrBuilder::new().execution_providers(args.execution_providers.clone());
 
-        path.set_extension("json");
+    if let Some(v) = args.intra_threads {
+        builder = builder.intra_threads(v);
+    }
 
-        let charset = read_to_string(path).expect("failed to open the ocr charset file");
+    if let Some(v) = args.inter_threads {
+        builder = builder.inter_threads(v);
+    }
+
+    if let Some(v) = args.graph_optimization_level {
+        builder = builder.optimization_level(v);
+    }
+
+    if let Some(v) = args.memory_arena {
+        builder = builder.memory_arena(v);
+    }
+
+    if let Some(v) = args.memory_pattern {
+        builder = builder.memory_pattern(v);
+    }
 
-        let mut ddddocr = Ddddocr::new(
+    builder
+}
+
+/// 从文件加载内容识别模型，字符集与模型同名。
+fn load_ocr(builder: &DdddocrBuilder, path: &str) -> OcrModel<'static> {
+    use std::str::FromStr;
+
+    let mut path = std::path::PathBuf::from(path);
+
+    let model = std::fs::read(&path).expect("failed to open the ocr model file");
+
+    path.set_extension("json");
+
+    let charset = std::fs::read_to_string(path).expect("failed to open the ocr charset file");
+
+    builder
+        .ocr(
             &model,
             Charset::from_str(&charset).expect("failed to parse charset"),
         )
-        .unwrap();
+        .unwrap()
+}
 
-        if let Some(v) = ocr_charset_range {
-            ddddocr.set_ranges(v)
-        };
+#[cfg(feature = "inline-model")]
+fn init_ocr(args: &Args) {
+    let builder = builder(args);
+    let mut models = Vec::new();
 
-        OCR.set(ddddocr).unwrap();
+    if args.ocr {
+        models.push((
+            "default".to_string(),
+            builder.ddddocr_classification().unwrap(),
+        ));
 
-        if args.ocr {
-            info!("ocr enabled successfully");
-        } else if args.old {
-            info!("old enabled successfully");
-        }
+        info!("ocr enabled successfully");
     }
 
+    if args.old {
+        models.push((
+            "old".to_string(),
+            builder.ddddocr_classification_old().unwrap(),
+        ));
+
+        info!("old enabled successfully");
+    }
+
+    init_models(args, &builder, models);
+
+    if args.det {
+        DET.set(builder.ddddocr_detection().unwrap()).unwrap();
+
+        info!("det enabled successfully");
+    }
+}
+
+#[cfg(not(feature = "inline-model"))]
+fn init_ocr(args: &Args) {
+    let builder = builder(args);
+    let mut models = Vec::new();
+
+    if args.ocr {
+        models.push(("default".to_string(), load_ocr(&builder, &args.ocr_path)));
+
+        info!("ocr enabled successfully");
+    }
+
+    if args.old {
+        models.push(("old".to_string(), load_ocr(&builder, &args.old_path)));
+
+        info!("old enabled successfully");
+    }
+
+    init_models(args, &builder, models);
+
     if args.det {
-        let model = read(args.det_path.clone()).expect("failed to open the det model file");
+        let model =
+            std::fs::read(args.det_path.clone()).expect("failed to open the det model file");
 
-        DET.set(Ddddocr::new_model(&model).unwrap()).unwrap();
+        DET.set(builder.detection(&model).unwrap()).unwrap();
 
         info!("det enabled successfully");
     }
 }
+
+/// 加载自定义模型，然后设置所有内容识别模型的字符范围和可信度校准。
+fn init_models(
+    args: &Args,
+    builder: &DdddocrBuilder,
+    mut models: Vec<(String, OcrModel<'static>)>,
+) {
+    for (name, path) in custom_models(args) {
+        models.push((name.clone(), load_ocr(builder, &path)));
+
+        info!("ocr model {} enabled successfully", name);
+    }
+
+    for (name, model) in models.iter_mut() {
+        if let Some(v) = ocr_charset_range(args) {
+            if let Err(e) = model.set_ranges(v) {
+                cli_error(format!(
+                    "invalid --ocr-charset-range for the ocr model {}: {}",
+                    name, e
+                ));
+            }
+        };
+
+        model.set_calibration(ocr_calibration(args, name));
+    }
+
+    OCR.set(models).unwrap();
+}
@@ -0,0 +1,203 @@
+use crate::{pipeline, ImageInput, PreprocessStage, Result};
+
+/// 去除文字验证码中的干扰线和噪点，假设是浅色背景上的深色文字，
+/// 先根据阈值找出前景，去除细长的干扰线，再去除面积很小的连通区域，最后用周围的背景颜色修补去除的像素。
+///
+/// json 格式例如 `{"minArea": 8, "maxLineWidth": 2, "minLineLength": 8, "inpaintRadius": 2}`，省略的字段使用默认值。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NoiseRemoval {
+    /// 灰度不大于阈值的像素是前景，如果为空，则使用 Otsu 方法自动选择阈值。
+    pub threshold: Option<u8>,
+
+    /// 像素数量小于这个值的连通区域当作噪点去除，0 表示不去除噪点。
+    pub min_area: u32,
+
+    /// 宽度不超过这个值的笔画当作干扰线，奇数会向上取整为偶数，0 表示不去除干扰线。
+    ///
+    /// 默认为 0，因为细字体的笔画与干扰线没有区别，也会被去除，需要根据验证码的线宽设置，例如 2。
+    pub max_line_width: u32,
+
+    /// 细笔画的长度（外接矩形较长的一边）不小于这个值才当作干扰线，避免去除文字本身较细的部分。
+    pub min_line_length: u32,
+
+    /// 修补时使用的邻域半径，去除的像素使用邻域中背景像素的平均颜色，邻域中没有背景时使用白色。
+    pub inpaint_radius: u32,
+}
+
+impl Default for NoiseRemoval {
+    fn default() -> Self {
+        Self {
+            threshold: None,
+            min_area: 8,
+            max_line_width: 0,
+            min_line_length: 8,
+            inpaint_radius: 2,
+        }
+    }
+}
+
+impl NoiseRemoval {
+    /// 返回去除干扰线和噪点之后的图片，可以保存下来调试参数。
+    pub fn clean<I>(&self, image: I) -> Result<image::DynamicImage>
+    where
+        I: ImageInput,
+    {
+        let image = image.to_image()?;
+
+        Ok(image::DynamicImage::ImageRgba8(self.remove(&image)))
+    }
+
+    fn remove(&self, image: &image::DynamicImage) -> image::RgbaImage {
+        let rgba = image.to_rgba8();
+        let (width, height) = rgba.dimensions();
+
+        if width == 0 || height == 0 {
+            return rgba;
+        }
+
+        let gray = pipeline::gray(image);
+        let min = gray.pixels().map(|v| v[0]).min().unwrap_or(0);
+        let max = gray.pixels().map(|v| v[0]).max().unwrap_or(0);
+
+        // 纯色图片没有前景
+        if min == max {
+            return rgba;
+        }
+
+        let threshold = self
+            .threshold
+            .unwrap_or_else(|| imageproc::contrast::otsu_level(&gray));
+
+        // 前景为 255，背景为 0
+        let foreground = image::GrayImage::from_fn(width, height, |x, y| {
+            image::Luma([if gray[(x, y)][0] <= threshold { 255 } else { 0 }])
+        });
+
+        let mut kept = foreground.clone();
+
+        if self.max_line_width > 0 {
+            // 开运算之后仍然存在的是粗笔画，其余的前景是细笔画
+            let radius = self.max_line_width.div_ceil(2).min(u8::MAX as u32) as u8;
+            let thick = imageproc::morphology::grayscale_open(
+                &foreground,
+                &imageproc::morphology::Mask::square(radius),
+            );
+            let thin = image::GrayImage::from_fn(width, height, |x, y| {
+                image::Luma([if thick[(x, y)][0] == 0 {
+                    foreground[(x, y)][0]
+                } else {
+                    0
+                }])
+            });
+
+            let (labels, components) = components(&thin);
+
+            for (x, y, label) in labels.enumerate_pixels() {
+                if label[0] != 0 && components[label[0] as usize].length() >= self.min_line_length {
+                    kept[(x, y)] = image::Luma([0]);
+                }
+            }
+        }
+
+        if self.min_area > 0 {
+            let (labels, components) = components(&kept);
+
+            for (x, y, label) in labels.enumerate_pixels() {
+                if label[0] != 0 && components[label[0] as usize].area < self.min_area {
+                    kept[(x, y)] = image::Luma([0]);
+                }
+            }
+        }
+
+        let mut result = rgba.clone();
+        let radius = self.inpaint_radius as i64;
+
+        for (x, y, pixel) in result.enumerate_pixels_mut() {
+            if foreground[(x, y)][0] == 0 || kept[(x, y)][0] != 0 {
+                continue;
+            }
+
+            // 邻域中背景像素的平均颜色
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+
+            for ny in (y as i64 - radius).max(0)..=(y as i64 + radius).min(height as i64 - 1) {
+                for nx in (x as i64 - radius).max(0)..=(x as i64 + radius).min(width as i64 - 1) {
+                    let (nx, ny) = (nx as u32, ny as u32);
+
+                    if foreground[(nx, ny)][0] == 0 {
+                        for (s, v) in sum.iter_mut().zip(rgba[(nx, ny)].0) {
+                            *s += v as u32;
+                        }
+
+                        count += 1;
+                    }
+                }
+            }
+
+            *pixel = if count > 0 {
+                image::Rgba(sum.map(|v| (v / count) as u8))
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            };
+        }
+
+        result
+    }
+}
+
+impl PreprocessStage for NoiseRemoval {
+    fn apply(&self, image: image::DynamicImage) -> Result<image::DynamicImage> {
+        Ok(image::DynamicImage::ImageRgba8(self.remove(&image)))
+    }
+}
+
+/// 连通区域的像素数量和外接矩形。
+#[derive(Debug, Clone, Copy)]
+struct Component {
+    area: u32,
+    min: (u32, u32),
+    max: (u32, u32),
+}
+
+impl Component {
+    /// 外接矩形较长的一边。
+    fn length(&self) -> u32 {
+        (self.max.0 - self.min.0 + 1).max(self.max.1 - self.min.1 + 1)
+    }
+}
+
+/// 标记八连通区域，返回每个像素的编号和每个编号的区域，编号 0 是背景。
+fn components(
+    mask: &image::GrayImage,
+) -> (
+    image::ImageBuffer<image::Luma<u32>, Vec<u32>>,
+    Vec<Component>,
+) {
+    let labels = imageproc::region_labelling::connected_components(
+        mask,
+        imageproc::region_labelling::Connectivity::Eight,
+        image::Luma([0]),
+    );
+
+    let count = labels.pixels().map(|v| v[0]).max().unwrap_or(0) as usize;
+    let mut components = vec![
+        Component {
+            area: 0,
+            min: (u32::MAX, u32::MAX),
+            max: (0, 0),
+        };
+        count + 1
+    ];
+
+    for (x, y, label) in labels.enumerate_pixels() {
+        let v = &mut components[label[0] as usize];
+
+        v.area += 1;
+        v.min = (v.min.0.min(x), v.min.1.min(y));
+        v.max = (v.max.0.max(x), v.max.1.max(y));
+    }
+
+    (labels, components)
+}
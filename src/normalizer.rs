@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use unicode_normalization::UnicodeNormalization;
+
+/// 常见的形近字符，每组中的字符容易互相认错，排在前面的优先。
+pub const DEFAULT_CONFUSABLES: &[&str] = &["0Oo", "1lIi|", "2Zz", "5Ss", "6G", "8B", "9gq"];
+
+/// 识别结果的后处理，依次进行 NFKC 规范化、全角转半角、固定替换和形近字符替换。
+///
+/// 形近字符替换需要允许的字符范围，例如网站只接受数字，`O` 会变成 `0`，
+/// 允许的字符范围为空的时候不做形近字符替换。
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TextNormalizer {
+    /// NFKC 规范化，例如 `①` 变成 `1`，`㎏` 变成 `kg`。
+    pub nfkc: bool,
+
+    /// 全角转半角，例如 `１２３` 变成 `123`，全角空格变成半角空格。
+    pub fullwidth: bool,
+
+    /// 固定的替换，总是生效，键是单个字符，例如 `{"|": "1"}`。
+    pub mapping: BTreeMap<String, String>,
+
+    /// 形近字符组，字符不在允许的字符范围中时，替换成同一组中第一个允许的字符，例如 `["0Oo", "5Ss"]`。
+    pub confusables: Vec<String>,
+}
+
+impl TextNormalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 开启 NFKC 规范化、全角转半角，并使用 `DEFAULT_CONFUSABLES`。
+    pub fn standard() -> Self {
+        Self::new()
+            .nfkc(true)
+            .fullwidth(true)
+            .confusables(DEFAULT_CONFUSABLES)
+    }
+
+    /// NFKC 规范化。
+    pub fn nfkc(mut self, enable: bool) -> Self {
+        self.nfkc = enable;
+        self
+    }
+
+    /// 全角转半角。
+    pub fn fullwidth(mut self, enable: bool) -> Self {
+        self.fullwidth = enable;
+        self
+    }
+
+    /// 添加固定的替换，from 总是替换成 to。
+    pub fn map<S>(mut self, from: char, to: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.mapping.insert(from.to_string(), to.into());
+        self
+    }
+
+    /// 添加形近字符组。
+    pub fn confusables<G, S>(mut self, groups: G) -> Self
+    where
+        G: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.confusables
+            .extend(groups.into_iter().map(|v| v.as_ref().to_string()));
+        self
+    }
+
+    /// 处理识别结果，allowed 是允许的字符范围，例如 `OcrModel::calc_ranges` 的结果，为空表示不限制。
+    pub fn normalize(&self, text: &str, allowed: &[String]) -> String {
+        let mut text = if self.nfkc {
+            text.nfkc().collect::<String>()
+        } else {
+            text.to_string()
+        };
+
+        if self.fullwidth {
+            text = text.chars().map(fullwidth_to_halfwidth).collect();
+        }
+
+        if !self.mapping.is_empty() {
+            text = text
+                .chars()
+                .map(|c| {
+                    let c = c.to_string();
+                    self.mapping.get(&c).cloned().unwrap_or(c)
+                })
+                .collect();
+        }
+
+        if allowed.is_empty() || self.confusables.is_empty() {
+            return text;
+        }
+
+        let is_allowed = |c: char| allowed.iter().any(|v| v.chars().eq(std::iter::once(c)));
+
+        text.chars()
+            .map(|c| {
+                if is_allowed(c) {
+                    return c;
+                }
+
+                self.confusables
+                    .iter()
+                    .filter(|group| group.contains(c))
+                    .flat_map(|group| group.chars())
+                    .find(|&v| is_allowed(v))
+                    .unwrap_or(c)
+            })
+            .collect()
+    }
+}
+
+/// 全角字符转半角，只处理全角 ascii 和全角空格。
+fn fullwidth_to_halfwidth(c: char) -> char {
+    match c {
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        _ => c,
+    }
+}
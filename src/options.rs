@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+
+use crate::{
+    Augmentation, CharsetRange, ColorFilter, EnsembleResult, EnsembleStrategy, ImageInput,
+    Inversion, Pipeline, PngFix, Recognition, Result,
+};
+
+/// 内容识别的选项，见 `OcrModel::classification_with_options`，省略的字段使用默认值，例如
+/// `ClassificationOptions { png_fix: PngFix::Auto, inversion: Inversion::Auto, ..Default::default() }`。
+///
+/// 设置了 ranges、可信度阈值或者 augmentations 的时候需要计算概率，仅限于使用官方模型。
+#[derive(Debug, Clone, Default)]
+pub struct ClassificationOptions {
+    /// 是否把透明部分用白色填充，支持透明黑色背景的 png 图片，在所有预处理之前对解码后的原图执行。
+    pub png_fix: PngFix,
+
+    /// 预处理流水线，在 png_fix 之后、颜色过滤之前执行。
+    pub pipeline: Pipeline,
+
+    /// 颜色过滤，例如 red 表示只识别红色。
+    pub filter: Option<ColorFilter>,
+
+    /// 浅色文字反转和对比度拉伸，在颜色过滤之后执行。
+    pub inversion: Inversion,
+
+    /// 限定字符范围，为 None 则使用 `set_ranges` 的字符范围。
+    pub ranges: Option<CharsetRange>,
+
+    /// 整体可信度的阈值，含义见 `Recognition::new`。
+    pub min_confidence: Option<f64>,
+
+    /// 每个字符可信度的阈值，含义见 `Recognition::new`。
+    pub min_char_confidence: Option<f64>,
+
+    /// 测试时增强的图片变体，例如 `DEFAULT_AUGMENTATIONS.to_vec()`，为空则不使用测试时增强，
+    /// 所有变体只运行一次推理，并且时间步数量相同，Fusion 总是可以使用。
+    pub augmentations: Vec<Augmentation>,
+
+    /// 测试时增强组合结果的方法。
+    pub strategy: EnsembleStrategy,
+}
+
+impl ClassificationOptions {
+    /// 是否设置了可信度阈值。
+    pub fn checks_confidence(&self) -> bool {
+        self.min_confidence.is_some() || self.min_char_confidence.is_some()
+    }
+
+    /// 是否需要计算概率。
+    pub(crate) fn needs_probability(&self) -> bool {
+        self.ranges.is_some() || self.checks_confidence() || !self.augmentations.is_empty()
+    }
+
+    /// 识别之前的预处理，依次执行 png_fix、预处理流水线、颜色过滤和浅色文字反转，
+    /// 返回处理之后的图片和是否使用了 png_fix，`PngFix::Auto` 根据解码后的原图判断。
+    ///
+    /// 处理之后的图片可以传给其他接受图片的接口，例如 `classification_probability_with_options`，
+    /// 这时 png_fix 应为 false，filter 应为 None。
+    pub fn preprocess<I>(&self, image: I) -> Result<(image::DynamicImage, bool)>
+    where
+        I: ImageInput,
+    {
+        let image = image.to_image()?;
+        let png_fix = self.png_fix.resolve(&*image)?;
+
+        Ok((self.apply(&image, png_fix)?.into_owned(), png_fix))
+    }
+
+    /// 使用已经决定的 png_fix 预处理解码后的原图，没有需要执行的步骤时不复制图片。
+    pub(crate) fn apply<'a>(
+        &self,
+        image: &'a image::DynamicImage,
+        png_fix: bool,
+    ) -> Result<Cow<'a, image::DynamicImage>> {
+        let mut image = Cow::Borrowed(image);
+
+        if png_fix {
+            image = Cow::Owned(crate::png_rgba_black_preprocess(&image));
+        }
+
+        if !self.pipeline.is_empty() {
+            image = Cow::Owned(self.pipeline.apply(image.into_owned())?);
+        }
+
+        if let Some(v) = &self.filter {
+            image = Cow::Owned(v.filter(&*image)?);
+        }
+
+        if self.inversion != Inversion::Off {
+            image = Cow::Owned(self.inversion.apply(&image).into_owned());
+        }
+
+        Ok(image)
+    }
+}
+
+/// `OcrModel::classification_with_options` 的结果。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Classification {
+    /// 识别结果。
+    pub text: String,
+
+    /// 是否使用了 png_fix，`PngFix::Auto` 的时候是根据解码后的原图判断的结果。
+    pub png_fix: bool,
+
+    /// 设置了可信度阈值的时候，根据阈值判断的结果，状态为 `RecognitionStatus::Unsure` 的时候建议刷新验证码。
+    pub recognition: Option<Recognition>,
+
+    /// 使用测试时增强的时候，组合的结果，members 与 augmentations 的顺序相同。
+    pub ensemble: Option<EnsembleResult>,
+}
@@ -0,0 +1,595 @@
+use std::collections::HashSet;
+
+use crate::{CharsetRange, Error, PrefixScorer, Result};
+
+/// 正则表达式展开后最多的状态数量，防止 `.{1000}{1000}` 这种写法耗尽内存。
+const MAX_STATES: usize = 10000;
+
+/// 识别结果需要满足的格式，用于 `ctc_pattern_search`。
+///
+/// 长度都是按照字符集中的字符计算的，官方模型的字符集中每个字符都是一个汉字、字母或者数字。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Pattern {
+    /// 正则表达式的子集，总是匹配整个结果，例如 `\d{4}`、`[A-Z]{2}\d{3}`、`(ab|cd)+`。
+    ///
+    /// 支持普通字符、`.`、`[a-z0-9]`、`[^0-9]`、`\d`、`\w`、`\s`、`\D`、`\W`、`\S`、
+    /// `(...)`、`|`、`?`、`*`、`+`、`{n}`、`{n,}`、`{n,m}`，`^` 和 `$` 会被忽略。
+    Regex(String),
+
+    /// 固定长度，例如 `4`。
+    Length(usize),
+
+    /// 长度范围，包含两端，例如 `{"min": 4, "max": 6}`，没有 max 表示不限制最大长度。
+    LengthRange {
+        #[serde(default)]
+        min: usize,
+        max: Option<usize>,
+    },
+
+    /// 每个位置的字符范围，结果的长度等于列表的长度，例如 `["Digit", "Digit", {"Other": "+-x"}]`。
+    Positions(Vec<CharsetRange>),
+}
+
+impl std::str::FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Parser::new(s).parse()?;
+
+        Ok(Pattern::Regex(s.to_string()))
+    }
+}
+
+impl TryFrom<&str> for Pattern {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl TryFrom<&String> for Pattern {
+    type Error = Error;
+
+    fn try_from(value: &String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<usize> for Pattern {
+    fn from(value: usize) -> Self {
+        Pattern::Length(value)
+    }
+}
+
+impl From<std::ops::RangeInclusive<usize>> for Pattern {
+    fn from(value: std::ops::RangeInclusive<usize>) -> Self {
+        Pattern::LengthRange {
+            min: *value.start(),
+            max: Some(*value.end()),
+        }
+    }
+}
+
+impl From<Vec<CharsetRange>> for Pattern {
+    fn from(value: Vec<CharsetRange>) -> Self {
+        Pattern::Positions(value)
+    }
+}
+
+impl Pattern {
+    /// 编译成自动机，charset 是模型的字符集，用于展开 `CharsetRange`。
+    pub(crate) fn automaton(&self, charset: &[String]) -> Result<Automaton> {
+        let node = match self {
+            Pattern::Regex(v) => Parser::new(v).parse()?,
+            Pattern::Length(v) => Node::Repeat {
+                node: Box::new(Node::Match(Matcher::Any)),
+                min: *v,
+                max: Some(*v),
+            },
+            Pattern::LengthRange { min, max } => {
+                if max.is_some_and(|max| max < *min) {
+                    return Err(Error::InvalidPattern(format!(
+                        "the max length {:?} is less than the min length {}",
+                        max, min
+                    )));
+                }
+
+                Node::Repeat {
+                    node: Box::new(Node::Match(Matcher::Any)),
+                    min: *min,
+                    max: *max,
+                }
+            }
+            Pattern::Positions(v) => Node::Concat(
+                v.iter()
+                    .map(|range| {
+                        Node::Match(Matcher::Set(
+                            range
+                                .clone()
+                                .characters(charset)
+                                .into_iter()
+                                .filter(|v| !v.is_empty())
+                                .collect(),
+                        ))
+                    })
+                    .collect(),
+            ),
+        };
+
+        Automaton::new(&node)
+    }
+}
+
+/// 匹配单个字符。
+#[derive(Debug, Clone)]
+enum Matcher {
+    /// 任意字符。
+    Any,
+
+    /// 字符类，例如 `[a-z0-9]`，每个元素是一个包含两端的范围。
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+
+    /// 字符集合，例如 `CharsetRange` 展开后的结果。
+    Set(HashSet<String>),
+}
+
+impl Matcher {
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            Matcher::Any => true,
+            Matcher::Set(v) => v.contains(text),
+            Matcher::Class { negated, ranges } => {
+                let mut chars = text.chars();
+
+                // 字符类只能匹配单个字符
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => {
+                        ranges.iter().any(|&(min, max)| min <= c && c <= max) != *negated
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// 正则表达式的语法树。
+#[derive(Debug, Clone)]
+enum Node {
+    Match(Matcher),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// 递归下降解析正则表达式的子集。
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(pattern: &str) -> Self {
+        Self {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        Err(Error::InvalidPattern(format!(
+            "{} at position {} of {:?}",
+            message,
+            self.pos,
+            self.chars.iter().collect::<String>()
+        )))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn parse(&mut self) -> Result<Node> {
+        let node = self.alternate()?;
+
+        if self.peek().is_some() {
+            return self.error("unexpected ')'");
+        }
+
+        Ok(node)
+    }
+
+    fn alternate(&mut self) -> Result<Node> {
+        let mut branches = vec![self.concat()?];
+
+        while self.peek() == Some('|') {
+            self.bump();
+            branches.push(self.concat()?);
+        }
+
+        Ok(if branches.len() == 1 {
+            branches.remove(0)
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node> {
+        let mut nodes = Vec::new();
+
+        while let Some(c) = self.peek() {
+            match c {
+                '|' | ')' => break,
+                '^' | '$' => {
+                    // 总是匹配整个结果，所以忽略锚点
+                    self.bump();
+                }
+                _ => nodes.push(self.repeat()?),
+            }
+        }
+
+        Ok(Node::Concat(nodes))
+    }
+
+    fn repeat(&mut self) -> Result<Node> {
+        let mut node = self.atom()?;
+
+        loop {
+            let (min, max) = match self.peek() {
+                Some('?') => (0, Some(1)),
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('{') => {
+                    self.bump();
+                    let min = self.number()?;
+                    let max = if self.peek() == Some(',') {
+                        self.bump();
+
+                        if self.peek() == Some('}') {
+                            None
+                        } else {
+                            Some(self.number()?)
+                        }
+                    } else {
+                        Some(min)
+                    };
+
+                    if self.peek() != Some('}') {
+                        return self.error("expected '}'");
+                    }
+
+                    if max.is_some_and(|max| max < min) {
+                        return self.error("the max repetition is less than the min");
+                    }
+
+                    (min, max)
+                }
+                _ => break,
+            };
+
+            self.bump();
+
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            };
+        }
+
+        Ok(node)
+    }
+
+    fn number(&mut self) -> Result<usize> {
+        let start = self.pos;
+
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+
+        match self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+        {
+            Ok(v) => Ok(v),
+            Err(_) => self.error("expected a number"),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node> {
+        match self.bump() {
+            Some('(') => {
+                // 非捕获分组和普通分组一样
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+
+                let node = self.alternate()?;
+
+                if self.bump() != Some(')') {
+                    return self.error("expected ')'");
+                }
+
+                Ok(node)
+            }
+            Some('[') => self.class(),
+            Some('.') => Ok(Node::Match(Matcher::Any)),
+            Some('\\') => {
+                let (negated, ranges) = self.escape()?;
+                Ok(Node::Match(Matcher::Class { negated, ranges }))
+            }
+            Some('?' | '*' | '+' | '{') => {
+                self.pos -= 1;
+                self.error("nothing to repeat")
+            }
+            Some(c) => Ok(Node::Match(Matcher::Class {
+                negated: false,
+                ranges: vec![(c, c)],
+            })),
+            None => self.error("unexpected end"),
+        }
+    }
+
+    /// 转义字符，返回是否取反和字符范围。
+    fn escape(&mut self) -> Result<(bool, Vec<(char, char)>)> {
+        let digit = vec![('0', '9')];
+        let word = vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+        let space = vec![('\t', '\r'), (' ', ' ')];
+
+        Ok(match self.bump() {
+            Some('d') => (false, digit),
+            Some('D') => (true, digit),
+            Some('w') => (false, word),
+            Some('W') => (true, word),
+            Some('s') => (false, space),
+            Some('S') => (true, space),
+            Some(c) => (false, vec![(c, c)]),
+            None => return self.error("unexpected end after '\\'"),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node> {
+        let negated = self.peek() == Some('^');
+
+        if negated {
+            self.bump();
+        }
+
+        let mut ranges = Vec::new();
+        let mut first = true;
+
+        loop {
+            let c = match self.bump() {
+                Some(']') if !first => break,
+                Some('\\') => {
+                    let (negated, escaped) = self.escape()?;
+
+                    if negated {
+                        return self.error("negated escapes are not supported in a class");
+                    }
+
+                    ranges.extend(escaped);
+                    first = false;
+                    continue;
+                }
+                Some(c) => c,
+                None => return self.error("expected ']'"),
+            };
+
+            first = false;
+
+            // 范围，例如 a-z，最后一个 - 是普通字符
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&v| v != ']') {
+                self.bump();
+
+                let end = match self.bump() {
+                    Some('\\') => match self.escape()? {
+                        (false, v) if v.len() == 1 && v[0].0 == v[0].1 => v[0].0,
+                        _ => return self.error("invalid range"),
+                    },
+                    Some(v) => v,
+                    None => return self.error("expected ']'"),
+                };
+
+                if end < c {
+                    return self.error("invalid range");
+                }
+
+                ranges.push((c, end));
+            } else {
+                ranges.push((c, c));
+            }
+        }
+
+        Ok(Node::Match(Matcher::Class { negated, ranges }))
+    }
+}
+
+/// 自动机的状态。
+#[derive(Debug, Default)]
+struct State {
+    epsilon: Vec<usize>,
+    transition: Option<(Matcher, usize)>,
+}
+
+/// 正则表达式编译成的非确定有限自动机，前缀束搜索的时候，每个前缀都带有一个状态集合。
+#[derive(Debug)]
+pub(crate) struct Automaton {
+    states: Vec<State>,
+    start: usize,
+    accept: usize,
+}
+
+impl Automaton {
+    fn new(node: &Node) -> Result<Self> {
+        let mut automaton = Self {
+            states: Vec::new(),
+            start: 0,
+            accept: 0,
+        };
+
+        let (start, accept) = automaton.compile(node)?;
+        automaton.start = start;
+        automaton.accept = accept;
+
+        Ok(automaton)
+    }
+
+    fn state(&mut self) -> Result<usize> {
+        if self.states.len() >= MAX_STATES {
+            return Err(Error::InvalidPattern(
+                "the pattern is too large".to_string(),
+            ));
+        }
+
+        self.states.push(State::default());
+
+        Ok(self.states.len() - 1)
+    }
+
+    /// Thompson 构造法，返回开始状态和结束状态。
+    fn compile(&mut self, node: &Node) -> Result<(usize, usize)> {
+        let start = self.state()?;
+
+        let end = match node {
+            Node::Match(v) => {
+                let end = self.state()?;
+                self.states[start].transition = Some((v.clone(), end));
+                end
+            }
+            Node::Concat(v) => {
+                let mut end = start;
+
+                for i in v {
+                    let (a, b) = self.compile(i)?;
+                    self.states[end].epsilon.push(a);
+                    end = b;
+                }
+
+                end
+            }
+            Node::Alternate(v) => {
+                let end = self.state()?;
+
+                for i in v {
+                    let (a, b) = self.compile(i)?;
+                    self.states[start].epsilon.push(a);
+                    self.states[b].epsilon.push(end);
+                }
+
+                end
+            }
+            Node::Repeat { node, min, max } => {
+                let mut end = start;
+
+                for _ in 0..*min {
+                    let (a, b) = self.compile(node)?;
+                    self.states[end].epsilon.push(a);
+                    end = b;
+                }
+
+                match max {
+                    None => {
+                        // 回到自己，可以重复任意次
+                        let hub = self.state()?;
+                        let (a, b) = self.compile(node)?;
+                        self.states[end].epsilon.push(hub);
+                        self.states[hub].epsilon.push(a);
+                        self.states[b].epsilon.push(hub);
+                        hub
+                    }
+                    Some(max) => {
+                        let last = self.state()?;
+
+                        for _ in *min..*max {
+                            let (a, b) = self.compile(node)?;
+                            self.states[end].epsilon.push(a);
+                            self.states[end].epsilon.push(last);
+                            end = b;
+                        }
+
+                        self.states[end].epsilon.push(last);
+                        last
+                    }
+                }
+            }
+        };
+
+        Ok((start, end))
+    }
+
+    /// 加上所有通过空转移可以到达的状态。
+    fn closure(&self, mut states: Vec<usize>) -> Vec<usize> {
+        let mut stack = states.clone();
+        let mut visited = states.iter().copied().collect::<HashSet<_>>();
+
+        while let Some(i) = stack.pop() {
+            for &j in &self.states[i].epsilon {
+                if visited.insert(j) {
+                    states.push(j);
+                    stack.push(j);
+                }
+            }
+        }
+
+        states.sort_unstable();
+        states
+    }
+}
+
+impl PrefixScorer for Automaton {
+    type State = Vec<usize>;
+
+    fn start(&self) -> Self::State {
+        self.closure(vec![self.start])
+    }
+
+    fn allows(&self, text: &str) -> bool {
+        self.states
+            .iter()
+            .any(|v| v.transition.as_ref().is_some_and(|(m, _)| m.matches(text)))
+    }
+
+    fn extend(&self, state: &Self::State, text: &str) -> Option<(Self::State, f64)> {
+        let next = state
+            .iter()
+            .filter_map(|&i| match &self.states[i].transition {
+                Some((m, j)) if m.matches(text) => Some(*j),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if next.is_empty() {
+            None
+        } else {
+            Some((self.closure(next), 1.0))
+        }
+    }
+
+    fn finish(&self, state: &Self::State) -> Option<f64> {
+        state.contains(&self.accept).then_some(1.0)
+    }
+}
@@ -0,0 +1,282 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use crate::{ColorFilter, Error, ImageInput, Inversion, NoiseRemoval, Result};
+
+/// 识别之前的图片预处理步骤，可以实现这个 trait 添加自定义的步骤。
+pub trait PreprocessStage: std::fmt::Debug + Send + Sync {
+    fn apply(&self, image: image::DynamicImage) -> Result<image::DynamicImage>;
+}
+
+/// 内置的预处理步骤，可以用 json 描述，
+/// 例如 `"grayscale"`、`{"threshold": 128}`、`"otsu"`、`{"adaptiveThreshold": 7}`、`{"median": 1}`、
+/// `{"gaussian": 1.0}`、`{"open": 1}`、`{"close": 1}`、`"contrast"`、`"invert"`、`{"pad": 4}`、`{"colorFilter": "red"}`、`{"removeNoise": {}}`、`{"inversion": "auto"}`。
+///
+/// 灰度化、二值化和形态学运算的结果是灰度图，透明部分会当作白色。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BuiltinStage {
+    /// 灰度化。
+    Grayscale,
+
+    /// 全局二值化，大于阈值的像素变成白色，其他变成黑色。
+    Threshold(u8),
+
+    /// 使用 Otsu 方法自动选择阈值的全局二值化。
+    Otsu,
+
+    /// 自适应二值化，参数是邻域半径，不小于邻域平均亮度的像素变成白色，适合光照不均匀的图片。
+    AdaptiveThreshold(u32),
+
+    /// 中值滤波去噪，参数是半径，适合椒盐噪点。
+    Median(u32),
+
+    /// 高斯模糊去噪，参数是标准差。
+    Gaussian(f32),
+
+    /// 形态学开运算，参数是半径，去除比周围亮的小噪点。
+    Open(u8),
+
+    /// 形态学闭运算，参数是半径，去除比周围暗的小噪点，例如白色背景上的黑点。
+    Close(u8),
+
+    /// 对比度拉伸，把最暗的灰度映射到 0，最亮的灰度映射到 255。
+    Contrast,
+
+    /// 颜色反转，透明度保持不变。
+    Invert,
+
+    /// 四周填充指定宽度的白色。
+    Pad(u32),
+
+    /// 颜色过滤，同 `ColorFilter`。
+    ColorFilter(ColorFilter),
+
+    /// 去除干扰线和噪点，同 `NoiseRemoval`。
+    RemoveNoise(NoiseRemoval),
+
+    /// 浅色文字反转和对比度拉伸，同 `Inversion`。
+    Inversion(Inversion),
+}
+
+impl PreprocessStage for BuiltinStage {
+    fn apply(&self, image: image::DynamicImage) -> Result<image::DynamicImage> {
+        if image.width() == 0 || image.height() == 0 {
+            return Ok(image);
+        }
+
+        Ok(match self {
+            BuiltinStage::Grayscale => image::DynamicImage::ImageLuma8(gray(&image)),
+            BuiltinStage::Threshold(v) => {
+                image::DynamicImage::ImageLuma8(imageproc::contrast::threshold(
+                    &gray(&image),
+                    *v,
+                    imageproc::contrast::ThresholdType::Binary,
+                ))
+            }
+            BuiltinStage::Otsu => {
+                let gray = gray(&image);
+                let level = imageproc::contrast::otsu_level(&gray);
+
+                image::DynamicImage::ImageLuma8(imageproc::contrast::threshold(
+                    &gray,
+                    level,
+                    imageproc::contrast::ThresholdType::Binary,
+                ))
+            }
+            BuiltinStage::AdaptiveThreshold(radius) => image::DynamicImage::ImageLuma8(
+                imageproc::contrast::adaptive_threshold(&gray(&image), (*radius).max(1)),
+            ),
+            BuiltinStage::Median(radius) => image::DynamicImage::ImageRgba8(
+                imageproc::filter::median_filter(&image.to_rgba8(), *radius, *radius),
+            ),
+            BuiltinStage::Gaussian(sigma) => {
+                if *sigma <= 0.0 {
+                    return Err(Error::InvalidOption(format!(
+                        "the gaussian sigma should be positive: {}",
+                        sigma
+                    )));
+                }
+
+                image.blur(*sigma)
+            }
+            BuiltinStage::Open(radius) => {
+                image::DynamicImage::ImageLuma8(imageproc::morphology::grayscale_open(
+                    &gray(&image),
+                    &imageproc::morphology::Mask::square(*radius),
+                ))
+            }
+            BuiltinStage::Close(radius) => {
+                image::DynamicImage::ImageLuma8(imageproc::morphology::grayscale_close(
+                    &gray(&image),
+                    &imageproc::morphology::Mask::square(*radius),
+                ))
+            }
+            BuiltinStage::Contrast => contrast_stretch(&image),
+            BuiltinStage::Invert => invert(image),
+            BuiltinStage::Pad(n) => pad(&image, *n),
+            BuiltinStage::ColorFilter(filter) => filter.filter(&image)?,
+            BuiltinStage::RemoveNoise(v) => v.apply(image)?,
+            BuiltinStage::Inversion(v) => PreprocessStage::apply(v, image)?,
+        })
+    }
+}
+
+impl PreprocessStage for ColorFilter {
+    fn apply(&self, image: image::DynamicImage) -> Result<image::DynamicImage> {
+        self.filter(&image)
+    }
+}
+
+/// 按顺序执行的预处理流水线。
+///
+/// 放到 `ClassificationOptions::pipeline` 中时，流水线在 png_fix 之后、颜色过滤之前执行。
+/// 也可以用 `Pipeline::input` 包装图片之后，传给任何接受 `ImageInput` 的接口，
+/// 这时流水线在解码之后马上执行，早于 png_fix，透明度通道可能已经被去掉。
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    stages: Vec<Arc<dyn PreprocessStage>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加一个步骤。
+    pub fn stage<S>(mut self, stage: S) -> Self
+    where
+        S: PreprocessStage + 'static,
+    {
+        self.push(stage);
+        self
+    }
+
+    /// 添加一个步骤。
+    pub fn push<S>(&mut self, stage: S)
+    where
+        S: PreprocessStage + 'static,
+    {
+        self.stages.push(Arc::new(stage));
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// 依次执行所有步骤。
+    pub fn apply(&self, image: image::DynamicImage) -> Result<image::DynamicImage> {
+        self.stages
+            .iter()
+            .try_fold(image, |image, v| v.apply(image))
+    }
+
+    /// 包装图片，解码之后先经过流水线。
+    pub fn input<I>(&self, image: I) -> PipelineInput<I>
+    where
+        I: ImageInput,
+    {
+        PipelineInput {
+            image,
+            pipeline: self.clone(),
+        }
+    }
+}
+
+impl From<Vec<BuiltinStage>> for Pipeline {
+    fn from(value: Vec<BuiltinStage>) -> Self {
+        value
+            .into_iter()
+            .fold(Self::new(), |pipeline, v| pipeline.stage(v))
+    }
+}
+
+/// 从 json 数组解析，例如 `["grayscale", {"median": 1}, "otsu"]`。
+impl std::str::FromStr for Pipeline {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(serde_json::from_str::<Vec<BuiltinStage>>(s)?.into())
+    }
+}
+
+/// 解码之后先经过预处理流水线的图片，见 `Pipeline::input`。
+#[derive(Debug, Clone)]
+pub struct PipelineInput<I> {
+    image: I,
+    pipeline: Pipeline,
+}
+
+impl<I> ImageInput for PipelineInput<I>
+where
+    I: ImageInput,
+{
+    fn to_image(&self) -> Result<Cow<'_, image::DynamicImage>> {
+        let image = self.image.to_image()?;
+
+        if self.pipeline.is_empty() {
+            return Ok(image);
+        }
+
+        Ok(Cow::Owned(self.pipeline.apply(image.into_owned())?))
+    }
+}
+
+/// 转换为灰度图，半透明的部分与白色混合。
+pub(crate) fn gray(image: &image::DynamicImage) -> image::GrayImage {
+    let rgba = image.to_rgba8();
+
+    image::GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        let pixel = rgba[(x, y)];
+        let luma = image::Pixel::to_luma(&pixel)[0] as u32;
+        let alpha = pixel[3] as u32;
+
+        image::Luma([((luma * alpha + 255 * (255 - alpha)) / 255) as u8])
+    })
+}
+
+/// 对比度拉伸，根据灰度的最小值和最大值拉伸每个颜色通道，透明度保持不变。
+pub(crate) fn contrast_stretch(image: &image::DynamicImage) -> image::DynamicImage {
+    let luma = image.to_luma8();
+    let min = luma.pixels().map(|v| v[0]).min().unwrap_or(0) as f32;
+    let max = luma.pixels().map(|v| v[0]).max().unwrap_or(255) as f32;
+
+    if max <= min {
+        return image.clone();
+    }
+
+    let mut image = image.to_rgba8();
+
+    for pixel in image.pixels_mut() {
+        for v in &mut pixel.0[..3] {
+            *v = ((*v as f32 - min) * 255.0 / (max - min))
+                .round()
+                .clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    image::DynamicImage::ImageRgba8(image)
+}
+
+/// 颜色反转，透明度保持不变。
+pub(crate) fn invert(mut image: image::DynamicImage) -> image::DynamicImage {
+    image.invert();
+    image
+}
+
+/// 四周填充指定宽度的白色。
+pub(crate) fn pad(image: &image::DynamicImage, n: u32) -> image::DynamicImage {
+    let mut padded = image::RgbaImage::from_pixel(
+        image.width() + n * 2,
+        image.height() + n * 2,
+        image::Rgba([255, 255, 255, 255]),
+    );
+
+    image::imageops::replace(&mut padded, &image.to_rgba8(), n as i64, n as i64);
+
+    image::DynamicImage::ImageRgba8(padded)
+}
@@ -0,0 +1,272 @@
+use crate::{Charset, Error, ImageInput, Result};
+
+/// 透明且不是白色的像素至少占这个比例时，自动模式才会使用 png_fix。
+const PNG_FIX_MIN_RATIO: f64 = 0.01;
+
+/// png_fix 的模式，把图片的透明部分用白色填充，与 png_fix 参数相同。
+///
+/// json 格式为 `"off"`、`"auto"`、`"on"`。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PngFix {
+    /// 不填充，同 `png_fix = false`。
+    #[default]
+    Off,
+
+    /// 根据透明度通道判断是否需要填充，见 `needs_png_fix`。
+    Auto,
+
+    /// 总是填充，同 `png_fix = true`。
+    On,
+}
+
+impl From<bool> for PngFix {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::On
+        } else {
+            Self::Off
+        }
+    }
+}
+
+impl std::str::FromStr for PngFix {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "off" | "false" => Self::Off,
+            "auto" => Self::Auto,
+            "on" | "true" => Self::On,
+            _ => return Err(Error::InvalidOption(format!("png_fix: {}", s))),
+        })
+    }
+}
+
+impl PngFix {
+    /// 决定是否使用 png_fix，返回值可以作为各个接口的 png_fix 参数，`Auto` 的时候需要解码图片，
+    /// `classification_with_options` 会在结果中返回判断的结果，不需要单独调用，
+    /// 已经解码的图片可以直接传入 `&DynamicImage`，避免重复解码。
+    pub fn resolve<I>(self, image: I) -> Result<bool>
+    where
+        I: ImageInput,
+    {
+        Ok(match self {
+            PngFix::Off => false,
+            PngFix::Auto => needs_png_fix(&*image.to_image()?),
+            PngFix::On => true,
+        })
+    }
+}
+
+/// 根据透明度通道判断图片是否需要 png_fix，也就是透明部分的颜色不是白色（例如透明黑色背景），
+/// 直接去掉透明度的时候背景会变成黑色，文字会消失。没有透明度通道的图片不需要。
+pub fn needs_png_fix(image: &image::DynamicImage) -> bool {
+    if !image.color().has_alpha() {
+        return false;
+    }
+
+    let rgba = image.to_rgba8();
+    let total = rgba.pixels().len();
+
+    if total == 0 {
+        return false;
+    }
+
+    let transparent = rgba
+        .pixels()
+        .filter(|v| v[3] == 0 && v.0[..3] != [255, 255, 255])
+        .count();
+
+    transparent as f64 >= total as f64 * PNG_FIX_MIN_RATIO
+}
+
+/// 图片标准化的参数，每个通道的值为 `(像素 / 255 - mean) / std`，mean 和 std 的长度等于通道数量。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Normalization {
+    pub mean: Vec<f32>,
+    pub std: Vec<f32>,
+}
+
+impl Normalization {
+    pub fn new(mean: Vec<f32>, std: Vec<f32>) -> Self {
+        Self { mean, std }
+    }
+
+    /// 官方模型使用的标准化，所有通道的 mean 和 std 都是 0.5。
+    pub fn ddddocr(channel: usize) -> Self {
+        Self::new(vec![0.5; channel], vec![0.5; channel])
+    }
+
+    /// 自定义模型使用的 ImageNet 标准化，单通道使用绿色通道的参数。
+    pub fn imagenet(channel: usize) -> Self {
+        if channel == 1 {
+            Self::new(vec![0.456], vec![0.224])
+        } else {
+            Self::new(vec![0.485, 0.456, 0.406], vec![0.229, 0.224, 0.225])
+        }
+    }
+}
+
+/// 把图片转换为内容识别模型输入的张量，缩放、通道转换、标准化都在这里完成，所有内容识别的接口共用。
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    word: bool,
+    image: [i64; 2],
+    channel: usize,
+    normalization: Normalization,
+}
+
+impl Preprocessor {
+    /// 根据字符集中的图片大小和通道数量创建，只支持 1 通道和 3 通道。
+    pub fn new(charset: &Charset, normalization: Normalization) -> Result<Self> {
+        let channel = charset.channel as usize;
+
+        if channel != 1 && channel != 3 {
+            return Err(Error::InvalidOption(format!(
+                "unsupported channel: {}",
+                charset.channel
+            )));
+        }
+
+        let mut preprocessor = Self {
+            word: charset.word,
+            image: charset.image,
+            channel,
+            normalization: Normalization::ddddocr(channel),
+        };
+
+        preprocessor.set_normalization(normalization)?;
+
+        Ok(preprocessor)
+    }
+
+    /// 通道数量。
+    pub fn channel(&self) -> usize {
+        self.channel
+    }
+
+    pub fn normalization(&self) -> &Normalization {
+        &self.normalization
+    }
+
+    /// 设置标准化的参数，mean 和 std 的长度必须等于通道数量。
+    pub fn set_normalization(&mut self, normalization: Normalization) -> Result<()> {
+        if normalization.mean.len() != self.channel || normalization.std.len() != self.channel {
+            return Err(Error::InvalidOption(format!(
+                "the normalization should have {} channels",
+                self.channel
+            )));
+        }
+
+        self.normalization = normalization;
+
+        Ok(())
+    }
+
+    /// 图片缩放后模型所需的宽度和高度，宽高比太大，缩放后的宽度超出 u32 的范围时返回错误。
+    pub fn size(&self, width: u32, height: u32) -> Result<(u32, u32)> {
+        crate::ensure_not_empty(width, height)?;
+
+        let [resize_width, resize_height] = self.image;
+
+        Ok(if resize_width == -1 {
+            if self.word {
+                (resize_height as u32, resize_height as u32)
+            } else {
+                // 使用 u64 计算，否则很宽的图片会溢出
+                let resized = u32::try_from(width as u64 * resize_height as u64 / height as u64)
+                    .map_err(|_| {
+                        Error::InvalidOption(format!(
+                            "the image is too wide to resize: {}x{}",
+                            width, height
+                        ))
+                    })?;
+
+                (resized.max(1), resize_height as u32)
+            }
+        } else {
+            (resize_width as u32, resize_height as u32)
+        })
+    }
+
+    /// 缩放图片并标准化，返回形状为 (channel, height, width) 的张量。
+    pub fn tensor(
+        &self,
+        image: &image::DynamicImage,
+        png_fix: bool,
+    ) -> Result<ndarray::Array3<f32>> {
+        let (width, height) = self.size(image.width(), image.height())?;
+
+        // 使用 ANTIALIAS (Lanczos3) 缩放图片
+        let image = image.resize_exact(width, height, image::imageops::FilterType::Lanczos3);
+
+        // 去掉透明度之前填充透明部分，否则透明黑色背景会变成黑色，单通道模型也一样
+        let image = if png_fix {
+            crate::png_rgba_black_preprocess(&image)
+        } else {
+            image
+        };
+
+        // 设置图片的通道数为模型所需的通道数，像素按行排列，每个像素的通道相邻
+        let pixels = if self.channel == 1 {
+            image.to_luma8().into_raw()
+        } else {
+            image.to_rgb8().into_raw()
+        };
+
+        let (width, height) = (width as usize, height as usize);
+        let mut data = vec![0f32; self.channel * height * width];
+
+        // 每个通道是一块连续的内存
+        for (c, plane) in data.chunks_exact_mut(height * width).enumerate() {
+            let mean = self.normalization.mean[c];
+            let std = self.normalization.std[c];
+
+            for (v, pixel) in plane.iter_mut().zip(pixels.chunks_exact(self.channel)) {
+                *v = (pixel[c] as f32 / 255.0 - mean) / std;
+            }
+        }
+
+        Ok(ndarray::Array3::from_shape_vec(
+            (self.channel, height, width),
+            data,
+        )?)
+    }
+
+    /// 把多张图片拼成一个批次，宽度不足的部分重复图片最右边的一列，返回张量和每张图片缩放后的宽度。
+    pub fn batch<T>(
+        &self,
+        images: &[T],
+        png_fix: bool,
+    ) -> Result<(ndarray::Array4<f32>, Vec<usize>)>
+    where
+        T: std::borrow::Borrow<image::DynamicImage>,
+    {
+        let tensors = images
+            .iter()
+            .map(|v| self.tensor(v.borrow(), png_fix))
+            .collect::<Result<Vec<_>>>()?;
+
+        let widths = tensors.iter().map(|v| v.shape()[2]).collect::<Vec<_>>();
+        let max_width = widths.iter().copied().max().unwrap_or(0);
+        let height = tensors.first().map_or(0, |v| v.shape()[1]);
+
+        let mut batch =
+            ndarray::Array4::<f32>::zeros((tensors.len(), self.channel, height, max_width));
+
+        for (n, (tensor, &width)) in tensors.iter().zip(&widths).enumerate() {
+            batch
+                .slice_mut(ndarray::s![n, .., .., ..width])
+                .assign(tensor);
+
+            if width > 0 && width < max_width {
+                batch
+                    .slice_mut(ndarray::s![n, .., .., width..])
+                    .assign(&tensor.slice(ndarray::s![.., .., width - 1..width]));
+            }
+        }
+
+        Ok((batch, widths))
+    }
+}
@@ -77,6 +77,132 @@ def test_routes(
                 "probability": True,
             },
         },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {
+                "image": image_b64,
+                "charset_range": "expr:cjk + '？'",
+                "probability": True,
+            },
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "pattern": "九.{4}？"},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "pattern": {"min": 4, "max": 6}},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "lexicon": "idiom"},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "language_model_weight": 1.0},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "log_probability": True},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "case_insensitive": "upper"},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {
+                "image": image_b64,
+                "min_confidence": 0.8,
+                "min_char_confidence": 0.5,
+            },
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "ensemble": "vote"},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "model": "old"},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "tta": True},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {
+                "image": image_b64,
+                "tta": ["original", {"resize": "triangle"}, "contrast"],
+                "tta_strategy": "fusion",
+            },
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {
+                "image": image_b64,
+                "preprocess": ["grayscale", {"median": 1}, "otsu", {"close": 1}],
+            },
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "remove_noise": True, "return_image": True},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {
+                "image": image_b64,
+                "remove_noise": {"minArea": 4, "maxLineWidth": 1},
+            },
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "invert": "auto"},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {"image": image_b64, "png_fix": "auto"},
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {
+                "image": image_b64,
+                "charset_range": "0",
+                "normalize": {
+                    "nfkc": True,
+                    "fullwidth": True,
+                    "confusables": ["0Oo", "1lI", "5Ss"],
+                },
+            },
+        },
+        {
+            "method": "post",
+            "path": "/ocr",
+            "json": {
+                "image": image_b64,
+                "charset_range": "A九A乘A六A等A于A？A",
+                "probability": True,
+                "renormalize": True,
+            },
+        },
         {"method": "post", "path": "/det", "json": {"image": image_b64}},
         {
             "method": "post",